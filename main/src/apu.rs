@@ -0,0 +1,808 @@
+use crate::blip::BlipBuf;
+use crate::gameboy::Model;
+
+pub const CPU_CLOCK_HZ: u32 = 4_194_304;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+pub const NR10: u16 = 0xFF10;
+pub const NR11: u16 = 0xFF11;
+pub const NR12: u16 = 0xFF12;
+pub const NR13: u16 = 0xFF13;
+pub const NR14: u16 = 0xFF14;
+pub const NR21: u16 = 0xFF16;
+pub const NR22: u16 = 0xFF17;
+pub const NR23: u16 = 0xFF18;
+pub const NR24: u16 = 0xFF19;
+pub const NR30: u16 = 0xFF1A;
+pub const NR31: u16 = 0xFF1B;
+pub const NR32: u16 = 0xFF1C;
+pub const NR33: u16 = 0xFF1D;
+pub const NR34: u16 = 0xFF1E;
+pub const NR41: u16 = 0xFF20;
+pub const NR42: u16 = 0xFF21;
+pub const NR43: u16 = 0xFF22;
+pub const NR44: u16 = 0xFF23;
+pub const NR50: u16 = 0xFF24;
+pub const NR51: u16 = 0xFF25;
+pub const NR52: u16 = 0xFF26;
+pub const WAVE_RAM: u16 = 0xFF30;
+
+// Bits that always read back as 1, for 0xFF10..=0xFF2F.
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // unused, NR21-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // unused, NR41-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+const DUTY_PATTERNS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0],
+];
+
+// Channels are looked at this often when building the band-limited output.
+const OUTPUT_STEP: u32 = 4;
+
+const NR_TRIGGER: u8 = 1 << 7;
+const NR_LENGTH_ENABLE: u8 = 1 << 6;
+const NR52_POWER: u8 = 1 << 7;
+
+struct LengthCounter {
+    counter: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    fn new() -> Self {
+        LengthCounter {
+            counter: 0,
+            enabled: false,
+        }
+    }
+
+    fn trigger(&mut self, max: u16) {
+        if self.counter == 0 {
+            self.counter = max;
+        }
+    }
+
+    // Returns true when the counter ran out and the channel must stop.
+    fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+}
+
+struct Envelope {
+    initial: u8,
+    increase: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Envelope {
+            initial: 0,
+            increase: false,
+            period: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    fn write(&mut self, value: u8) {
+        self.initial = value >> 4;
+        self.increase = value & 0x08 != 0;
+        self.period = value & 0x07;
+    }
+
+    fn trigger(&mut self) {
+        self.volume = self.initial;
+        self.timer = self.period;
+    }
+
+    fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+
+        self.timer = self.period;
+        if self.increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+}
+
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    shadow: u16,
+    enabled: bool,
+    // Set once a subtraction has been done since the last trigger; clearing
+    // the negate bit afterwards kills the channel.
+    negated: bool,
+}
+
+impl Sweep {
+    fn new() -> Self {
+        Sweep {
+            period: 0,
+            negate: false,
+            shift: 0,
+            timer: 0,
+            shadow: 0,
+            enabled: false,
+            negated: false,
+        }
+    }
+
+    fn reload_timer(&mut self) {
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    fn next_frequency(&mut self) -> u16 {
+        let delta = self.shadow >> self.shift;
+        if self.negate {
+            self.negated = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+}
+
+struct SquareChannel {
+    enabled: bool,
+    dac_enabled: bool,
+    duty: u8,
+    position: u8,
+    frequency: u16,
+    timer: i32,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl SquareChannel {
+    fn new() -> Self {
+        SquareChannel {
+            enabled: false,
+            dac_enabled: false,
+            duty: 0,
+            position: 0,
+            frequency: 0,
+            timer: 0,
+            length: LengthCounter::new(),
+            envelope: Envelope::new(),
+        }
+    }
+
+    fn period(&self) -> i32 {
+        (2048 - self.frequency as i32) * 4
+    }
+
+    fn write_envelope(&mut self, value: u8) {
+        self.envelope.write(value);
+        self.dac_enabled = value & 0xF8 != 0;
+        self.enabled &= self.dac_enabled;
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger(64);
+        self.timer = self.period();
+        self.envelope.trigger();
+    }
+
+    fn advance(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        self.timer -= cycles as i32;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            self.position = (self.position + 1) & 0x07;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        DUTY_PATTERNS[self.duty as usize][self.position as usize] * self.envelope.volume
+    }
+}
+
+struct WaveChannel {
+    enabled: bool,
+    dac_enabled: bool,
+    volume_code: u8,
+    position: u8,
+    frequency: u16,
+    timer: i32,
+    length: LengthCounter,
+    ram: [u8; 16],
+}
+
+impl WaveChannel {
+    fn new() -> Self {
+        WaveChannel {
+            enabled: false,
+            dac_enabled: false,
+            volume_code: 0,
+            position: 0,
+            frequency: 0,
+            timer: 0,
+            length: LengthCounter::new(),
+            ram: [0; 16],
+        }
+    }
+
+    fn period(&self) -> i32 {
+        (2048 - self.frequency as i32) * 2
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger(256);
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    fn advance(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        self.timer -= cycles as i32;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            self.position = (self.position + 1) & 0x1F;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        let byte = self.ram[self.position as usize / 2];
+        let sample = if self.position & 1 == 0 { byte >> 4 } else { byte & 0x0F };
+        // 0 = mute, 1 = 100%, 2 = 50%, 3 = 25%
+        match self.volume_code {
+            0 => 0,
+            code => sample >> (code - 1),
+        }
+    }
+}
+
+struct NoiseChannel {
+    enabled: bool,
+    dac_enabled: bool,
+    lfsr: u16,
+    clock_shift: u8,
+    width_7: bool,
+    divisor_code: u8,
+    timer: i32,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl NoiseChannel {
+    fn new() -> Self {
+        NoiseChannel {
+            enabled: false,
+            dac_enabled: false,
+            lfsr: 0x7FFF,
+            clock_shift: 0,
+            width_7: false,
+            divisor_code: 0,
+            timer: 0,
+            length: LengthCounter::new(),
+            envelope: Envelope::new(),
+        }
+    }
+
+    fn period(&self) -> i32 {
+        let divisor = if self.divisor_code == 0 { 8 } else { self.divisor_code as i32 * 16 };
+        divisor << self.clock_shift
+    }
+
+    fn write_envelope(&mut self, value: u8) {
+        self.envelope.write(value);
+        self.dac_enabled = value & 0xF8 != 0;
+        self.enabled &= self.dac_enabled;
+    }
+
+    fn write_polynomial(&mut self, value: u8) {
+        self.clock_shift = value >> 4;
+        self.width_7 = value & 0x08 != 0;
+        self.divisor_code = value & 0x07;
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger(64);
+        self.timer = self.period();
+        self.envelope.trigger();
+        self.lfsr = 0x7FFF;
+    }
+
+    fn advance(&mut self, cycles: u32) {
+        // Shifts of 14 and 15 never clock the LFSR.
+        if !self.enabled || self.clock_shift >= 14 {
+            return;
+        }
+        self.timer -= cycles as i32;
+        while self.timer <= 0 {
+            self.timer += self.period();
+
+            let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (bit << 14);
+            if self.width_7 {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (bit << 6);
+            }
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled || self.lfsr & 1 != 0 {
+            return 0;
+        }
+        self.envelope.volume
+    }
+}
+
+// The output capacitor that removes DC from the mix. Its charge leaks a little
+// every clock, faster on the CGB than on the DMG.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HighPassFilter {
+    Off,
+    Dmg,
+    Cgb,
+}
+
+impl HighPassFilter {
+    pub fn for_model(model: Model) -> Self {
+        match model {
+            Model::Dmg => HighPassFilter::Dmg,
+            Model::Cgb => HighPassFilter::Cgb,
+        }
+    }
+
+    // Charge left on the capacitor after one output sample.
+    fn charge_factor(self, sample_rate: u32) -> Option<f32> {
+        let per_clock: f64 = match self {
+            HighPassFilter::Off => return None,
+            HighPassFilter::Dmg => 0.999958,
+            HighPassFilter::Cgb => 0.998943,
+        };
+        Some(per_clock.powf(CPU_CLOCK_HZ as f64 / sample_rate as f64) as f32)
+    }
+}
+
+// One stereo signal on its way out: band-limited synthesis, then the
+// high-pass filter, then interleaved into `samples`.
+struct Output {
+    left: BlipBuf,
+    right: BlipBuf,
+    level: (f32, f32),
+    capacitor: (f32, f32),
+    scratch: (Vec<f32>, Vec<f32>),
+    samples: Vec<f32>,
+}
+
+impl Output {
+    fn new(sample_rate: u32) -> Self {
+        Output {
+            left: BlipBuf::new(CPU_CLOCK_HZ, sample_rate),
+            right: BlipBuf::new(CPU_CLOCK_HZ, sample_rate),
+            level: (0.0, 0.0),
+            capacitor: (0.0, 0.0),
+            scratch: (Vec::new(), Vec::new()),
+            samples: Vec::new(),
+        }
+    }
+
+    fn set_level(&mut self, clock: u32, (left, right): (f32, f32)) {
+        if left != self.level.0 {
+            self.left.add_delta(clock, left - self.level.0);
+        }
+        if right != self.level.1 {
+            self.right.add_delta(clock, right - self.level.1);
+        }
+        self.level = (left, right);
+    }
+
+    fn end_frame(&mut self, clocks: u32, charge_factor: Option<f32>) {
+        let (left, right) = &mut self.scratch;
+        left.clear();
+        right.clear();
+        self.left.end_frame(clocks, left);
+        self.right.end_frame(clocks, right);
+
+        for (&l, &r) in left.iter().zip(right.iter()) {
+            let (l, r) = match charge_factor {
+                Some(charge) => {
+                    let out = (l - self.capacitor.0, r - self.capacitor.1);
+                    self.capacitor = (l - out.0 * charge, r - out.1 * charge);
+                    out
+                }
+                None => (l, r),
+            };
+            self.samples.push(l);
+            self.samples.push(r);
+        }
+    }
+}
+
+pub struct Apu {
+    powered: bool,
+    registers: [u8; 0x20],
+    square1: SquareChannel,
+    sweep: Sweep,
+    square2: SquareChannel,
+    wave: WaveChannel,
+    noise: NoiseChannel,
+    frame_sequencer_step: u8,
+    sample_rate: u32,
+    high_pass: HighPassFilter,
+    charge_factor: Option<f32>,
+    // Interleaved left/right samples in -1.0..=1.0.
+    output: Output,
+    // The same, for each channel on its own, when channel capture is on.
+    channel_output: Option<Box<[Output; 4]>>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        let mut apu = Apu {
+            powered: false,
+            registers: [0; 0x20],
+            square1: SquareChannel::new(),
+            sweep: Sweep::new(),
+            square2: SquareChannel::new(),
+            wave: WaveChannel::new(),
+            noise: NoiseChannel::new(),
+            frame_sequencer_step: 0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            high_pass: HighPassFilter::Dmg,
+            charge_factor: None,
+            output: Output::new(DEFAULT_SAMPLE_RATE),
+            channel_output: None,
+        };
+        apu.set_high_pass_filter(HighPassFilter::Dmg);
+        apu
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Any rate works; samples not yet taken are dropped.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.output = Output::new(sample_rate);
+        self.set_channel_capture(self.channel_output.is_some());
+        self.set_high_pass_filter(self.high_pass);
+    }
+
+    pub fn high_pass_filter(&self) -> HighPassFilter {
+        self.high_pass
+    }
+
+    pub fn set_high_pass_filter(&mut self, filter: HighPassFilter) {
+        self.high_pass = filter;
+        self.charge_factor = filter.charge_factor(self.sample_rate);
+    }
+
+    // Hands over everything produced since the last call, as interleaved stereo.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.output.samples)
+    }
+
+    pub fn set_channel_capture(&mut self, enabled: bool) {
+        self.channel_output = enabled
+            .then(|| Box::new(std::array::from_fn(|_| Output::new(self.sample_rate))));
+    }
+
+    // Per-channel output since the last call, or empty buffers if capture is off.
+    pub fn take_channel_samples(&mut self) -> [Vec<f32>; 4] {
+        match self.channel_output.as_mut() {
+            Some(channels) => channels.each_mut().map(|output| std::mem::take(&mut output.samples)),
+            None => Default::default(),
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            NR52 => {
+                let mut value = self.registers[(NR52 - NR10) as usize] & NR52_POWER;
+                for (i, enabled) in self.channels_enabled().into_iter().enumerate() {
+                    value |= (enabled as u8) << i;
+                }
+                value | READ_MASKS[(NR52 - NR10) as usize]
+            }
+            NR10..=0xFF2F => {
+                let index = (address - NR10) as usize;
+                self.registers[index] | READ_MASKS[index]
+            }
+            WAVE_RAM..=0xFF3F => self.wave.ram[(address - WAVE_RAM) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            WAVE_RAM..=0xFF3F => {
+                self.wave.ram[(address - WAVE_RAM) as usize] = value;
+                return;
+            }
+            NR52 => {
+                self.set_power(value & NR52_POWER != 0);
+                return;
+            }
+            NR10..=NR51 if self.powered => {}
+            _ => return,
+        }
+        self.registers[(address - NR10) as usize] = value;
+
+        match address {
+            NR10 => {
+                self.sweep.period = (value >> 4) & 0x07;
+                self.sweep.shift = value & 0x07;
+                let negate = value & 0x08 != 0;
+                if self.sweep.negate && !negate && self.sweep.negated {
+                    self.square1.enabled = false;
+                }
+                self.sweep.negate = negate;
+            }
+            NR11 => {
+                self.square1.duty = value >> 6;
+                self.square1.length.counter = 64 - (value & 0x3F) as u16;
+            }
+            NR12 => self.square1.write_envelope(value),
+            NR13 => self.square1.frequency = (self.square1.frequency & 0x700) | value as u16,
+            NR14 => {
+                self.square1.frequency = (self.square1.frequency & 0xFF) | ((value as u16 & 0x07) << 8);
+                self.square1.length.enabled = value & NR_LENGTH_ENABLE != 0;
+                if value & NR_TRIGGER != 0 {
+                    self.square1.trigger();
+                    self.trigger_sweep();
+                }
+            }
+            NR21 => {
+                self.square2.duty = value >> 6;
+                self.square2.length.counter = 64 - (value & 0x3F) as u16;
+            }
+            NR22 => self.square2.write_envelope(value),
+            NR23 => self.square2.frequency = (self.square2.frequency & 0x700) | value as u16,
+            NR24 => {
+                self.square2.frequency = (self.square2.frequency & 0xFF) | ((value as u16 & 0x07) << 8);
+                self.square2.length.enabled = value & NR_LENGTH_ENABLE != 0;
+                if value & NR_TRIGGER != 0 {
+                    self.square2.trigger();
+                }
+            }
+            NR30 => {
+                self.wave.dac_enabled = value & 0x80 != 0;
+                self.wave.enabled &= self.wave.dac_enabled;
+            }
+            NR31 => self.wave.length.counter = 256 - value as u16,
+            NR32 => self.wave.volume_code = (value >> 5) & 0x03,
+            NR33 => self.wave.frequency = (self.wave.frequency & 0x700) | value as u16,
+            NR34 => {
+                self.wave.frequency = (self.wave.frequency & 0xFF) | ((value as u16 & 0x07) << 8);
+                self.wave.length.enabled = value & NR_LENGTH_ENABLE != 0;
+                if value & NR_TRIGGER != 0 {
+                    self.wave.trigger();
+                }
+            }
+            NR41 => self.noise.length.counter = 64 - (value & 0x3F) as u16,
+            NR42 => self.noise.write_envelope(value),
+            NR43 => self.noise.write_polynomial(value),
+            NR44 => {
+                self.noise.length.enabled = value & NR_LENGTH_ENABLE != 0;
+                if value & NR_TRIGGER != 0 {
+                    self.noise.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Called on every falling edge of DIV bit 4 (512 Hz).
+    pub fn step_frame_sequencer(&mut self) {
+        if !self.powered {
+            return;
+        }
+
+        let step = self.frame_sequencer_step;
+        if step.is_multiple_of(2) {
+            self.clock_lengths();
+        }
+        if step == 2 || step == 6 {
+            self.clock_sweep();
+        }
+        if step == 7 {
+            self.square1.envelope.clock();
+            self.square2.envelope.clock();
+            self.noise.envelope.clock();
+        }
+        self.frame_sequencer_step = (step + 1) & 0x07;
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        let mut clock = 0;
+
+        while clock < cycles {
+            let step = (cycles - clock).min(OUTPUT_STEP);
+            if self.powered {
+                self.square1.advance(step);
+                self.square2.advance(step);
+                self.wave.advance(step);
+                self.noise.advance(step);
+            }
+
+            clock += step;
+            self.update_output(clock);
+        }
+
+        self.output.end_frame(cycles, self.charge_factor);
+        if let Some(channels) = self.channel_output.as_mut() {
+            for output in channels.iter_mut() {
+                output.end_frame(cycles, self.charge_factor);
+            }
+        }
+    }
+
+    fn channels_enabled(&self) -> [bool; 4] {
+        [
+            self.square1.enabled,
+            self.square2.enabled,
+            self.wave.enabled,
+            self.noise.enabled,
+        ]
+    }
+
+    // Each channel's DAC maps its 0..=15 output onto -1.0..=1.0, or silence when off.
+    fn channel_outputs(&self) -> [f32; 4] {
+        let dac = |enabled: bool, digital: u8| {
+            if enabled {
+                digital as f32 / 7.5 - 1.0
+            } else {
+                0.0
+            }
+        };
+        [
+            dac(self.square1.dac_enabled, self.square1.output()),
+            dac(self.square2.dac_enabled, self.square2.output()),
+            dac(self.wave.dac_enabled, self.wave.output()),
+            dac(self.noise.dac_enabled, self.noise.output()),
+        ]
+    }
+
+    // Each channel's contribution to the left and right outputs after NR51
+    // panning and NR50 master volume. The mix is their sum.
+    fn panned_outputs(&self) -> [(f32, f32); 4] {
+        if !self.powered {
+            return [(0.0, 0.0); 4];
+        }
+
+        let nr50 = self.registers[(NR50 - NR10) as usize];
+        let nr51 = self.registers[(NR51 - NR10) as usize];
+        let left_volume = (((nr50 >> 4) & 0x07) as f32 + 1.0) / 8.0 / 4.0;
+        let right_volume = ((nr50 & 0x07) as f32 + 1.0) / 8.0 / 4.0;
+
+        let outputs = self.channel_outputs();
+        std::array::from_fn(|i| {
+            let left = if nr51 & (0x10 << i) != 0 { outputs[i] } else { 0.0 };
+            let right = if nr51 & (0x01 << i) != 0 { outputs[i] } else { 0.0 };
+            (left * left_volume, right * right_volume)
+        })
+    }
+
+    fn update_output(&mut self, clock: u32) {
+        let panned = self.panned_outputs();
+
+        let left = panned.iter().map(|&(left, _)| left).sum();
+        let right = panned.iter().map(|&(_, right)| right).sum();
+        self.output.set_level(clock, (left, right));
+
+        if let Some(channels) = self.channel_output.as_mut() {
+            for (output, level) in channels.iter_mut().zip(panned) {
+                output.set_level(clock, level);
+            }
+        }
+    }
+
+    fn set_power(&mut self, on: bool) {
+        if on && !self.powered {
+            self.frame_sequencer_step = 0;
+        }
+        if !on && self.powered {
+            // Powering off clears every register except wave RAM.
+            let wave_ram = self.wave.ram;
+            self.registers = [0; 0x20];
+            self.square1 = SquareChannel::new();
+            self.sweep = Sweep::new();
+            self.square2 = SquareChannel::new();
+            self.wave = WaveChannel::new();
+            self.wave.ram = wave_ram;
+            self.noise = NoiseChannel::new();
+        }
+
+        self.powered = on;
+        self.registers[(NR52 - NR10) as usize] = if on { NR52_POWER } else { 0 };
+    }
+
+    fn clock_lengths(&mut self) {
+        if self.square1.length.clock() {
+            self.square1.enabled = false;
+        }
+        if self.square2.length.clock() {
+            self.square2.enabled = false;
+        }
+        if self.wave.length.clock() {
+            self.wave.enabled = false;
+        }
+        if self.noise.length.clock() {
+            self.noise.enabled = false;
+        }
+    }
+
+    fn trigger_sweep(&mut self) {
+        self.sweep.shadow = self.square1.frequency;
+        self.sweep.negated = false;
+        self.sweep.reload_timer();
+        self.sweep.enabled = self.sweep.period != 0 || self.sweep.shift != 0;
+
+        if self.sweep.shift != 0 && self.sweep.next_frequency() > 2047 {
+            self.square1.enabled = false;
+        }
+    }
+
+    fn clock_sweep(&mut self) {
+        self.sweep.timer = self.sweep.timer.saturating_sub(1);
+        if self.sweep.timer > 0 {
+            return;
+        }
+
+        self.sweep.reload_timer();
+        if !self.sweep.enabled || self.sweep.period == 0 {
+            return;
+        }
+
+        let frequency = self.sweep.next_frequency();
+        if frequency > 2047 {
+            self.square1.enabled = false;
+        } else if self.sweep.shift != 0 {
+            self.sweep.shadow = frequency;
+            self.square1.frequency = frequency;
+            // The new frequency is checked for overflow straight away.
+            if self.sweep.next_frequency() > 2047 {
+                self.square1.enabled = false;
+            }
+        }
+    }
+}
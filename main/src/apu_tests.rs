@@ -0,0 +1,206 @@
+#[cfg(test)]
+mod tests {
+    use crate::apu::*;
+    use crate::blip::BlipBuf;
+    use crate::memory::GbMemory;
+
+    fn powered_apu() -> Apu {
+        let mut apu = Apu::new();
+        apu.write(NR52, 0x80);
+        apu.write(NR50, 0x77);
+        apu.write(NR51, 0xFF);
+        apu
+    }
+
+    // Channel 2 alone with its DAC on and volume 0, at full master volume.
+    fn apu_silence() -> f32 {
+        -1.0 / 4.0
+    }
+
+    #[test]
+    fn test_power_off_ignores_writes() {
+        let mut apu = Apu::new();
+
+        apu.write(NR50, 0x77);
+
+        assert_eq!(apu.read(NR52), 0x70);
+        assert_eq!(apu.read(NR50), 0x00);
+
+        apu.write(NR52, 0x80);
+        apu.write(NR50, 0x77);
+
+        assert_eq!(apu.read(NR52), 0xF0);
+        assert_eq!(apu.read(NR50), 0x77);
+    }
+
+    #[test]
+    fn test_power_off_clears_registers_but_not_wave_ram() {
+        let mut apu = powered_apu();
+        apu.write(WAVE_RAM, 0x12);
+        apu.write(NR22, 0xF0);
+
+        apu.write(NR52, 0x00);
+        apu.write(NR52, 0x80);
+
+        assert_eq!(apu.read(NR22), 0x00);
+        assert_eq!(apu.read(WAVE_RAM), 0x12);
+    }
+
+    #[test]
+    fn test_trigger_sets_status_bit() {
+        let mut apu = powered_apu();
+
+        apu.write(NR22, 0xF0);
+        apu.write(NR24, 0x80);
+
+        assert_eq!(apu.read(NR52) & 0x0F, 0x02);
+    }
+
+    #[test]
+    fn test_trigger_without_dac_stays_off() {
+        let mut apu = powered_apu();
+
+        apu.write(NR42, 0x00);
+        apu.write(NR44, 0x80);
+
+        assert_eq!(apu.read(NR52) & 0x0F, 0x00);
+    }
+
+    #[test]
+    fn test_length_counter_stops_channel() {
+        let mut apu = powered_apu();
+        apu.write(NR30, 0x80);
+        // 256 - 0xFE = 2 length clocks
+        apu.write(NR31, 0xFE);
+        apu.write(NR34, 0xC0);
+        assert_eq!(apu.read(NR52) & 0x04, 0x04);
+
+        // Steps 0 and 2 clock the length counters, step 1 does not.
+        apu.step_frame_sequencer();
+        apu.step_frame_sequencer();
+        assert_eq!(apu.read(NR52) & 0x04, 0x04);
+
+        apu.step_frame_sequencer();
+        assert_eq!(apu.read(NR52) & 0x04, 0x00);
+    }
+
+    #[test]
+    fn test_sweep_overflow_on_trigger() {
+        let mut apu = powered_apu();
+        apu.write(NR10, 0x01);
+        apu.write(NR12, 0xF0);
+        apu.write(NR13, 0xFF);
+        apu.write(NR14, 0x87);
+
+        assert_eq!(apu.read(NR52) & 0x01, 0x00);
+    }
+
+    #[test]
+    fn test_envelope_fades_out() {
+        let mut apu = powered_apu();
+        apu.set_high_pass_filter(HighPassFilter::Off);
+        apu.write(NR51, 0x22);
+        // Volume 1, decreasing every envelope clock, 50% duty
+        apu.write(NR21, 0x80);
+        apu.write(NR22, 0x11);
+        apu.write(NR24, 0x80);
+
+        apu.tick(CPU_CLOCK_HZ / 64);
+        assert!(apu.take_samples().iter().any(|&s| s != apu_silence()));
+
+        for _ in 0..8 {
+            apu.step_frame_sequencer();
+        }
+        apu.tick(CPU_CLOCK_HZ / 64);
+        // Skip the tail of the last edge still ringing through the filter kernel.
+        let samples = apu.take_samples();
+        assert!(samples[64..].iter().all(|&s| (s - apu_silence()).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_high_pass_removes_dc() {
+        let mut apu = powered_apu();
+        // DAC on at volume 0 holds the output at a constant negative level.
+        apu.write(NR22, 0x08);
+        apu.write(NR24, 0x80);
+
+        apu.tick(CPU_CLOCK_HZ);
+        let samples = apu.take_samples();
+
+        assert!(samples[..200].iter().any(|&s| s < -0.2));
+        assert!(samples[samples.len() - 2..].iter().all(|&s| s.abs() < 0.01));
+    }
+
+    #[test]
+    fn test_cgb_filter_charges_faster() {
+        let mut dmg = powered_apu();
+        let mut cgb = powered_apu();
+        cgb.set_high_pass_filter(HighPassFilter::for_model(crate::gameboy::Model::Cgb));
+        for apu in [&mut dmg, &mut cgb] {
+            apu.write(NR22, 0x08);
+            apu.write(NR24, 0x80);
+            apu.tick(CPU_CLOCK_HZ / 100);
+        }
+
+        let dmg_level = dmg.take_samples().last().unwrap().abs();
+        let cgb_level = cgb.take_samples().last().unwrap().abs();
+
+        assert!(cgb_level < dmg_level);
+    }
+
+    #[test]
+    fn test_blip_step_is_band_limited() {
+        let mut blip = BlipBuf::new(CPU_CLOCK_HZ, 44_100);
+        let mut out = Vec::new();
+
+        blip.add_delta(1000, 1.0);
+        blip.end_frame(10_000, &mut out);
+
+        // A sinc step rings on both sides of the edge, then settles on the new level.
+        assert!(out.iter().any(|&s| s < 0.0));
+        assert!(out.iter().any(|&s| s > 1.0));
+        assert!((out.last().unwrap() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_panning() {
+        let mut apu = powered_apu();
+        apu.write(NR51, 0x20);
+        apu.write(NR22, 0xF0);
+        apu.write(NR24, 0x80);
+
+        apu.tick(CPU_CLOCK_HZ / 64);
+        let samples = apu.take_samples();
+
+        assert!(samples.chunks(2).any(|frame| frame[0] != 0.0));
+        assert!(samples.chunks(2).all(|frame| frame[1] == 0.0));
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let mut apu = powered_apu();
+        apu.set_sample_rate(48_000);
+
+        apu.tick(CPU_CLOCK_HZ);
+
+        assert_eq!(apu.take_samples().len(), 2 * 48_000);
+    }
+
+    #[test]
+    fn test_frame_sequencer_clocked_from_div() {
+        let mut memory = GbMemory::new();
+        memory.write_u8(NR52, 0x80);
+        memory.write_u8(NR42, 0xF0);
+        // A single length clock left
+        memory.write_u8(NR41, 0x3F);
+        memory.write_u8(NR44, 0xC0);
+        assert_eq!(memory.read_u8(NR52) & 0x08, 0x08);
+
+        // DIV bit 4 falls every 8192 cycles.
+        memory.tick(8188);
+        assert_eq!(memory.read_u8(NR52) & 0x08, 0x08);
+
+        memory.tick(4);
+        assert_eq!(memory.read_u8(NR52) & 0x08, 0x00);
+    }
+}
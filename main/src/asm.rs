@@ -0,0 +1,510 @@
+use crate::gameboy::Gameboy;
+use crate::opcodes::{self, OpcodeInfo};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+// A small assembler for SM83 code in RGBDS syntax, built from the names in
+// dmgops.json. It takes one instruction or directive per line, or several
+// separated by `::`, with `label:` definitions, `.local` labels and `;`
+// comments. The directives are db, dw, ds and SECTION, which only moves the
+// address to the one in its brackets. Expressions are sums of numbers ($FF,
+// 0xFF, %1010, 255), labels and `@` for the current address.
+
+// One way to encode an instruction, with the operands of its name in
+// dmgops.json such as ["A", "(FF00+u8)"].
+struct Encoding {
+    operands: Vec<&'static str>,
+    prefixed: bool,
+    opcode: u8,
+    length: u16,
+}
+
+static ENCODINGS: Lazy<HashMap<&'static str, Vec<Encoding>>> = Lazy::new(|| {
+    let mut encodings = HashMap::new();
+    let mut add = |info: &'static OpcodeInfo, prefixed: bool, opcode: u8| {
+        if info.group == "unused" || info.name == "PREFIX CB" {
+            return;
+        }
+        let (mnemonic, operands) = info.name.split_once(' ').unwrap_or((&info.name, ""));
+        let operands = operands.split(',').filter(|operand| !operand.is_empty()).collect();
+        encodings.entry(mnemonic).or_insert_with(Vec::new).push(Encoding {
+            operands,
+            prefixed,
+            opcode,
+            length: info.length,
+        });
+    };
+    for opcode in 0..=0xFF {
+        add(opcodes::unprefixed(opcode), false, opcode);
+        add(opcodes::cb_prefixed(opcode), true, opcode);
+    }
+    encodings
+});
+
+// Instructions that take A as an implied first operand.
+const ALU: [&str; 8] = ["ADD", "ADC", "SUB", "SBC", "AND", "XOR", "OR", "CP"];
+
+const FIXED: [&str; 15] = ["A", "B", "C", "D", "E", "H", "L", "AF", "BC", "DE", "HL", "SP", "NZ", "Z", "NC"];
+
+#[derive(Debug, Clone)]
+struct Expr {
+    text: String,
+    // The global label that `.local` names belong to.
+    scope: String,
+    line: usize,
+}
+
+#[derive(Debug)]
+enum Operand {
+    // Registers, conditions and memory through registers, spelled as in
+    // dmgops.json, e.g. "HL", "NC" or "(HL+)".
+    Fixed(String),
+    Immediate(Expr),
+    Memory(Expr),
+    // [$FF00+n], or [n] after LDH.
+    HighMemory(Expr),
+    // SP+e
+    SpOffset(Expr),
+}
+
+enum Item {
+    Instruction(&'static Encoding, Vec<Operand>),
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+}
+
+struct Assembler {
+    items: Vec<(u16, Item)>,
+    labels: HashMap<String, u16>,
+    scope: String,
+    address: u16,
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    assemble_at(source, 0)
+}
+
+// Assembles code that will sit at `origin`, which is where labels count from.
+pub fn assemble_at(source: &str, origin: u16) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler {
+        items: Vec::new(),
+        labels: HashMap::new(),
+        scope: String::new(),
+        address: origin,
+    };
+    for (i, line) in source.lines().enumerate() {
+        assembler.parse_line(line, i + 1).map_err(|err| at(i + 1, err))?;
+    }
+    assembler.encode()
+}
+
+// Assembles `source` into memory at `address`, over cartridge ROM too, and
+// returns how many bytes it wrote.
+pub fn patch(gameboy: &mut Gameboy, address: u16, source: &str) -> Result<usize, String> {
+    let bytes = assemble_at(source, address)?;
+    for (i, byte) in bytes.iter().enumerate() {
+        gameboy.memory.patch_u8(address.wrapping_add(i as u16), *byte);
+    }
+    Ok(bytes.len())
+}
+
+impl Assembler {
+    fn parse_line(&mut self, line: &str, number: usize) -> Result<(), String> {
+        let mut rest = strip_comment(line).trim();
+
+        while let Some((name, after)) = split_label(rest) {
+            let name = self.qualify(name);
+            if self.labels.insert(name.clone(), self.address).is_some() {
+                return Err(format!("label '{}' is already defined", name));
+            }
+            if !name.contains('.') {
+                self.scope = name;
+            }
+            rest = after.trim();
+        }
+
+        for statement in rest.split("::") {
+            let statement = statement.trim();
+            if !statement.is_empty() {
+                self.parse_statement(statement, number)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_statement(&mut self, statement: &str, line: usize) -> Result<(), String> {
+        let (mnemonic, args) = statement
+            .split_once(char::is_whitespace)
+            .map_or((statement, ""), |(mnemonic, args)| (mnemonic, args.trim()));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let expr = |text: &str| Expr {
+            text: text.trim().to_string(),
+            scope: self.scope.clone(),
+            line,
+        };
+
+        let (item, length) = match mnemonic.as_str() {
+            "SECTION" => {
+                // The first brackets after the name, not those of BANK[n].
+                let name_end = args.rfind('"').map_or(0, |quote| quote + 1);
+                let start = args[name_end..].find('[').ok_or("SECTION needs an address in brackets")?;
+                let bracket = &args[name_end + start + 1..];
+                let end = bracket.find(']').ok_or("missing ']'")?;
+                self.address = expect_u16(constant(&expr(&bracket[..end]))?)?;
+                return Ok(());
+            }
+            "DB" => {
+                let mut values = Vec::new();
+                for arg in split_args(args) {
+                    match arg.strip_prefix('"').and_then(|arg| arg.strip_suffix('"')) {
+                        Some(string) => {
+                            values.extend(string.bytes().map(|byte| expr(&byte.to_string())));
+                        }
+                        None => values.push(expr(&arg)),
+                    }
+                }
+                let length = values.len();
+                (Item::Bytes(values), length)
+            }
+            "DW" => {
+                let values: Vec<Expr> = split_args(args).iter().map(|arg| expr(arg)).collect();
+                let length = values.len() * 2;
+                (Item::Words(values), length)
+            }
+            "DS" => {
+                let args = split_args(args);
+                let (count, fill) = match args.as_slice() {
+                    [count] => (count, "0".to_string()),
+                    [count, fill] => (count, fill.clone()),
+                    _ => return Err("expected ds COUNT[, FILL]".to_string()),
+                };
+                let count = constant(&expr(count))?;
+                let count = usize::try_from(count).map_err(|_| format!("invalid count {}", count))?;
+                (Item::Bytes(vec![expr(&fill); count]), count)
+            }
+            _ => {
+                let ldh = mnemonic == "LDH";
+                let mnemonic = if ldh { "LD" } else { mnemonic.as_str() };
+                let mut operands = split_args(args)
+                    .iter()
+                    .map(|arg| parse_operand(arg, ldh, &expr))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if ALU.contains(&mnemonic) && operands.len() == 1 {
+                    operands.insert(0, Operand::Fixed("A".to_string()));
+                }
+                if mnemonic == "JP" && matches!(operands.as_slice(), [Operand::Fixed(hl)] if hl == "(HL)") {
+                    operands[0] = Operand::Fixed("HL".to_string());
+                }
+
+                let encoding = find_encoding(mnemonic, &operands)?
+                    .ok_or_else(|| format!("no such instruction '{}'", statement))?;
+                (Item::Instruction(encoding, operands), encoding.length as usize)
+            }
+        };
+
+        self.items.push((self.address, item));
+        self.address = self.address.wrapping_add(length as u16);
+        Ok(())
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        match name.starts_with('.') {
+            true => format!("{}{}", self.scope, name),
+            false => name.to_string(),
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        for (address, item) in &self.items {
+            match item {
+                Item::Bytes(values) => {
+                    for value in values {
+                        bytes.push(expect_u8(self.evaluate(value, *address)?).map_err(|err| at(value.line, err))?);
+                    }
+                }
+                Item::Words(values) => {
+                    for value in values {
+                        let word = expect_u16(self.evaluate(value, *address)?).map_err(|err| at(value.line, err))?;
+                        bytes.extend_from_slice(&word.to_le_bytes());
+                    }
+                }
+                Item::Instruction(encoding, operands) => {
+                    self.encode_instruction(&mut bytes, *address, encoding, operands)?;
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn encode_instruction(
+        &self,
+        bytes: &mut Vec<u8>,
+        address: u16,
+        encoding: &Encoding,
+        operands: &[Operand],
+    ) -> Result<(), String> {
+        if encoding.prefixed {
+            bytes.push(0xCB);
+        }
+        bytes.push(encoding.opcode);
+
+        for (template, operand) in encoding.operands.iter().zip(operands) {
+            let (Operand::Immediate(value)
+            | Operand::Memory(value)
+            | Operand::HighMemory(value)
+            | Operand::SpOffset(value)) = operand
+            else {
+                continue;
+            };
+            let number = self.evaluate(value, address)?;
+            let encoded = match *template {
+                "u8" => expect_u8(number).map(|byte| vec![byte]),
+                "u16" | "(u16)" => expect_u16(number).map(|word| word.to_le_bytes().to_vec()),
+                "(FF00+u8)" => match number {
+                    0xFF00..=0xFFFF => Ok(vec![(number - 0xFF00) as u8]),
+                    0..=0xFF => Ok(vec![number as u8]),
+                    _ => Err(format!("{:X} is outside FF00-FFFF", number)),
+                },
+                // JR counts from the end of the instruction.
+                "i8" if encoding.opcode & 0xE7 == 0x20 || encoding.opcode == 0x18 => {
+                    expect_i8(number - (address as i64 + encoding.length as i64))
+                        .map_err(|_| format!("jump to {:04X} is out of range", number))
+                }
+                "i8" | "SP+i8" => expect_i8(number),
+                // Bit numbers and RST vectors are part of the opcode.
+                _ => Ok(Vec::new()),
+            };
+            bytes.extend(encoded.map_err(|err| at(value.line, err))?);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&self, expr: &Expr, address: u16) -> Result<i64, String> {
+        evaluate(expr, Some((&self.labels, address)))
+    }
+}
+
+fn find_encoding(mnemonic: &str, operands: &[Operand]) -> Result<Option<&'static Encoding>, String> {
+    let Some(encodings) = ENCODINGS.get(mnemonic) else {
+        return Ok(None);
+    };
+    for encoding in encodings {
+        if encoding.operands.len() != operands.len() {
+            continue;
+        }
+        let mut matched = true;
+        for (template, operand) in encoding.operands.iter().zip(operands) {
+            matched &= match (*template, operand) {
+                ("u8" | "u16" | "i8", Operand::Immediate(_)) => true,
+                ("(u16)", Operand::Memory(_)) => true,
+                ("(FF00+u8)", Operand::HighMemory(_)) => true,
+                ("SP+i8", Operand::SpOffset(_)) => true,
+                (template, Operand::Fixed(fixed)) => template == fixed,
+                // The bit number of BIT, RES and SET, or an RST vector like "38h".
+                (template, Operand::Immediate(value)) => {
+                    let literal = match template.strip_suffix('h') {
+                        Some(vector) => i64::from_str_radix(vector, 16).ok(),
+                        None => template.parse().ok(),
+                    };
+                    literal.is_some() && literal == Some(constant(value)?)
+                }
+                _ => false,
+            };
+        }
+        if matched {
+            return Ok(Some(encoding));
+        }
+    }
+    Ok(None)
+}
+
+fn parse_operand(text: &str, ldh: bool, expr: &dyn Fn(&str) -> Expr) -> Result<Operand, String> {
+    let squashed: String = text.split_whitespace().collect::<String>().to_ascii_uppercase();
+
+    let inner = text
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+        .or_else(|| text.strip_prefix('(').and_then(|text| text.strip_suffix(')')));
+    if let Some(inner) = inner {
+        let squashed = &squashed[1..squashed.len() - 1];
+        let fixed = match squashed {
+            "HL" | "BC" | "DE" => format!("({})", squashed),
+            "HL+" | "HLI" => "(HL+)".to_string(),
+            "HL-" | "HLD" => "(HL-)".to_string(),
+            "C" | "$FF00+C" | "0XFF00+C" => "(FF00+C)".to_string(),
+            _ => {
+                for prefix in ["$FF00+", "0XFF00+"] {
+                    if squashed.starts_with(prefix) {
+                        let plus = inner.find('+').unwrap();
+                        return Ok(Operand::HighMemory(expr(&inner[plus + 1..])));
+                    }
+                }
+                return Ok(match ldh {
+                    true => Operand::HighMemory(expr(inner)),
+                    false => Operand::Memory(expr(inner)),
+                });
+            }
+        };
+        return Ok(Operand::Fixed(fixed));
+    }
+
+    if FIXED.contains(&squashed.as_str()) {
+        return Ok(Operand::Fixed(squashed));
+    }
+    if squashed.starts_with("SP+") || squashed.starts_with("SP-") {
+        let sign = text.find(['+', '-']).unwrap();
+        return Ok(Operand::SpOffset(expr(&text[sign..])));
+    }
+    if text.is_empty() {
+        return Err("missing operand".to_string());
+    }
+    Ok(Operand::Immediate(expr(text)))
+}
+
+// A value that doesn't depend on any label.
+fn constant(expr: &Expr) -> Result<i64, String> {
+    evaluate(expr, None)
+}
+
+// Sums and differences of terms. Labels need `labels`, which also gives the
+// address of the instruction for `@`.
+fn evaluate(expr: &Expr, labels: Option<(&HashMap<String, u16>, u16)>) -> Result<i64, String> {
+    let invalid = || at(expr.line, format!("invalid expression '{}'", expr.text));
+    let mut total = 0;
+    let mut sign = 1;
+    let mut current = String::new();
+    // Set by a space after a term, which only an operator may follow.
+    let mut ended = false;
+
+    for c in expr.text.chars() {
+        match c {
+            '+' | '-' if current.is_empty() => {
+                if c == '-' {
+                    sign = -sign;
+                }
+            }
+            '+' | '-' => {
+                total += sign * term(expr, &current, labels)?;
+                current.clear();
+                ended = false;
+                sign = if c == '-' { -1 } else { 1 };
+            }
+            c if c.is_whitespace() => ended = !current.is_empty(),
+            _ if ended => return Err(invalid()),
+            _ => current.push(c),
+        }
+    }
+    if current.is_empty() {
+        return Err(invalid());
+    }
+    Ok(total + sign * term(expr, &current, labels)?)
+}
+
+fn term(expr: &Expr, term: &str, labels: Option<(&HashMap<String, u16>, u16)>) -> Result<i64, String> {
+    let number = if let Some(hex) = term.strip_prefix('$') {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = term.strip_prefix("0x").or_else(|| term.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = term.strip_prefix('%') {
+        i64::from_str_radix(binary, 2).ok()
+    } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+        term.parse().ok()
+    } else {
+        let Some((labels, address)) = labels else {
+            return Err(at(expr.line, format!("'{}' must be a constant", term)));
+        };
+        if term == "@" {
+            return Ok(address as i64);
+        }
+        let name = match term.starts_with('.') {
+            true => format!("{}{}", expr.scope, term),
+            false => term.to_string(),
+        };
+        return labels
+            .get(&name)
+            .map(|address| *address as i64)
+            .ok_or_else(|| at(expr.line, format!("unknown label '{}'", term)));
+    };
+    number.ok_or_else(|| at(expr.line, format!("invalid number '{}'", term)))
+}
+
+// Puts the line number in front of an error that doesn't have one yet.
+fn at(line: usize, err: String) -> String {
+    match err.starts_with("line ") {
+        true => err,
+        false => format!("line {}: {}", line, err),
+    }
+}
+
+fn expect_u8(value: i64) -> Result<u8, String> {
+    match value {
+        -0x80..=0xFF => Ok(value as u8),
+        _ => Err(format!("{} doesn't fit in a byte", value)),
+    }
+}
+
+fn expect_i8(value: i64) -> Result<Vec<u8>, String> {
+    match value {
+        -0x80..=0x7F => Ok(vec![value as u8]),
+        _ => Err(format!("{} is outside -128 to 127", value)),
+    }
+}
+
+fn expect_u16(value: i64) -> Result<u16, String> {
+    match value {
+        -0x8000..=0xFFFF => Ok(value as u16),
+        _ => Err(format!("{} doesn't fit in a word", value)),
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// A `name:` at the start of `text`, and what follows it, or a `name::`
+// with nothing after it. `nop::nop` is two instructions, not a label.
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        .unwrap_or(text.len());
+    let name = &text[..end];
+    let after = text[end..].strip_prefix(':')?;
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    match after.strip_prefix(':') {
+        Some(after) if after.trim().is_empty() => Some((name, after)),
+        Some(_) => None,
+        None => Some((name, after)),
+    }
+}
+
+// Splits on commas outside quotes.
+fn split_args(args: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in args.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => parts.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !parts.is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
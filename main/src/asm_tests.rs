@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::*;
+    use crate::cartridge::Cartridge;
+    use crate::disasm::{ROM_BANK_SIZE, decode};
+    use crate::gameboy::Gameboy;
+    use crate::opcodes;
+    use crate::rgbds;
+
+    #[test]
+    fn test_assembles_instructions() {
+        assert_eq!(assemble("ld a,$12\nxor a").unwrap(), [0x3E, 0x12, 0xAF]);
+        assert_eq!(
+            assemble("LD HL, $9FFF\nld [hl-], a\nbit 7, h\nldh [$FF40], a\nldh a, [c]").unwrap(),
+            [0x21, 0xFF, 0x9F, 0x32, 0xCB, 0x7C, 0xE0, 0x40, 0xF2]
+        );
+        assert_eq!(assemble("ld (hli),a\nld a,[$ff00+$44]\njp [hl]").unwrap(), [0x22, 0xF0, 0x44, 0xE9]);
+        assert_eq!(assemble("add sp, -2\nld hl, sp+5\nrst $38").unwrap(), [0xE8, 0xFE, 0xF8, 0x05, 0xFF]);
+        assert_eq!(assemble("cp 10\nsub a, %101\nand b").unwrap(), [0xFE, 0x0A, 0xD6, 0x05, 0xA0]);
+    }
+
+    // Every opcode, written out the way the RGBDS disassembler does, comes
+    // back as the same bytes.
+    #[test]
+    fn test_every_opcode_round_trips() {
+        for opcode in 0..=0xFF {
+            for prefixed in [false, true] {
+                let bytes = match prefixed {
+                    true => vec![0xCB, opcode, 0x00],
+                    false => vec![opcode, 0x34, 0x12],
+                };
+                if !prefixed && (opcodes::unprefixed(opcode).group == "unused" || opcode == 0xCB) {
+                    continue;
+                }
+                let instruction = decode(&bytes, 0x0150);
+                let text = rgbds::instruction_text(&instruction, None);
+
+                let assembled = assemble_at(&text, 0x0150).unwrap_or_else(|err| panic!("{}: {}", text, err));
+                assert_eq!(assembled, instruction.bytes, "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_labels() {
+        let source = "
+            start:
+                ld b, 3
+            .loop:
+                dec b
+                jr nz, .loop
+                call done
+                jp start
+            done: ret
+        ";
+        assert_eq!(
+            assemble_at(source, 0xC000).unwrap(),
+            [0x06, 0x03, 0x05, 0x20, 0xFD, 0xCD, 0x0B, 0xC0, 0xC3, 0x00, 0xC0, 0xC9]
+        );
+        assert_eq!(assemble("jr @ + 2 :: nop").unwrap(), [0x18, 0x00, 0x00]);
+        assert_eq!(assemble("nop::nop").unwrap(), [0x00, 0x00]);
+        assert_eq!(assemble("nop::\njp nop").unwrap(), [0xC3, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_data_directives() {
+        assert_eq!(
+            assemble("db 1, $FF, \"Hi\" ; comment\ndw $1234, end\nds 2, $AA\nend:").unwrap(),
+            [0x01, 0xFF, b'H', b'i', 0x34, 0x12, 0x0A, 0x00, 0xAA, 0xAA]
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        assert_eq!(assemble("nop\nld a, [bc], 1").unwrap_err(), "line 2: no such instruction 'ld a, [bc], 1'");
+        assert_eq!(assemble("nop\n\njp nowhere").unwrap_err(), "line 3: unknown label 'nowhere'");
+        assert_eq!(assemble("ld a, 256").unwrap_err(), "line 1: 256 doesn't fit in a byte");
+        assert_eq!(assemble("jr far\nds 200\nfar:").unwrap_err(), "line 1: jump to 00CA is out of range");
+        assert_eq!(assemble("a: nop\na: nop").unwrap_err(), "line 2: label 'a' is already defined");
+    }
+
+    #[test]
+    fn test_rgbds_source_reassembles() {
+        let mut rom = vec![0xFF; ROM_BANK_SIZE * 2];
+        rom[0x40] = 0xD9;
+        rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        let program = assemble_at(
+            "
+                ld hl, $9FFF
+            .clear:
+                ld [hl-], a
+                bit 7, h
+                jr nz, .clear
+                ld a, 1
+                ld [$2000], a
+                call $4010
+                ldh [$FF40], a
+                ld [$FF41], a
+                stop
+            spin:
+                jr spin
+            ",
+            0x0150,
+        )
+        .unwrap();
+        rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+        rom[ROM_BANK_SIZE + 0x10..ROM_BANK_SIZE + 0x12].copy_from_slice(&[0xAF, 0xC9]);
+
+        assert_eq!(assemble(&rgbds::source(&rom)).unwrap(), rom);
+    }
+
+    #[test]
+    fn test_patch_writes_over_rom() {
+        let mut gameboy = Gameboy::new();
+        gameboy.insert_cartridge(Cartridge::from_bytes(vec![0; ROM_BANK_SIZE * 2]).unwrap(), None);
+        gameboy.skip_boot_rom();
+
+        assert_eq!(patch(&mut gameboy, 0x0150, "ld a, $12\nhalt").unwrap(), 3);
+        assert_eq!(patch(&mut gameboy, 0xC000, "xor a").unwrap(), 1);
+
+        assert_eq!(gameboy.memory.read_u8(0x0150), 0x3E);
+        assert_eq!(gameboy.memory.read_u8(0x0151), 0x12);
+        assert_eq!(gameboy.memory.read_u8(0x0152), 0x76);
+        assert_eq!(gameboy.memory.read_u8(0xC000), 0xAF);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::gameboy::Gameboy;
+use crate::serial::SerialDevice;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// blargg's test ROMs print their results over the link port, and most also
+// keep them in cartridge RAM: $A001-$A003 hold DE B0 61 once the text at
+// $A004 is valid, and $A000 is 80 while running, then the result code, 0
+// for a pass.
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const STATUS: u16 = 0xA000;
+const TEXT: u16 = 0xA004;
+const RUNNING: u8 = 0x80;
+
+// How often the output is looked at, in T-cycles.
+const CHECK_INTERVAL: u64 = 1 << 16;
+
+// Keeps everything sent over the link port.
+pub struct SerialLog {
+    bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SerialDevice for SerialLog {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.bytes.borrow_mut().push(byte);
+        0xFF
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct BlarggResult {
+    // None when the ROM hadn't finished within the budget.
+    pub passed: Option<bool>,
+    pub output: String,
+    // The numbers of the sub-tests that failed, or the failure line of a ROM
+    // with a single test.
+    pub failures: Vec<String>,
+    pub cycles: u64,
+}
+
+// Runs a test ROM that's already loaded until it reports a result, stops or
+// `budget` T-cycles have passed.
+pub fn run(gameboy: &mut Gameboy, budget: u64) -> BlarggResult {
+    let bytes = Rc::new(RefCell::new(Vec::new()));
+    gameboy.memory.serial.attach(Box::new(SerialLog { bytes: bytes.clone() }));
+
+    let start = gameboy.cycles;
+    let mut next_check = start;
+    let mut verdict = None;
+    while gameboy.running && gameboy.cycles - start < budget {
+        gameboy.execute_next();
+        if gameboy.cycles >= next_check {
+            next_check = gameboy.cycles + CHECK_INTERVAL;
+            verdict = memory_verdict(gameboy).or_else(|| serial_verdict(&bytes.borrow()));
+            if verdict.is_some() {
+                break;
+            }
+        }
+    }
+    gameboy.memory.serial.detach();
+
+    let (passed, output) = match verdict {
+        Some((passed, output)) => (Some(passed), output),
+        None => (None, String::from_utf8_lossy(&bytes.borrow()).into_owned()),
+    };
+    BlarggResult {
+        passed,
+        failures: failures(&output),
+        output,
+        cycles: gameboy.cycles - start,
+    }
+}
+
+fn memory_verdict(gameboy: &Gameboy) -> Option<(bool, String)> {
+    let memory = &gameboy.memory;
+    let signature = [memory.read_u8(STATUS + 1), memory.read_u8(STATUS + 2), memory.read_u8(STATUS + 3)];
+    let status = memory.read_u8(STATUS);
+    if signature != SIGNATURE || status == RUNNING {
+        return None;
+    }
+
+    let text: Vec<u8> = (TEXT..0xC000)
+        .map(|address| memory.read_u8(address))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    Some((status == 0, String::from_utf8_lossy(&text).into_owned()))
+}
+
+// Done once a whole line starting with Passed or Failed has come through.
+fn serial_verdict(bytes: &[u8]) -> Option<(bool, String)> {
+    let text = String::from_utf8_lossy(bytes);
+    let complete = &text[..text.rfind('\n')?];
+    let passed = complete.lines().find_map(|line| match line {
+        _ if line.starts_with("Passed") => Some(true),
+        _ if line.starts_with("Failed") => Some(false),
+        _ => None,
+    })?;
+    Some((passed, text.into_owned()))
+}
+
+// cpu_instrs and the other multi-ROM tests list "01:ok  02:01 ...", naming
+// the result of each sub-test. A single test prints "Failed #N" or a reason.
+pub fn failures(output: &str) -> Vec<String> {
+    let results: Vec<(&str, &str)> = output
+        .split_whitespace()
+        .filter_map(|word| word.split_once(':'))
+        .filter(|(number, _)| number.len() == 2 && number.bytes().all(|b| b.is_ascii_digit()))
+        .collect();
+    if !results.is_empty() {
+        return results
+            .into_iter()
+            .filter(|(_, result)| *result != "ok")
+            .map(|(number, _)| number.to_string())
+            .collect();
+    }
+
+    output
+        .lines()
+        .filter(|line| line.starts_with("Failed"))
+        .map(str::to_string)
+        .collect()
+}
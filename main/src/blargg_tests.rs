@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use crate::blargg::*;
+    use crate::gameboy::Gameboy;
+    use crate::testing::gameboy_with_rom_program;
+    use std::path::PathBuf;
+
+    const SECOND: u64 = 4_194_304;
+
+    // The ROMs and how long each gets, in emulated seconds.
+    const ROMS: [(&str, u64); 5] = [
+        ("cpu_instrs.gb", 60),
+        ("instr_timing.gb", 5),
+        ("mem_timing.gb", 10),
+        ("halt_bug.gb", 5),
+        ("dmg_sound.gb", 40),
+    ];
+
+    // ROMs that are known to fail, and why. The rest have to pass, and these
+    // have to keep failing until they're taken off the list.
+    const KNOWN_FAILING: &[(&str, &str)] = &[
+        ("cpu_instrs.gb", "no jumps, calls or CB opcodes yet"),
+        ("instr_timing.gb", "no jumps, calls or CB opcodes yet"),
+        ("mem_timing.gb", "no jumps, calls or CB opcodes yet"),
+        ("halt_bug.gb", "no jumps, calls or HALT yet"),
+        ("dmg_sound.gb", "no jumps, calls or CB opcodes yet"),
+    ];
+
+    // Straight-line code at 0150, since the CPU can't branch yet.
+    // MBC1 with 8KB of RAM.
+    fn gameboy_running(program: &str) -> Gameboy {
+        gameboy_with_rom_program(program, 0x03, 0x02)
+    }
+
+    fn poke(address: u16, value: u8) -> String {
+        format!("ld h, ${:02X}\nld l, ${:02X}\nld a, ${:02X}\nld [hl], a\n", address >> 8, address & 0xFF, value)
+    }
+
+    // Sends each byte and waits out the transfer.
+    fn serial_program(text: &str) -> String {
+        text.bytes().map(|byte| poke(0xFF01, byte) + &poke(0xFF02, 0x81) + "ds 1100, $7F\n").collect()
+    }
+
+    fn memory_program(text: &str, status: u8) -> String {
+        let mut program = poke(0x0000, 0x0A) + &poke(0xA000, 0x80);
+        for (i, byte) in [0xDE, 0xB0, 0x61].into_iter().chain(text.bytes()).chain([0]).enumerate() {
+            program += &poke(0xA001 + i as u16, byte);
+        }
+        program + &poke(0xA000, status)
+    }
+
+    #[test]
+    fn test_reads_serial_output() {
+        let mut gameboy = gameboy_running(&serial_program("halt bug\n\nPassed\n"));
+        let result = run(&mut gameboy, SECOND);
+        assert_eq!(result.passed, Some(true));
+        assert_eq!(result.output, "halt bug\n\nPassed\n");
+        assert!(result.failures.is_empty());
+    }
+
+    #[test]
+    fn test_reads_cartridge_ram_output() {
+        let output = "cpu_instrs\n\n01:ok  02:01  03:ok  04:03\n\nFailed 2 tests.\n";
+        let mut gameboy = gameboy_running(&memory_program(output, 2));
+        let result = run(&mut gameboy, SECOND);
+        assert_eq!(result.passed, Some(false));
+        assert_eq!(result.output, output);
+        assert_eq!(result.failures, ["02", "04"]);
+    }
+
+    #[test]
+    fn test_stops_at_the_budget() {
+        let mut gameboy = gameboy_running("nop");
+        let result = run(&mut gameboy, 100_000);
+        assert_eq!(result.passed, None);
+        assert!(result.cycles >= 100_000 && result.cycles < 100_100);
+    }
+
+    #[test]
+    fn test_failures_of_a_single_test() {
+        assert_eq!(failures("01-special\n\n\nFailed #6\n"), ["Failed #6"]);
+        assert_eq!(failures("instr_timing\n\n\nPassed\n"), Vec::<String>::new());
+    }
+
+    // Needs the ROMs listed in testdata/blargg/README.md; run it with
+    // `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs the ROMs in testdata/blargg"]
+    fn test_blargg_roms() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/blargg");
+        let mut wrong = Vec::new();
+        for (name, seconds) in ROMS {
+            let path = dir.join(name);
+            assert!(path.exists(), "{} isn't in {}", name, dir.display());
+
+            let mut gameboy = Gameboy::new();
+            gameboy.load_cartridge(&path, None).unwrap();
+            gameboy.skip_boot_rom();
+            let result = run(&mut gameboy, seconds * SECOND);
+            let outcome = match result.passed {
+                Some(true) => "passed".to_string(),
+                Some(false) => format!("failed {}", result.failures.join(", ")),
+                None => format!("no result after {}s\n{}", seconds, result.output),
+            };
+            match KNOWN_FAILING.iter().find(|(rom, _)| *rom == name) {
+                None if result.passed != Some(true) => wrong.push(format!("{}: {}", name, outcome)),
+                Some((_, why)) if result.passed == Some(true) => {
+                    wrong.push(format!("{}: passes now, take it off KNOWN_FAILING ({})", name, why))
+                }
+                _ => {}
+            }
+        }
+        assert!(wrong.is_empty(), "{}", wrong.join("\n"));
+    }
+}
@@ -0,0 +1,97 @@
+use once_cell::sync::Lazy;
+
+// Band-limited synthesis in the style of blargg's blip_buf. Instead of
+// sampling a waveform, the caller reports every change in amplitude along with
+// the clock it happened on. Each change is added to the output as a windowed
+// sinc step, so square edges come out without aliasing at any sample rate.
+
+const PHASES: usize = 64;
+const WIDTH: usize = 16;
+const HALF_WIDTH: f64 = (WIDTH / 2) as f64;
+// Passband edge as a fraction of the output sample rate, a little under Nyquist.
+const CUTOFF: f64 = 0.45;
+
+// Impulse response for each fractional position of a step between two output
+// samples, normalised so that a step always settles at exactly its delta.
+static KERNEL: Lazy<[[f32; WIDTH]; PHASES]> = Lazy::new(|| {
+    let mut kernel = [[0.0; WIDTH]; PHASES];
+
+    for (phase, taps) in kernel.iter_mut().enumerate() {
+        let fraction = phase as f64 / PHASES as f64;
+        let mut sum = 0.0;
+        let mut impulse = [0.0f64; WIDTH];
+
+        for (k, tap) in impulse.iter_mut().enumerate() {
+            let x = k as f64 - fraction - HALF_WIDTH + 1.0;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                let arg = std::f64::consts::PI * 2.0 * CUTOFF * x;
+                arg.sin() / arg
+            };
+            // Blackman window over the kernel's width
+            let w = (x + HALF_WIDTH) / WIDTH as f64;
+            let window = 0.42 - 0.5 * (2.0 * std::f64::consts::PI * w).cos()
+                + 0.08 * (4.0 * std::f64::consts::PI * w).cos();
+
+            *tap = sinc * window.max(0.0);
+            sum += *tap;
+        }
+
+        for (k, tap) in taps.iter_mut().enumerate() {
+            *tap = (impulse[k] / sum) as f32;
+        }
+    }
+    kernel
+});
+
+pub struct BlipBuf {
+    // Output samples per input clock
+    factor: f64,
+    // Output position of clock 0 of the current frame
+    offset: f64,
+    deltas: Vec<f32>,
+    integrator: f32,
+}
+
+impl BlipBuf {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        BlipBuf {
+            factor: sample_rate as f64 / clock_rate as f64,
+            offset: 0.0,
+            deltas: vec![0.0; WIDTH],
+            integrator: 0.0,
+        }
+    }
+
+    // Amplitude changed by `delta` at `clock`, counted from the start of the frame.
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.offset + clock as f64 * self.factor;
+        let index = position as usize;
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+
+        if self.deltas.len() < index + WIDTH {
+            self.deltas.resize(index + WIDTH, 0.0);
+        }
+        for (slot, tap) in self.deltas[index..index + WIDTH].iter_mut().zip(&KERNEL[phase]) {
+            *slot += delta * tap;
+        }
+    }
+
+    // Ends the frame after `clocks` and appends every finished sample to `out`.
+    pub fn end_frame(&mut self, clocks: u32, out: &mut Vec<f32>) {
+        let end = self.offset + clocks as f64 * self.factor;
+        let ready = end as usize;
+
+        if self.deltas.len() < ready + WIDTH {
+            self.deltas.resize(ready + WIDTH, 0.0);
+        }
+        for &delta in &self.deltas[..ready] {
+            self.integrator += delta;
+            out.push(self.integrator);
+        }
+
+        self.deltas.drain(..ready);
+        self.offset = end - ready as f64;
+    }
+}
@@ -0,0 +1,180 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+pub const HEADER_TITLE: usize = 0x0134;
+pub const HEADER_CGB_FLAG: usize = 0x0143;
+pub const HEADER_TYPE: usize = 0x0147;
+pub const HEADER_RAM_SIZE: usize = 0x0149;
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mbc {
+    None,
+    Mbc1,
+    Mbc3,
+    Mbc5,
+}
+
+pub struct Cartridge {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mbc: Mbc,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    // MBC1 only: the 2-bit register applies to the RAM bank and ROM bank 0.
+    mbc1_advanced_mode: bool,
+}
+
+impl Cartridge {
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(rom: Vec<u8>) -> std::io::Result<Self> {
+        if rom.len() < 0x150 {
+            return Err(Error::new(ErrorKind::InvalidData, "ROM is too small to hold a header"));
+        }
+
+        let mbc = match rom[HEADER_TYPE] {
+            0x00 | 0x08 | 0x09 => Mbc::None,
+            0x01..=0x03 => Mbc::Mbc1,
+            0x0F..=0x13 => Mbc::Mbc3,
+            0x19..=0x1E => Mbc::Mbc5,
+            other => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("unsupported cartridge type {:02X}", other),
+                ));
+            }
+        };
+
+        let ram_size = match rom[HEADER_RAM_SIZE] {
+            0x02 => RAM_BANK_SIZE,
+            0x03 => 4 * RAM_BANK_SIZE,
+            0x04 => 16 * RAM_BANK_SIZE,
+            0x05 => 8 * RAM_BANK_SIZE,
+            _ => 0,
+        };
+
+        Ok(Cartridge {
+            rom,
+            ram: vec![0; ram_size],
+            mbc,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            mbc1_advanced_mode: false,
+        })
+    }
+
+    pub fn title(&self) -> String {
+        self.rom[HEADER_TITLE..HEADER_CGB_FLAG]
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| b as char)
+            .collect()
+    }
+
+    pub fn mbc(&self) -> Mbc {
+        self.mbc
+    }
+
+    // 0x80 for games that also run on a DMG, 0xC0 for CGB only.
+    pub fn cgb_flag(&self) -> u8 {
+        self.rom[HEADER_CGB_FLAG]
+    }
+
+    pub fn supports_cgb(&self) -> bool {
+        self.cgb_flag() & 0x80 != 0
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => self.rom.get(self.rom_index(address)).copied().unwrap_or(0xFF),
+            0xA000..=0xBFFF => match self.ram_offset(address) {
+                Some(offset) => self.ram[offset],
+                None => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match (self.mbc, address) {
+            (Mbc::None, 0x0000..=0x7FFF) => {}
+            (_, 0x0000..=0x1FFF) => self.ram_enabled = value & 0x0F == 0x0A,
+            (Mbc::Mbc1, 0x2000..=0x3FFF) => self.rom_bank = (value & 0x1F).max(1) as u16,
+            (Mbc::Mbc3, 0x2000..=0x3FFF) => self.rom_bank = (value & 0x7F).max(1) as u16,
+            (Mbc::Mbc5, 0x2000..=0x2FFF) => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            (Mbc::Mbc5, 0x3000..=0x3FFF) => {
+                self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 0x01) << 8)
+            }
+            (Mbc::Mbc1, 0x4000..=0x5FFF) => self.ram_bank = value & 0x03,
+            // RTC registers (0x08-0x0C) are not emulated and read back as open bus.
+            (Mbc::Mbc3, 0x4000..=0x5FFF) => self.ram_bank = value & 0x0F,
+            (Mbc::Mbc5, 0x4000..=0x5FFF) => self.ram_bank = value & 0x0F,
+            (Mbc::Mbc1, 0x6000..=0x7FFF) => self.mbc1_advanced_mode = value & 0x01 != 0,
+            (_, 0xA000..=0xBFFF) => {
+                if let Some(offset) = self.ram_offset(address) {
+                    self.ram[offset] = value;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Changes the ROM byte mapped at `address` in 0000-7FFF, for patching
+    // code at runtime.
+    pub fn patch(&mut self, address: u16, value: u8) {
+        let index = self.rom_index(address);
+        if let Some(byte) = self.rom.get_mut(index) {
+            *byte = value;
+        }
+    }
+
+    // The ROM bank mapped at `address` in 0000-7FFF.
+    pub fn bank_at(&self, address: u16) -> usize {
+        self.rom_index(address) / ROM_BANK_SIZE
+    }
+
+    // Where in the ROM the byte at `address` in 0000-7FFF comes from, given
+    // the banks mapped now.
+    fn rom_index(&self, address: u16) -> usize {
+        let (bank, offset) = match address {
+            0x0000..=0x3FFF => match self.mbc {
+                Mbc::Mbc1 if self.mbc1_advanced_mode => ((self.ram_bank as usize) << 5, address as usize),
+                _ => (0, address as usize),
+            },
+            _ => match self.mbc {
+                Mbc::Mbc1 => (
+                    ((self.ram_bank as usize) << 5) | self.rom_bank as usize,
+                    address as usize - ROM_BANK_SIZE,
+                ),
+                _ => (self.rom_bank as usize, address as usize - ROM_BANK_SIZE),
+            },
+        };
+        let banks = self.rom.len().div_ceil(ROM_BANK_SIZE);
+        (bank % banks) * ROM_BANK_SIZE + offset
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() || (self.mbc != Mbc::None && !self.ram_enabled) {
+            return None;
+        }
+
+        let bank = match self.mbc {
+            Mbc::Mbc1 if !self.mbc1_advanced_mode => 0,
+            Mbc::Mbc3 if self.ram_bank > 0x03 => return None,
+            _ => self.ram_bank as usize,
+        };
+        let offset = bank * RAM_BANK_SIZE + (address as usize - 0xA000);
+        Some(offset % self.ram.len())
+    }
+}
@@ -1,5 +1,3 @@
-use byteorder::{BigEndian, ByteOrder};
-
 #[derive(Debug, PartialEq, Clone, Copy)]
 #[repr(u8)]
 pub enum Reg8 {
@@ -59,10 +57,17 @@ pub struct Gbz80 {
     pub program_counter: u16,
 }
 
+impl Default for Gbz80 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Gbz80 {
     pub const FLAG_Z: u8 = 1 << 7;
     pub const FLAG_N: u8 = 1 << 6;
     pub const FLAG_H: u8 = 1 << 5;
+    pub const FLAG_C: u8 = 1 << 4;
 
     pub fn new() -> Self {
         Gbz80 {
@@ -123,10 +128,14 @@ impl Gbz80 {
         if value {
             self.write_reg8(Reg8::F, self.reg8(Reg8::F) | flag);
         }else{
-            self.write_reg8(Reg8::F, self.reg8(Reg8::F) & flag);
+            self.write_reg8(Reg8::F, self.reg8(Reg8::F) & !flag);
         }
     }
 
+    pub fn flag(&self, flag: u8) -> bool {
+        self.f & flag != 0
+    }
+
     pub fn reg16(&self, reg: Reg16) -> u16 {
         match reg {
             Reg16::BC => self.bc(),
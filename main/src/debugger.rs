@@ -0,0 +1,415 @@
+use crate::cpu::Gbz80;
+use crate::gameboy::{Fault, Gameboy};
+use crate::asm;
+use crate::disasm;
+use crate::parse::{parse_address, parse_hex};
+use crate::watch::{WatchHit, Watchpoint};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const HELP: &str = "\
+step [N]          (s)  run N instructions, default 1
+continue          (c)  run until a breakpoint
+finish            (f)  run until the current function returns
+break [ADDR]      (b)  stop at ADDR, or list breakpoints
+break op XX            stop before any instruction with opcode XX
+delete [ADDR]     (d)  remove a breakpoint, or all of them
+delete op XX           remove an opcode breakpoint
+watch [SPEC]           stop on an access, or list watchpoints. SPEC is
+                       read|write|change:ADDR[-END], or write:ADDR=XX
+unwatch [N]            remove watchpoint N, or all of them
+regs              (r)  show the registers and flags
+x ADDR [LEN]           hexdump LEN bytes, default 64
+write ADDR XX...  (w)  write bytes starting at ADDR
+asm ADDR CODE          assemble CODE into memory at ADDR, ROM included.
+                       Separate instructions with ::
+disasm [ADDR] [N] (l)  disassemble N instructions at ADDR, default around PC
+quit              (q)  leave the debugger
+Addresses and bytes are hex. An empty line repeats the last command.
+";
+
+// How many instructions `disasm` shows by default.
+const DISASM_LINES: usize = 10;
+
+// How many instructions run between looks at the interrupt flag.
+const INTERRUPT_INTERVAL: u64 = 4096;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Stop {
+    Steps,
+    Breakpoint(u16),
+    Opcode(u8),
+    Watchpoint(Vec<WatchHit>),
+    Returned,
+    Fault(Fault),
+    Interrupted,
+    Halted,
+}
+
+// A line-based debugger driving `Gameboy::execute_next`. Commands come from
+// `execute`, so the REPL in `run` is only a thin loop around it.
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    opcode_breakpoints: BTreeSet<u8>,
+    last_command: String,
+    quit: bool,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            opcode_breakpoints: BTreeSet::new(),
+            last_command: String::new(),
+            quit: false,
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Setting this, from a Ctrl-C handler say, stops the program if it's
+    // running.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.interrupted.clone()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn add_opcode_breakpoint(&mut self, opcode: u8) {
+        self.opcode_breakpoints.insert(opcode);
+    }
+
+    // Reads commands until `quit` or the end of the input.
+    pub fn run<R: BufRead, W: Write>(&mut self, gameboy: &mut Gameboy, mut input: R, mut output: W) -> std::io::Result<()> {
+        write!(output, "{}", disassemble_line(gameboy, gameboy.cpu.program_counter, true).0)?;
+
+        while !self.quit {
+            write!(output, "(gb) ")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                line => {
+                    self.last_command = line.to_string();
+                    line.to_string()
+                }
+            };
+
+            match self.execute(gameboy, &line) {
+                Ok(text) => write!(output, "{}", text)?,
+                Err(err) => writeln!(output, "error: {}", err)?,
+            }
+        }
+        Ok(())
+    }
+
+    // Runs one command and returns what it prints.
+    pub fn execute(&mut self, gameboy: &mut Gameboy, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(String::new());
+        };
+        let args: Vec<&str> = words.collect();
+
+        match (command, args.as_slice()) {
+            ("s" | "step", []) => Ok(self.resume(gameboy, Some(1), false)),
+            ("s" | "step", [count]) => {
+                let count = count.parse().map_err(|_| format!("invalid count '{}'", count))?;
+                Ok(self.resume(gameboy, Some(count), false))
+            }
+            ("c" | "continue", []) => Ok(self.resume(gameboy, None, false)),
+            // Without RET the function could never return.
+            ("f" | "finish", []) if !Gameboy::has_handler(0xC9) => {
+                Err("finish needs RET, which isn't implemented yet".to_string())
+            }
+            ("f" | "finish", []) => Ok(self.resume(gameboy, None, true)),
+            ("b" | "break", []) => Ok(self.list_breakpoints()),
+            ("b" | "break", ["op", opcode]) => {
+                self.add_opcode_breakpoint(parse_byte(opcode)?);
+                Ok(String::new())
+            }
+            ("b" | "break", [address]) => {
+                self.add_breakpoint(parse_address(address)?);
+                Ok(String::new())
+            }
+            ("d" | "delete", []) => {
+                self.breakpoints.clear();
+                self.opcode_breakpoints.clear();
+                Ok(String::new())
+            }
+            ("d" | "delete", ["op", opcode]) => {
+                let opcode = parse_byte(opcode)?;
+                match self.opcode_breakpoints.remove(&opcode) {
+                    true => Ok(String::new()),
+                    false => Err(format!("no breakpoint on opcode {:02X}", opcode)),
+                }
+            }
+            ("d" | "delete", [address]) => {
+                let address = parse_address(address)?;
+                match self.breakpoints.remove(&address) {
+                    true => Ok(String::new()),
+                    false => Err(format!("no breakpoint at {:04X}", address)),
+                }
+            }
+            ("watch", []) => Ok(list_watchpoints(gameboy)),
+            ("watch", [spec]) => {
+                gameboy.memory.add_watchpoint(spec.parse::<Watchpoint>()?);
+                Ok(String::new())
+            }
+            ("unwatch", []) => {
+                gameboy.memory.clear_watchpoints();
+                Ok(String::new())
+            }
+            ("unwatch", [index]) => {
+                let index = index.parse().map_err(|_| format!("invalid watchpoint '{}'", index))?;
+                match gameboy.memory.remove_watchpoint(index) {
+                    Some(_) => Ok(String::new()),
+                    None => Err(format!("no watchpoint {}", index)),
+                }
+            }
+            ("r" | "regs", []) => Ok(registers(&gameboy.cpu)),
+            ("x", [address]) => Ok(hexdump(gameboy, parse_address(address)?, 0x40)),
+            ("x", [address, length]) => {
+                let length = length.parse().map_err(|_| format!("invalid length '{}'", length))?;
+                Ok(hexdump(gameboy, parse_address(address)?, length))
+            }
+            ("w" | "write", [address, bytes @ ..]) if !bytes.is_empty() => {
+                let address = parse_address(address)?;
+                let bytes = bytes.iter().map(|byte| parse_byte(byte)).collect::<Result<Vec<_>, _>>()?;
+                for (i, byte) in bytes.into_iter().enumerate() {
+                    gameboy.memory.write_u8(address.wrapping_add(i as u16), byte);
+                }
+                Ok(String::new())
+            }
+            ("asm", [address, _, ..]) => {
+                let source = line.trim_start()["asm".len()..].trim_start()[address.len()..].trim();
+                let address = parse_address(address)?;
+                let length = asm::patch(gameboy, address, source)?;
+                Ok(format!("{} bytes at {:04X}\n", length, address))
+            }
+            ("l" | "disasm", []) => Ok(disassemble_around_pc(gameboy, DISASM_LINES)),
+            ("l" | "disasm", [address]) => Ok(disassemble(gameboy, parse_address(address)?, DISASM_LINES)),
+            ("l" | "disasm", [address, count]) => {
+                let count = count.parse().map_err(|_| format!("invalid count '{}'", count))?;
+                Ok(disassemble(gameboy, parse_address(address)?, count))
+            }
+            ("q" | "quit", []) => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            ("h" | "help", []) => Ok(HELP.to_string()),
+            _ => Err(format!("can't parse '{}', try help", line)),
+        }
+    }
+
+    // Executes instructions until `steps` have run, a breakpoint is reached
+    // or, with `finish`, the function we're in returns. Breakpoints at the PC
+    // we start from are ignored so that a stopped program can go on.
+    pub fn run_until(&mut self, gameboy: &mut Gameboy, steps: Option<u64>, finish: bool) -> Stop {
+        let start_sp = gameboy.cpu.stack_pointer;
+        let mut executed = 0;
+
+        loop {
+            if steps == Some(executed) {
+                return Stop::Steps;
+            }
+            if let Some(fault) = gameboy.fault() {
+                return Stop::Fault(fault);
+            }
+            if !gameboy.running {
+                return Stop::Halted;
+            }
+
+            let pc = gameboy.cpu.program_counter;
+            let opcode = gameboy.memory.read_u8(pc);
+            if executed > 0 {
+                if self.breakpoints.contains(&pc) {
+                    return Stop::Breakpoint(pc);
+                }
+                if self.opcode_breakpoints.contains(&opcode) {
+                    return Stop::Opcode(opcode);
+                }
+                if executed % INTERRUPT_INTERVAL == 0 && self.interrupted.swap(false, Ordering::Relaxed) {
+                    return Stop::Interrupted;
+                }
+            }
+
+            gameboy.execute_next();
+            executed += 1;
+
+            let hits = gameboy.memory.take_watch_hits();
+            if !hits.is_empty() {
+                return Stop::Watchpoint(hits);
+            }
+
+            // A return taken from this frame leaves SP above where it started.
+            if finish && is_return(opcode) && gameboy.cpu.stack_pointer > start_sp {
+                return Stop::Returned;
+            }
+        }
+    }
+
+    fn resume(&mut self, gameboy: &mut Gameboy, steps: Option<u64>, finish: bool) -> String {
+        // A Ctrl-C at the prompt is for nothing that's running.
+        self.interrupted.store(false, Ordering::Relaxed);
+        let stop = self.run_until(gameboy, steps, finish);
+        let pc = gameboy.cpu.program_counter;
+
+        let mut text = match stop {
+            Stop::Steps => String::new(),
+            Stop::Breakpoint(address) => format!("breakpoint at {:04X}\n", address),
+            Stop::Opcode(opcode) => format!("opcode {:02X} at {:04X}\n", opcode, pc),
+            Stop::Watchpoint(hits) => hits.iter().map(|hit| format!("watchpoint: {}\n", hit)).collect(),
+            Stop::Returned => format!("returned to {:04X}\n", pc),
+            Stop::Fault(fault) => format!("{}\n", fault),
+            Stop::Interrupted => "interrupted\n".to_string(),
+            Stop::Halted => "emulation stopped\n".to_string(),
+        };
+        text.push_str(&disassemble_line(gameboy, pc, true).0);
+        text
+    }
+
+    fn list_breakpoints(&self) -> String {
+        let mut text = String::new();
+        for address in &self.breakpoints {
+            writeln!(text, "{:04X}", address).unwrap();
+        }
+        for opcode in &self.opcode_breakpoints {
+            writeln!(text, "op {:02X}", opcode).unwrap();
+        }
+        if text.is_empty() {
+            text.push_str("no breakpoints\n");
+        }
+        text
+    }
+}
+
+fn list_watchpoints(gameboy: &Gameboy) -> String {
+    let watchpoints = gameboy.memory.watchpoints();
+    if watchpoints.is_empty() {
+        return "no watchpoints\n".to_string();
+    }
+    watchpoints
+        .iter()
+        .enumerate()
+        .map(|(i, watchpoint)| format!("{}: {}\n", i, watchpoint))
+        .collect()
+}
+
+fn is_return(opcode: u8) -> bool {
+    matches!(opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9)
+}
+
+fn parse_byte(s: &str) -> Result<u8, String> {
+    parse_hex(s).map_err(|_| format!("invalid byte '{}'", s))
+}
+
+pub fn registers(cpu: &Gbz80) -> String {
+    let flag = |mask: u8, name: char| if cpu.f & mask != 0 { name } else { '-' };
+    format!(
+        "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X}\n\
+         A={:02X} F={:02X} B={:02X} C={:02X} D={:02X} E={:02X} H={:02X} L={:02X}\n\
+         flags {}{}{}{}\n",
+        cpu.af(),
+        cpu.bc(),
+        cpu.de(),
+        cpu.hl(),
+        cpu.stack_pointer,
+        cpu.program_counter,
+        cpu.a,
+        cpu.f,
+        cpu.b,
+        cpu.c,
+        cpu.d,
+        cpu.e,
+        cpu.h,
+        cpu.l,
+        flag(Gbz80::FLAG_Z, 'Z'),
+        flag(Gbz80::FLAG_N, 'N'),
+        flag(Gbz80::FLAG_H, 'H'),
+        flag(Gbz80::FLAG_C, 'C'),
+    )
+}
+
+// Lengths past the 64K address space are cut down to it.
+pub fn hexdump(gameboy: &Gameboy, address: u16, length: usize) -> String {
+    let length = length.min(0x10000);
+    let mut text = String::new();
+    for line in 0..length.div_ceil(16) {
+        let start = address.wrapping_add((line * 16) as u16);
+        let count = (length - line * 16).min(16);
+        let bytes: Vec<u8> = (0..count as u16).map(|i| gameboy.memory.read_u8(start.wrapping_add(i))).collect();
+
+        write!(text, "{:04X}:", start).unwrap();
+        for byte in &bytes {
+            write!(text, " {:02X}", byte).unwrap();
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        writeln!(text, "{:width$}  |{}|", "", ascii, width = (16 - count) * 3).unwrap();
+    }
+    text
+}
+
+// One instruction as "ADDR: BYTES NAME", with the length it took.
+fn disassemble_line(gameboy: &Gameboy, address: u16, current: bool) -> (String, u16) {
+    let instruction = &disasm::decode_many(|address| gameboy.memory.read_u8(address), address, 1)[0];
+
+    let mut bytes = String::new();
+    for byte in &instruction.bytes {
+        write!(bytes, "{:02X} ", byte).unwrap();
+    }
+    let marker = if current { "=>" } else { "  " };
+    (
+        format!("{} {:04X}: {:9} {}\n", marker, address, bytes, instruction),
+        instruction.length(),
+    )
+}
+
+pub fn disassemble(gameboy: &Gameboy, address: u16, count: usize) -> String {
+    let mut text = String::new();
+    let mut address = address;
+    for _ in 0..count {
+        let (line, length) = disassemble_line(gameboy, address, address == gameboy.cpu.program_counter);
+        text.push_str(&line);
+        address = address.wrapping_add(length);
+    }
+    text
+}
+
+// Code before the PC can't be decoded with certainty, so this starts from the
+// furthest point within a few bytes that lines up with the PC.
+fn disassemble_around_pc(gameboy: &Gameboy, count: usize) -> String {
+    let pc = gameboy.cpu.program_counter;
+    for back in (1..=8u16).rev() {
+        let start = pc.wrapping_sub(back);
+        let mut address = start;
+        let mut lines = 0;
+        while address != pc && lines < count / 2 {
+            address = address.wrapping_add(disassemble_line(gameboy, address, false).1);
+            lines += 1;
+        }
+        if address == pc {
+            return disassemble(gameboy, start, count);
+        }
+    }
+    disassemble(gameboy, pc, count)
+}
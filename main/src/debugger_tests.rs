@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests {
+    use crate::debugger::*;
+    use crate::gameboy::Gameboy;
+    use crate::testing::gameboy_with_program;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    const PROGRAM: &str = "ld a, $12\nld b, $34\nld c, b\nld a, d\nld e, a";
+
+    #[test]
+    fn test_step_runs_instructions() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        let mut debugger = Debugger::new();
+
+        let output = debugger.execute(&mut gameboy, "step 2").unwrap();
+
+        assert_eq!(gameboy.cpu.a, 0x12);
+        assert_eq!(gameboy.cpu.b, 0x34);
+        assert_eq!(output, "=> C004: 48        LD C,B\n");
+    }
+
+    #[test]
+    fn test_continue_stops_at_breakpoints() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        let mut debugger = Debugger::new();
+
+        debugger.execute(&mut gameboy, "break C005").unwrap();
+        debugger.execute(&mut gameboy, "b op 5F").unwrap();
+        assert_eq!(debugger.execute(&mut gameboy, "break").unwrap(), "C005\nop 5F\n");
+
+        let output = debugger.execute(&mut gameboy, "continue").unwrap();
+        assert!(output.starts_with("breakpoint at C005\n"));
+        assert_eq!(gameboy.cpu.c, 0x34);
+
+        let output = debugger.execute(&mut gameboy, "c").unwrap();
+        assert!(output.starts_with("opcode 5F at C006\n"));
+
+        debugger.execute(&mut gameboy, "delete").unwrap();
+        assert_eq!(debugger.execute(&mut gameboy, "break").unwrap(), "no breakpoints\n");
+    }
+
+    #[test]
+    fn test_continue_stops_at_unimplemented_instruction() {
+        let mut gameboy = gameboy_with_program("ld a, $12\njp $C000");
+        let mut debugger = Debugger::new();
+
+        let output = debugger.execute(&mut gameboy, "continue").unwrap();
+
+        assert!(output.starts_with("opcode C3 at C002 isn't implemented\n=> C002:"), "{}", output);
+        assert_eq!(gameboy.cpu.program_counter, 0xC002);
+    }
+
+    #[test]
+    fn test_interrupt_stops_continue() {
+        // With nothing else in memory the CPU runs NOPs round the address
+        // space forever.
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.set_flat_bus(true);
+        let mut debugger = Debugger::new();
+        let interrupted = debugger.interrupt_flag();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let interrupter = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    interrupted.store(true, Ordering::Relaxed);
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            })
+        };
+        let output = debugger.execute(&mut gameboy, "continue").unwrap();
+        done.store(true, Ordering::Relaxed);
+        interrupter.join().unwrap();
+
+        assert!(output.starts_with("interrupted\n"), "{}", output);
+        assert!(gameboy.running);
+    }
+
+    #[test]
+    fn test_finish_needs_ret() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        let mut debugger = Debugger::new();
+
+        let err = debugger.execute(&mut gameboy, "finish").unwrap_err();
+
+        assert_eq!(err, "finish needs RET, which isn't implemented yet");
+        assert_eq!(gameboy.cpu.program_counter, 0xC000);
+    }
+
+    #[test]
+    fn test_registers_show_flags() {
+        let mut gameboy = Gameboy::new();
+        gameboy.cpu.set_af(0x01B0);
+        gameboy.cpu.set_hl(0x014D);
+
+        let output = registers(&gameboy.cpu);
+
+        assert!(output.contains("AF=01B0"));
+        assert!(output.contains("HL=014D"));
+        assert!(output.contains("A=01 F=B0"));
+        assert!(output.ends_with("flags Z-HC\n"));
+    }
+
+    #[test]
+    fn test_write_and_hexdump() {
+        let mut gameboy = Gameboy::new();
+        let mut debugger = Debugger::new();
+
+        debugger.execute(&mut gameboy, "write C010 48 69 $21").unwrap();
+        let output = debugger.execute(&mut gameboy, "x C010 3").unwrap();
+
+        assert_eq!(output, format!("C010: 48 69 21{}  |Hi!|\n", " ".repeat(39)));
+        assert_eq!(hexdump(&gameboy, 0xC000, 32).lines().count(), 2);
+    }
+
+    #[test]
+    fn test_hexdump_stops_at_64k() {
+        let mut gameboy = Gameboy::new();
+        let mut debugger = Debugger::new();
+
+        let output = debugger.execute(&mut gameboy, "x FFF0 65536").unwrap();
+        assert_eq!(output.lines().count(), 0x1000);
+        assert!(output.lines().nth(1).unwrap().starts_with("0000:"));
+        assert!(output.lines().last().unwrap().starts_with("FFE0:"));
+        let output = debugger.execute(&mut gameboy, "x 0 99999999999").unwrap();
+        assert_eq!(output.lines().count(), 0x1000);
+    }
+
+    #[test]
+    fn test_asm_patches_memory() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        let mut debugger = Debugger::new();
+
+        let output = debugger.execute(&mut gameboy, "asm C004  xor a :: ld [hl+], a").unwrap();
+
+        assert_eq!(output, "2 bytes at C004\n");
+        assert_eq!(
+            disassemble(&gameboy, 0xC004, 2),
+            "   C004: AF        XOR A,A\n   C005: 22        LD (HL+),A\n"
+        );
+        assert!(debugger.execute(&mut gameboy, "asm C000 bogus").is_err());
+    }
+
+    #[test]
+    fn test_disassemble_from_pc() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        gameboy.cpu.program_counter = 0xC002;
+
+        let output = disassemble(&gameboy, 0xC000, 3);
+
+        assert_eq!(
+            output,
+            "   C000: 3E 12     LD A,$12\n=> C002: 06 34     LD B,$34\n   C004: 48        LD C,B\n"
+        );
+    }
+
+    #[test]
+    fn test_repl_repeats_last_command() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        let mut debugger = Debugger::new();
+        let mut output = Vec::new();
+
+        debugger.run(&mut gameboy, "step\n\nbogus\nquit\nstep\n".as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(gameboy.cpu.program_counter, 0xC004);
+        assert!(output.contains("error: can't parse 'bogus'"));
+        assert_eq!(output.matches("(gb) ").count(), 4);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::opcodes;
+use std::fmt;
+use std::fmt::Write as _;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+
+// One decoded instruction. Bytes missing from the end of the input read as
+// zero, so decoding never fails; opcodes the CPU doesn't have come out as
+// `DB $XX`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+impl Instruction {
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    // Where a JR, JP, CALL or RST goes, if it can be known without running it.
+    pub fn target(&self) -> Option<u16> {
+        let opcode = self.bytes[0];
+        match opcode {
+            0x18 | 0x20 | 0x28 | 0x30 | 0x38 => Some(
+                self.address
+                    .wrapping_add(self.length())
+                    .wrapping_add(self.bytes[1] as i8 as u16),
+            ),
+            0xC2 | 0xC3 | 0xCA | 0xD2 | 0xDA | 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => {
+                Some(u16::from_le_bytes([self.bytes[1], self.bytes[2]]))
+            }
+            _ if opcode & 0xC7 == 0xC7 => Some((opcode & 0x38) as u16),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+// Decodes the instruction at the start of `bytes`, which sits at `address`.
+pub fn decode(bytes: &[u8], address: u16) -> Instruction {
+    let byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    let opcode = byte(0);
+    let info = opcodes::lookup(opcode, byte(1));
+
+    if info.group == "unused" {
+        return Instruction {
+            address,
+            bytes: vec![opcode],
+            text: format!("DB ${:02X}", opcode),
+        };
+    }
+
+    let bytes: Vec<u8> = (0..info.length as usize).map(byte).collect();
+    let text = if info.name.contains("u16") {
+        info.name.replace("u16", &format!("${:02X}{:02X}", bytes[2], bytes[1]))
+    } else if info.name.contains("u8") {
+        info.name.replace("u8", &format!("${:02X}", bytes[1]))
+    } else if info.name.starts_with("JR") {
+        // Relative jumps keep the raw offset byte, see `Instruction::target`.
+        info.name.replace("i8", &format!("${:02X}", bytes[1]))
+    } else if info.name.contains("+i8") {
+        // LD HL,SP+i8
+        info.name.replace("+i8", &format!("{:+}", bytes[1] as i8))
+    } else if info.name.contains("i8") {
+        // ADD SP,i8
+        info.name.replace("i8", &(bytes[1] as i8).to_string())
+    } else {
+        info.name.clone()
+    };
+
+    Instruction { address, bytes, text }
+}
+
+// Decodes instructions one after another from `start` until `count` are done.
+// `read` gives the byte at an address.
+pub fn decode_many<F: Fn(u16) -> u8>(read: F, start: u16, count: usize) -> Vec<Instruction> {
+    let mut instructions = Vec::with_capacity(count);
+    let mut address = start;
+    for _ in 0..count {
+        let bytes = [read(address), read(address.wrapping_add(1)), read(address.wrapping_add(2))];
+        let instruction = decode(&bytes, address);
+        address = address.wrapping_add(instruction.length());
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+// Where a ROM file offset appears to the CPU: bank 0 at 0000, every other
+// bank switched in at 4000.
+pub fn rom_address(offset: usize) -> (usize, u16) {
+    let bank = offset / ROM_BANK_SIZE;
+    let address = match bank {
+        0 => offset,
+        _ => ROM_BANK_SIZE + offset % ROM_BANK_SIZE,
+    };
+    (bank, address as u16)
+}
+
+// A listing of the instructions that start in rom[start..end], one per line
+// as "BB:AAAA  BYTES     TEXT".
+pub fn listing(rom: &[u8], start: usize, end: usize) -> String {
+    let end = end.min(rom.len());
+    let mut text = String::new();
+    let mut offset = start;
+    while offset < end {
+        let (bank, address) = rom_address(offset);
+        // Instructions don't run on past the end of their bank.
+        let bank_end = ((bank + 1) * ROM_BANK_SIZE).min(rom.len());
+        let instruction = decode(&rom[offset..bank_end], address);
+
+        let mut bytes = String::new();
+        for byte in &instruction.bytes {
+            write!(bytes, "{:02X} ", byte).unwrap();
+        }
+        writeln!(text, "{:02X}:{:04X}  {:9} {}", bank, address, bytes, instruction).unwrap();
+        offset += instruction.bytes.len();
+    }
+    text
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use crate::disasm::*;
+
+    fn text(bytes: &[u8]) -> String {
+        decode(bytes, 0x0150).text
+    }
+
+    #[test]
+    fn test_operands_are_resolved() {
+        assert_eq!(text(&[0x00]), "NOP");
+        assert_eq!(text(&[0x32]), "LD (HL-),A");
+        assert_eq!(text(&[0x3E, 0x12]), "LD A,$12");
+        assert_eq!(text(&[0x21, 0x34, 0x12]), "LD HL,$1234");
+        assert_eq!(text(&[0xEA, 0x00, 0xC0]), "LD ($C000),A");
+        assert_eq!(text(&[0xE0, 0x40]), "LD (FF00+$40),A");
+        assert_eq!(text(&[0x20, 0xFB]), "JR NZ,$FB");
+        assert_eq!(text(&[0xE8, 0xFE]), "ADD SP,-2");
+        assert_eq!(text(&[0xF8, 0x05]), "LD HL,SP+5");
+        assert_eq!(text(&[0xCD, 0x00, 0x40]), "CALL $4000");
+    }
+
+    #[test]
+    fn test_cb_prefixed_instructions() {
+        let instruction = decode(&[0xCB, 0x7C], 0);
+        assert_eq!(instruction.text, "BIT 7,H");
+        assert_eq!(instruction.length(), 2);
+        assert_eq!(text(&[0xCB, 0x36]), "SWAP (HL)");
+    }
+
+    #[test]
+    fn test_illegal_opcodes() {
+        for opcode in [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD] {
+            let instruction = decode(&[opcode, 0x12, 0x34], 0);
+            assert_eq!(instruction.text, format!("DB ${:02X}", opcode));
+            assert_eq!(instruction.length(), 1);
+        }
+    }
+
+    #[test]
+    fn test_jump_targets() {
+        assert_eq!(decode(&[0x20, 0xFB], 0x0150).target(), Some(0x014D));
+        assert_eq!(decode(&[0x18, 0x02], 0x0150).target(), Some(0x0154));
+        assert_eq!(decode(&[0xC3, 0x50, 0x01], 0).target(), Some(0x0150));
+        assert_eq!(decode(&[0xFF], 0).target(), Some(0x0038));
+        assert_eq!(decode(&[0xE9], 0).target(), None);
+    }
+
+    #[test]
+    fn test_short_input_reads_as_zero() {
+        let instruction = decode(&[0x21, 0x34], 0);
+        assert_eq!(instruction.bytes, [0x21, 0x34, 0x00]);
+        assert_eq!(instruction.text, "LD HL,$0034");
+    }
+
+    #[test]
+    fn test_decode_many_follows_on() {
+        let program = [0x3E, 0x12, 0xCB, 0x37, 0x76];
+        let instructions = decode_many(|address| program.get(address as usize).copied().unwrap_or(0), 0, 3);
+
+        let texts: Vec<_> = instructions.iter().map(|i| (i.address, i.text.as_str())).collect();
+        assert_eq!(texts, [(0, "LD A,$12"), (2, "SWAP A"), (4, "HALT")]);
+    }
+
+    #[test]
+    fn test_listing_uses_bank_addresses() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0xC3;
+        rom[0x0101] = 0x50;
+        rom[0x0102] = 0x01;
+        rom[ROM_BANK_SIZE] = 0xAF;
+
+        assert_eq!(listing(&rom, 0x0100, 0x0104), "00:0100  C3 50 01  JP $0150\n00:0103  00        NOP\n");
+        assert_eq!(listing(&rom, ROM_BANK_SIZE, ROM_BANK_SIZE + 1), "01:4000  AF        XOR A,A\n");
+        assert_eq!(rom_address(ROM_BANK_SIZE * 5 + 0x10), (5, 0x4010));
+    }
+}
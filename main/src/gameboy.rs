@@ -1,74 +1,144 @@
+use crate::apu::{HighPassFilter, NR50, NR51, NR52};
+use crate::cartridge::Cartridge;
 use crate::cpu::Gbz80;
-use crate::memory::GbMemory;
+use crate::joypad::Button;
+use crate::memory::{GbMemory, INT_JOYPAD};
+use crate::ppu::{BGP, LCDC, OBP0, OBP1};
+use crate::screenshot::{self, ImageFormat};
+use crate::timer::DIV;
+use crate::trace::Tracer;
 use once_cell::sync::Lazy;
+use std::fmt;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::Read;
+use std::path::Path;
+
+pub const CYCLES_PER_FRAME: u32 = 70224;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Model {
+    Dmg,
+    Cgb,
+}
+
+// An instruction the CPU can't run yet. Reaching one stops emulation.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Fault {
+    pub opcode: u8,
+    pub address: u16,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "opcode {:02X} at {:04X} isn't implemented", self.opcode, self.address)
+    }
+}
 
 pub struct Gameboy {
     pub running: bool,
+    // Why `running` went false, if it was an instruction we can't run.
+    fault: Option<Fault>,
+    pub model: Model,
+    // T-cycles run since power on, at the CPU's speed.
+    pub cycles: u64,
     pub cpu: Gbz80,
     pub memory: GbMemory,
+    // Logs each instruction before it runs.
+    pub tracer: Option<Tracer>,
+    // Treat LD B,B as a breakpoint, the way test ROMs such as Mooneye's
+    // signal that they've finished.
+    pub break_on_ld_b_b: bool,
+    ld_b_b_hit: bool,
+    // Cycles the last frame ran over by, taken off the next one.
+    frame_overshoot: u32,
+    audio_frame: Vec<f32>,
+    channel_audio_frames: [Vec<f32>; 4],
 }
 
 type GameboyInstruction = fn(&mut Gameboy, u8);
 
-static DISPATCH: Lazy<[GameboyInstruction; 256]> = Lazy::new(|| {
-    let mut table: [GameboyInstruction; 256] = [Gameboy::not_implemented; 256];
-    table[0x00] = Gameboy::nop;
+// T-cycles per opcode when no branch is taken, taken from dmgops.json.
+#[rustfmt::skip]
+static CYCLES: [u8; 256] = [
+     4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4,
+     4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4,
+     8, 12,  8,  8,  4,  4,  8,  4,  8,  8,  8,  8,  4,  4,  8,  4,
+     8, 12,  8,  8, 12, 12, 12,  4,  8,  8,  8,  8,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     8, 12, 12, 16, 12, 16,  8, 16,  8, 16, 12,  4, 12, 24,  8, 16,
+     8, 12, 12,  4, 12, 16,  8, 16,  8, 16, 12,  4, 12,  4,  8, 16,
+    12, 12,  8,  4,  4, 16,  8, 16, 16,  4, 16,  4,  4,  4,  8, 16,
+    12, 12,  8,  4,  4, 16,  8, 16, 12,  8, 16,  4,  4,  4,  8, 16,
+];
 
-    for i in 0x01..=0x3f {
+// The handler for each opcode, or None for the ones that aren't implemented.
+static DISPATCH: Lazy<[Option<GameboyInstruction>; 256]> = Lazy::new(|| {
+    let mut table: [Option<GameboyInstruction>; 256] = [None; 256];
+    table[0x00] = Some(Gameboy::nop);
+
+    for (i, entry) in table.iter_mut().enumerate().take(0x40).skip(1) {
         let lsb4 = i & 0x0f;
         match lsb4 {
-            0x01 | 0x02 | 0x06 | 0x0A | 0x0E => table[i] = Gameboy::ld,
-            //0x03 | 0x04 | 0x0C => table[i] = Gameboy::inc,
-            //0x05 | 0x0B | 0x0D => table[i] = Gameboy::dec,
-            //0x09 => table[i] = Gameboy::add,
-            _ => table[i] = Gameboy::not_implemented,
+            0x01 | 0x02 | 0x06 | 0x0A | 0x0E => *entry = Some(Gameboy::ld),
+            0x04 | 0x0C => *entry = Some(Gameboy::inc),
+            0x05 | 0x0D => *entry = Some(Gameboy::dec),
+            //0x09 => *entry = Gameboy::add,
+            _ => {}
         }
     }
 
-    table[0x40..=0x7f].fill(Gameboy::ld);
+    table[0x10] = Some(Gameboy::stop);
+    table[0x40..=0x7f].fill(Some(Gameboy::ld));
     for i in [0xE0, 0xE2, 0xEA, 0xF0, 0xF2, 0xF8, 0xF9, 0xFA] {
-        table[i] = Gameboy::ld;
+        table[i] = Some(Gameboy::ld);
     }
     //table[0x07] = Gameboy::RLCA;
     //table[0x17] = Gameboy::RLA;
-    //table[0x27] = Gameboy::DAA;
-    //table[0x37] = Gameboy::SCF;
+    table[0x27] = Some(Gameboy::daa);
+    table[0x2F] = Some(Gameboy::cpl);
+    table[0x37] = Some(Gameboy::scf);
+    table[0x3F] = Some(Gameboy::ccf);
 
-    table[0x76] = Gameboy::not_implemented; // HALT
+    table[0x76] = None; // HALT
 
-    //table[0x80..=0x87].fill(Gameboy::add);
-    //table[0x88..=0x8F].fill(Gameboy::adc);
-    //table[0x90..=0x97].fill(Gameboy::sub);
-    //table[0x98..=0x9F].fill(Gameboy::sbc);
-    //table[0xA0..=0xA7].fill(Gameboy::and);
-    table[0xA8..=0xAF].fill(Gameboy::xor);
-    //table[0xB0..=0xB7].fill(Gameboy::or);
-    //table[0xB8..=0xBF].fill(Gameboy::cp);
+    table[0x80..=0x87].fill(Some(Gameboy::add));
+    table[0x88..=0x8F].fill(Some(Gameboy::adc));
+    table[0x90..=0x97].fill(Some(Gameboy::sub));
+    table[0x98..=0x9F].fill(Some(Gameboy::sbc));
+    table[0xA0..=0xA7].fill(Some(Gameboy::and));
+    table[0xA8..=0xAF].fill(Some(Gameboy::xor));
+    table[0xB0..=0xB7].fill(Some(Gameboy::or));
+    table[0xB8..=0xBF].fill(Some(Gameboy::cp));
 
     //table[0xC0] = Gameboy::ret;
     //table[0xD0] = Gameboy::ret;
     //table[0xD8] = Gameboy::ret;
 
-    //table[0xCE] Gameboy::adc;
-    //table[0xE6] = Gameboy::and;
+    table[0xCE] = Some(Gameboy::adc);
+    table[0xE6] = Some(Gameboy::and);
     //table[0xCB] = Gameboy::cb;
-    //table[0xFE] = Gameboy::cp;
+    table[0xFE] = Some(Gameboy::cp);
     //table[0xF3] = Gameboy::di;
     //table[0xFB] = Gameboy::ei;
-    //table[0xF6] = Gameboy::or;
+    table[0xF6] = Some(Gameboy::or);
     //table[0xD9] = Gameboy::reti;
-    //table[0xDE] = Gameboy::sbc;
-    //table[0xD6] = Gameboy::sub;
-    table[0xEE] = Gameboy::xor;
+    table[0xDE] = Some(Gameboy::sbc);
+    table[0xD6] = Some(Gameboy::sub);
+    table[0xEE] = Some(Gameboy::xor);
 
     // for i in [0xD4,0xDC,0xC4,0xCD,0xCC] {
     //     table[i] = Gameboy::call;
     // }
 
-    //table[0xC6] = Gameboy::add;
+    table[0xC6] = Some(Gameboy::add);
     //table[0xE8] = Gameboy::add;
 
     // for i in [0xC2,0xC3,0xCA,0xD2,0xDA,0xE9] {
@@ -104,25 +174,45 @@ static DISPATCH: Lazy<[GameboyInstruction; 256]> = Lazy::new(|| {
     table
 });
 
+impl Default for Gameboy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[allow(dead_code)]
 #[allow(unused)]
 impl Gameboy {
     pub fn new() -> Self {
         Gameboy {
             running: true,
+            fault: None,
+            model: Model::Dmg,
+            cycles: 0,
             cpu: Gbz80::new(),
             memory: GbMemory::new(),
+            tracer: None,
+            break_on_ld_b_b: false,
+            ld_b_b_hit: false,
+            frame_overshoot: 0,
+            audio_frame: Vec::new(),
+            channel_audio_frames: Default::default(),
         }
     }
 
+    // Whether DISPATCH has a handler for `opcode`.
+    pub fn has_handler(opcode: u8) -> bool {
+        DISPATCH[opcode as usize].is_some()
+    }
+
     pub fn read_u8_increment_pc(&mut self) -> u8 {
         let val = self.memory.read_u8(self.cpu.program_counter);
-        self.cpu.program_counter += 1;
+        self.cpu.program_counter = self.cpu.program_counter.wrapping_add(1);
         val
     }
     pub fn read_u16_increment_pc(&mut self) -> u16 {
         let val = self.memory.read_u16(self.cpu.program_counter);
-        self.cpu.program_counter += 2;
+        self.cpu.program_counter = self.cpu.program_counter.wrapping_add(2);
         val
     }
 
@@ -135,11 +225,84 @@ impl Gameboy {
 
             self.memory.write_u8(address + i, byte);
 
-            i = i + 1;
+            i += 1;
         }
         i
     }
 
+    pub fn load_boot_rom<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        self.memory.load_boot_rom(std::fs::read(path)?);
+        Ok(())
+    }
+
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.memory.set_cgb(model == Model::Cgb);
+        self.memory
+            .apu
+            .set_high_pass_filter(HighPassFilter::for_model(model));
+    }
+
+    // Runs on the model the header asks for unless one is forced.
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge, model: Option<Model>) -> Model {
+        let model = model.unwrap_or(if cartridge.supports_cgb() {
+            Model::Cgb
+        } else {
+            Model::Dmg
+        });
+        self.set_model(model);
+        self.memory.cartridge = Some(cartridge);
+        model
+    }
+
+    pub fn load_cartridge<P: AsRef<Path>>(&mut self, path: P, model: Option<Model>) -> std::io::Result<Model> {
+        let cartridge = Cartridge::load(path)?;
+        Ok(self.insert_cartridge(cartridge, model))
+    }
+
+    // Puts the registers where the boot ROM leaves them. Games tell a CGB
+    // apart by A being 0x11 on entry.
+    pub fn skip_boot_rom(&mut self) {
+        match self.model {
+            Model::Dmg => {
+                self.cpu.set_af(0x01B0);
+                self.cpu.set_bc(0x0013);
+                self.cpu.set_de(0x00D8);
+                self.cpu.set_hl(0x014D);
+            }
+            Model::Cgb => {
+                self.cpu.set_af(0x1180);
+                self.cpu.set_bc(0x0000);
+                self.cpu.set_de(0xFF56);
+                self.cpu.set_hl(0x000D);
+            }
+        }
+        self.cpu.stack_pointer = 0xFFFE;
+        self.cpu.program_counter = 0x0100;
+
+        self.memory.write_u8(NR52, 0x80);
+        self.memory.write_u8(NR50, 0x77);
+        self.memory.write_u8(NR51, 0xF3);
+        self.memory.write_u8(LCDC, 0x91);
+        self.memory.write_u8(BGP, 0xFC);
+        self.memory.write_u8(OBP0, 0xFF);
+        self.memory.write_u8(OBP1, 0xFF);
+    }
+
+    pub fn save_screenshot<P: AsRef<Path>>(&self, path: P, format: ImageFormat) -> std::io::Result<()> {
+        screenshot::save(&self.memory.ppu, path, format)
+    }
+
+    pub fn press_button(&mut self, button: Button) {
+        if self.memory.joypad.press(button) {
+            self.memory.request_interrupt(INT_JOYPAD);
+        }
+    }
+
+    pub fn release_button(&mut self, button: Button) {
+        self.memory.joypad.release(button);
+    }
+
     pub fn ld_n_n(&self, opcode: u8) {
         println!("{}", opcode);
     }
@@ -150,27 +313,115 @@ impl Gameboy {
 
     pub fn ld_sp_u16(&mut self, program_counter: &mut u16, stack_pointer: &mut u16) {
         self.ld_16_16(self.memory.read_u16(*program_counter + 1), stack_pointer);
-        *program_counter = *program_counter + 3;
+        *program_counter += 3;
     }
 
+    // Stops with PC left on the instruction, so a debugger shows where.
     pub fn not_implemented(&mut self, opcode: u8) {
-        println!("instruction not implemented opcode {:0X}", opcode);
+        let address = self.cpu.program_counter.wrapping_sub(1);
+        self.fault = Some(Fault { opcode, address });
+        self.cpu.program_counter = address;
+        self.running = false;
     }
 
-    pub fn nop(&mut self, _opcode: u8) {
-        self.cpu.program_counter += 1;
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
     }
+
+    pub fn nop(&mut self, _opcode: u8) {}
     
+    // With KEY1 armed on the CGB, STOP switches CPU speed instead of stopping.
+    pub fn stop(&mut self, _opcode: u8) {
+        self.memory.switch_speed();
+        self.memory.write_u8(DIV, 0);
+    }
+
     pub fn halt(&mut self) {
         self.running = false;
     }
 
+    // Whether an LD B,B has run since the last call, with `break_on_ld_b_b` on.
+    pub fn take_ld_b_b_hit(&mut self) -> bool {
+        std::mem::take(&mut self.ld_b_b_hit)
+    }
+
+    pub(crate) fn hit_ld_b_b(&mut self) {
+        if self.break_on_ld_b_b {
+            self.ld_b_b_hit = true;
+        }
+    }
+
     pub fn opcode_dest_register(opcode: u8) -> u8 {
         opcode >> 3 & 0x07
     }
 
-    pub fn execute_next(&mut self) {
+    // Runs one instruction and returns the T-cycles it took.
+    pub fn execute_next(&mut self) -> u32 {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.log(&self.cpu, &self.memory);
+        }
+        self.memory.set_instruction_pc(Some(self.cpu.program_counter));
         let opcode = self.read_u8_increment_pc();
-        DISPATCH[opcode as usize](self, opcode);
+        match DISPATCH[opcode as usize] {
+            Some(handler) => handler(self, opcode),
+            None => self.not_implemented(opcode),
+        }
+
+        let mut cycles = CYCLES[opcode as usize] as u32;
+        self.memory.tick(cycles);
+
+        // VRAM DMA keeps the CPU off the bus while the rest of the system runs.
+        loop {
+            let stall = self.memory.take_dma_stall_cycles();
+            if stall == 0 {
+                break;
+            }
+            self.memory.tick(stall);
+            cycles += stall;
+        }
+        self.memory.set_instruction_pc(None);
+        self.cycles += cycles as u64;
+        cycles
+    }
+
+    // Runs a frame's worth of cycles and collects the audio produced meanwhile.
+    // Frames are counted at normal speed, so double speed runs twice the code.
+    pub fn run_frame(&mut self) {
+        self.run_frame_until(|_| false);
+    }
+
+    // The same, but checks `stop` before every instruction and ends the frame
+    // early once it returns true. Returns whether it did.
+    pub fn run_frame_until<F: FnMut(&Gameboy) -> bool>(&mut self, mut stop: F) -> bool {
+        let mut stopped = false;
+        let mut cycles = self.frame_overshoot;
+        while self.running && cycles < CYCLES_PER_FRAME {
+            if stop(self) {
+                stopped = true;
+                break;
+            }
+
+            let instruction_cycles = self.execute_next();
+            cycles += if self.memory.double_speed() {
+                instruction_cycles / 2
+            } else {
+                instruction_cycles
+            };
+        }
+        self.frame_overshoot = cycles.saturating_sub(CYCLES_PER_FRAME);
+
+        self.audio_frame = self.memory.apu.take_samples();
+        self.channel_audio_frames = self.memory.apu.take_channel_samples();
+        stopped
+    }
+
+    // Interleaved stereo samples from the last `run_frame`.
+    pub fn audio_frame(&self) -> &[f32] {
+        &self.audio_frame
+    }
+
+    // The same for channel 0..=3 alone; empty unless channel capture is on.
+    pub fn channel_audio_frame(&self, channel: usize) -> &[f32] {
+        &self.channel_audio_frames[channel]
     }
 }
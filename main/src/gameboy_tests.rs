@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use crate::cartridge::Cartridge;
+    use crate::gameboy::{Fault, Gameboy, Model};
+    use crate::memory::BOOT;
+    use crate::opcodes;
+    use proptest::prelude::*;
+    use proptest::sample::select;
+
+    // A ROM-only cartridge with `fill` in every byte apart from the header.
+    fn cartridge(fill: u8) -> Cartridge {
+        let mut rom = vec![fill; 0x8000];
+        rom[0x0143] = 0x00;
+        rom[0x0147] = 0x00;
+        rom[0x0149] = 0x00;
+        Cartridge::from_bytes(rom).unwrap()
+    }
+
+    #[test]
+    fn test_boot_rom_overlays_cartridge_until_disabled() {
+        let mut gameboy = Gameboy::new();
+        gameboy.insert_cartridge(cartridge(0x11), None);
+        gameboy.memory.load_boot_rom(vec![0x22; 0x100]);
+
+        assert_eq!(gameboy.memory.read_u8(0x0000), 0x22);
+        assert_eq!(gameboy.memory.read_u8(0x00FF), 0x22);
+        assert_eq!(gameboy.memory.read_u8(0x0100), 0x11);
+
+        gameboy.memory.write_u8(BOOT, 0x01);
+        assert!(!gameboy.memory.boot_rom_mapped());
+        assert_eq!(gameboy.memory.read_u8(0x0000), 0x11);
+    }
+
+    #[test]
+    fn test_cgb_boot_rom_skips_header() {
+        let mut gameboy = Gameboy::new();
+        gameboy.insert_cartridge(cartridge(0x11), Some(Model::Cgb));
+        gameboy.memory.load_boot_rom(vec![0x22; 0x900]);
+
+        assert_eq!(gameboy.memory.read_u8(0x0150), 0x11);
+        assert_eq!(gameboy.memory.read_u8(0x0200), 0x22);
+        assert_eq!(gameboy.memory.read_u8(0x08FF), 0x22);
+        assert_eq!(gameboy.memory.read_u8(0x0900), 0x11);
+    }
+
+    #[test]
+    fn test_run_frame_until_stops_before_instruction() {
+        let mut gameboy = Gameboy::new();
+        gameboy.insert_cartridge(cartridge(0x00), None);
+        gameboy.skip_boot_rom();
+
+        assert!(gameboy.run_frame_until(|gameboy| gameboy.cycles >= 8 * 4));
+        assert_eq!(gameboy.cycles, 8 * 4);
+
+        assert!(!gameboy.run_frame_until(|_| false));
+    }
+
+    #[test]
+    fn test_nop_is_one_byte() {
+        let mut gameboy = Gameboy::new();
+        gameboy.insert_cartridge(cartridge(0x00), None);
+        gameboy.cpu.program_counter = 0x0150;
+
+        assert_eq!(gameboy.execute_next(), 4);
+        assert_eq!(gameboy.cpu.program_counter, 0x0151);
+    }
+
+    #[test]
+    fn test_unimplemented_instruction_stops_with_fault() {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.set_flat_bus(true);
+        gameboy.cpu.program_counter = 0xC000;
+        gameboy.memory.write_u8(0xC000, 0x00);
+        gameboy.memory.write_u8(0xC001, 0xC3); // JP u16
+
+        gameboy.run_frame();
+
+        assert!(!gameboy.running);
+        assert_eq!(gameboy.fault(), Some(Fault { opcode: 0xC3, address: 0xC001 }));
+        assert_eq!(gameboy.cpu.program_counter, 0xC001);
+        assert_eq!(gameboy.fault().unwrap().to_string(), "opcode C3 at C001 isn't implemented");
+    }
+
+    #[test]
+    fn test_has_handler() {
+        assert!(Gameboy::has_handler(0x00));
+        assert!(Gameboy::has_handler(0x7F));
+        assert!(!Gameboy::has_handler(0x76)); // HALT
+        assert!(!Gameboy::has_handler(0xC3)); // JP u16
+    }
+
+    fn opcodes_with_handlers() -> Vec<u8> {
+        (0..=0xFF).filter(|&opcode| Gameboy::has_handler(opcode)).collect()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(4096))]
+
+        // Every opcode DISPATCH handles moves PC on by its length and takes
+        // the cycles dmgops.json gives, from any state. A branch may instead
+        // jump, taking the branch cycles.
+        #[test]
+        fn test_length_and_cycles_match_dmgops(
+            opcode in select(opcodes_with_handlers()),
+            operands: [u8; 2],
+            registers: [u8; 8],
+            pc: u16,
+            sp: u16,
+        ) {
+            let mut gameboy = Gameboy::new();
+            gameboy.memory.set_flat_bus(true);
+            let cpu = &mut gameboy.cpu;
+            [cpu.a, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l, cpu.f] = registers;
+            cpu.f &= 0xF0;
+            cpu.program_counter = pc;
+            cpu.stack_pointer = sp;
+            gameboy.memory.write_u8(pc, opcode);
+            gameboy.memory.write_u8(pc.wrapping_add(1), operands[0]);
+            gameboy.memory.write_u8(pc.wrapping_add(2), operands[1]);
+            let info = opcodes::lookup(opcode, operands[0]);
+
+            let cycles = gameboy.execute_next();
+            let advance = gameboy.cpu.program_counter.wrapping_sub(pc);
+
+            if cycles == info.t_cycles_no_branch {
+                if info.group != "control/br" || info.t_cycles_branch != info.t_cycles_no_branch {
+                    prop_assert_eq!(advance, info.length, "PC advance of {} ({:02X})", info.name, opcode);
+                }
+            } else {
+                prop_assert_eq!(cycles, info.t_cycles_branch, "cycles of {} ({:02X})", info.name, opcode);
+            }
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::gameboy::Gameboy;
+use crate::parse::parse_hex;
+use crate::watch::{WatchKind, Watchpoint};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+// A GDB remote serial protocol stub. GDB has no SM83 target, but its Z80 one
+// reads the registers in the same order, so gdb-multiarch works with
+//     set architecture z80
+//     target remote localhost:PORT
+// Registers are AF BC DE HL SP PC, 16 bits each and little-endian.
+pub const REGISTER_COUNT: usize = 6;
+
+// The longest packet we take or send, which GDB is told about.
+const PACKET_SIZE: usize = 0x1000;
+// As many bytes as fit in a reply to `m` once it's hex and framed. GDB asks
+// again for the rest.
+const MAX_READ: usize = (PACKET_SIZE - 4) / 2;
+
+// Instructions run between checks for a Ctrl-C from the client.
+const INTERRUPT_INTERVAL: u64 = 4096;
+
+const SIGINT: &str = "S02";
+const SIGILL: &str = "S04";
+const SIGTRAP: &str = "S05";
+
+pub struct GdbServer {
+    listener: TcpListener,
+    breakpoints: BTreeSet<u16>,
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl GdbServer {
+    // Listens on localhost only. Port 0 picks a free one, see `local_addr`.
+    pub fn bind(port: u16) -> std::io::Result<Self> {
+        Ok(GdbServer {
+            listener: TcpListener::bind(("127.0.0.1", port))?,
+            breakpoints: BTreeSet::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // Waits for a client and serves it until it detaches, kills the target or
+    // goes away. The emulator stays stopped while no command is running.
+    pub fn serve(&mut self, gameboy: &mut Gameboy) -> std::io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+        let mut connection = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+
+        while let Some(packet) = connection.read_packet()? {
+            match packet.as_str() {
+                "k" => break,
+                "D" => {
+                    connection.write_packet("OK")?;
+                    break;
+                }
+                _ => match self.handle(gameboy, &mut connection, &packet) {
+                    Ok(reply) => connection.write_packet(&reply)?,
+                    // The client went away while the emulator was running.
+                    Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+                    Err(err) => return Err(err),
+                },
+            }
+        }
+
+        // Watchpoints belong to the session.
+        gameboy.memory.clear_watchpoints();
+        Ok(())
+    }
+
+    fn handle(&mut self, gameboy: &mut Gameboy, connection: &mut Connection, packet: &str) -> std::io::Result<String> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+
+        let reply = match command {
+            "?" => SIGTRAP.to_string(),
+            "g" => read_registers(gameboy),
+            "G" => reply(write_registers(gameboy, args)),
+            "p" => parse_hex(args).and_then(|n| read_register(gameboy, n)).unwrap_or_else(error),
+            "P" => reply(write_register(gameboy, args)),
+            "m" => read_memory(gameboy, args).unwrap_or_else(error),
+            "M" => reply(write_memory(gameboy, args)),
+            "c" => {
+                set_pc(gameboy, args);
+                self.resume(gameboy, connection, false)?
+            }
+            "s" => {
+                set_pc(gameboy, args);
+                self.resume(gameboy, connection, true)?
+            }
+            "Z" => reply(self.set_point(gameboy, args, true)),
+            "z" => reply(self.set_point(gameboy, args, false)),
+            "H" => "OK".to_string(),
+            "q" if args.starts_with("Supported") => format!("PacketSize={:x}", PACKET_SIZE),
+            "q" if args == "Attached" => "1".to_string(),
+            // Anything else, including vCont and binary X writes, is answered
+            // as unsupported so GDB falls back to the packets above.
+            _ => String::new(),
+        };
+        Ok(reply)
+    }
+
+    // Runs one instruction for a step, otherwise until a breakpoint, a
+    // watchpoint or a Ctrl-C. A breakpoint at the starting PC is skipped.
+    fn resume(&mut self, gameboy: &mut Gameboy, connection: &mut Connection, step: bool) -> std::io::Result<String> {
+        let mut executed = 0;
+
+        loop {
+            if gameboy.fault().is_some() {
+                return Ok(SIGILL.to_string());
+            }
+            if !gameboy.running {
+                return Ok("W00".to_string());
+            }
+            if step && executed == 1 {
+                return Ok(SIGTRAP.to_string());
+            }
+            if executed > 0 {
+                if self.breakpoints.contains(&gameboy.cpu.program_counter) {
+                    return Ok(SIGTRAP.to_string());
+                }
+                if executed % INTERRUPT_INTERVAL == 0 && connection.interrupted()? {
+                    return Ok(SIGINT.to_string());
+                }
+            }
+
+            gameboy.execute_next();
+            executed += 1;
+
+            if let Some(hit) = gameboy.memory.take_watch_hits().first() {
+                let reason = match hit.kind {
+                    WatchKind::Read => "rwatch",
+                    _ => "watch",
+                };
+                return Ok(format!("T05{}:{:04x};", reason, hit.address));
+            }
+        }
+    }
+
+    // TYPE,ADDR,KIND. Types 0 and 1 are breakpoints, 2 write, 3 read and
+    // 4 access watchpoints. KIND is the length for watchpoints.
+    fn set_point(&mut self, gameboy: &mut Gameboy, args: &str, insert: bool) -> Result<(), String> {
+        let fields: Vec<&str> = args.split(',').collect();
+        let [kind, address, length] = fields[..] else {
+            return Err(format!("malformed breakpoint '{}'", args));
+        };
+        let address = parse_hex::<usize>(address)? as u16;
+
+        let kinds = match kind {
+            "0" | "1" => {
+                match insert {
+                    true => self.breakpoints.insert(address),
+                    false => self.breakpoints.remove(&address),
+                };
+                return Ok(());
+            }
+            "2" => vec![WatchKind::Write],
+            "3" => vec![WatchKind::Read],
+            "4" => vec![WatchKind::Read, WatchKind::Write],
+            _ => return Err(format!("unsupported breakpoint type {}", kind)),
+        };
+
+        let length = parse_hex::<usize>(length)?.max(1);
+        let end = address.saturating_add((length - 1).min(0xFFFF) as u16);
+        for kind in kinds {
+            let watchpoint = Watchpoint::new(address, end, kind);
+            if insert {
+                gameboy.memory.add_watchpoint(watchpoint);
+            } else if let Some(index) = gameboy.memory.watchpoints().iter().position(|w| *w == watchpoint) {
+                gameboy.memory.remove_watchpoint(index);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Connection {
+    // The payload of the next packet, acknowledging it. None once the client
+    // has disconnected.
+    fn read_packet(&mut self) -> std::io::Result<Option<String>> {
+        loop {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            // Acks, and Ctrl-Cs that arrive while we're already stopped.
+            if byte[0] != b'$' {
+                continue;
+            }
+
+            let mut data = Vec::new();
+            if self.reader.read_until(b'#', &mut data)? == 0 || data.pop() != Some(b'#') {
+                return Ok(None);
+            }
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+            if expected != Some(checksum_of(&data)) {
+                self.writer.write_all(b"-")?;
+                continue;
+            }
+            self.writer.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> std::io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.writer.write_all(packet.as_bytes())?;
+        self.writer.flush()
+    }
+
+    // Whether the client has sent a Ctrl-C, without waiting for one. An
+    // UnexpectedEof error if it has disconnected.
+    fn interrupted(&mut self) -> std::io::Result<bool> {
+        if self.reader.buffer().is_empty() {
+            self.reader.get_ref().set_nonblocking(true)?;
+            let filled = self.reader.fill_buf().map(|_| ());
+            self.reader.get_ref().set_nonblocking(false)?;
+            match filled {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(false),
+                other => other?,
+            }
+            if self.reader.buffer().is_empty() {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+        }
+
+        let interrupted = self.reader.buffer().first() == Some(&0x03);
+        if interrupted {
+            self.reader.consume(1);
+        }
+        Ok(interrupted)
+    }
+}
+
+pub fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn registers(gameboy: &Gameboy) -> [u16; REGISTER_COUNT] {
+    let cpu = &gameboy.cpu;
+    [cpu.af(), cpu.bc(), cpu.de(), cpu.hl(), cpu.stack_pointer, cpu.program_counter]
+}
+
+fn set_register(gameboy: &mut Gameboy, index: usize, value: u16) {
+    let cpu = &mut gameboy.cpu;
+    match index {
+        0 => cpu.set_af(value),
+        1 => cpu.set_bc(value),
+        2 => cpu.set_de(value),
+        3 => cpu.set_hl(value),
+        4 => cpu.stack_pointer = value,
+        _ => cpu.program_counter = value,
+    }
+}
+
+fn read_registers(gameboy: &Gameboy) -> String {
+    registers(gameboy).iter().map(|value| encode_u16(*value)).collect()
+}
+
+fn read_register(gameboy: &Gameboy, index: usize) -> Result<String, String> {
+    registers(gameboy)
+        .get(index)
+        .map(|value| encode_u16(*value))
+        .ok_or_else(|| format!("no register {}", index))
+}
+
+fn write_registers(gameboy: &mut Gameboy, args: &str) -> Result<(), String> {
+    let bytes = decode_hex(args)?;
+    if bytes.len() < REGISTER_COUNT * 2 {
+        return Err("too few registers".to_string());
+    }
+    for (index, value) in bytes.chunks_exact(2).take(REGISTER_COUNT).enumerate() {
+        set_register(gameboy, index, u16::from_le_bytes([value[0], value[1]]));
+    }
+    Ok(())
+}
+
+// N=VALUE, with VALUE little-endian like in `g`.
+fn write_register(gameboy: &mut Gameboy, args: &str) -> Result<(), String> {
+    let (index, value) = args.split_once('=').ok_or("expected N=VALUE")?;
+    let index = parse_hex(index)?;
+    let value = decode_hex(value)?;
+    if index >= REGISTER_COUNT || value.len() != 2 {
+        return Err(format!("can't write register {}", index));
+    }
+    set_register(gameboy, index, u16::from_le_bytes([value[0], value[1]]));
+    Ok(())
+}
+
+// ADDR,LEN
+fn read_memory(gameboy: &Gameboy, args: &str) -> Result<String, String> {
+    let (address, length) = args.split_once(',').ok_or("expected ADDR,LEN")?;
+    let address = parse_hex::<usize>(address)?;
+    let length = parse_hex::<usize>(length)?.min(MAX_READ);
+
+    let mut text = String::new();
+    for i in 0..length {
+        let byte = gameboy.memory.read_u8(address.wrapping_add(i) as u16);
+        write!(text, "{:02x}", byte).unwrap();
+    }
+    Ok(text)
+}
+
+// ADDR,LEN:BYTES. ROM is patched, the way GDB expects memory to change,
+// rather than the write going to the MBC.
+fn write_memory(gameboy: &mut Gameboy, args: &str) -> Result<(), String> {
+    let (range, bytes) = args.split_once(':').ok_or("expected ADDR,LEN:BYTES")?;
+    let (address, length) = range.split_once(',').ok_or("expected ADDR,LEN:BYTES")?;
+    let address = parse_hex::<usize>(address)?;
+    let bytes = decode_hex(bytes)?;
+    if bytes.len() != parse_hex::<usize>(length)? {
+        return Err("length doesn't match the data".to_string());
+    }
+    for (i, byte) in bytes.into_iter().enumerate() {
+        gameboy.memory.patch_u8(address.wrapping_add(i) as u16, byte);
+    }
+    Ok(())
+}
+
+// `c` and `s` may give an address to resume from.
+fn set_pc(gameboy: &mut Gameboy, args: &str) {
+    if let Ok(address) = parse_hex::<usize>(args) {
+        gameboy.cpu.program_counter = address as u16;
+    }
+}
+
+fn reply(result: Result<(), String>) -> String {
+    match result {
+        Ok(()) => "OK".to_string(),
+        Err(err) => error(err),
+    }
+}
+
+// GDB only shows the number, so the message is dropped.
+fn error(_: String) -> String {
+    "E01".to_string()
+}
+
+fn encode_u16(value: u16) -> String {
+    format!("{:02x}{:02x}", value as u8, (value >> 8) as u8)
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) {
+        return Err(format!("odd length hex '{}'", s));
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| format!("invalid hex '{}'", s))
+        })
+        .collect()
+}
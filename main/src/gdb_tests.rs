@@ -0,0 +1,199 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm;
+    use crate::cartridge::Cartridge;
+    use crate::gameboy::Gameboy;
+    use crate::gdb::*;
+    use crate::testing::gameboy_with_program;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    const PROGRAM: &str = "ld a, $12\nld b, $34\nld c, b\nld c, b";
+
+    // Sends one packet and returns the payload of the reply.
+    fn exchange(stream: &mut TcpStream, data: &str) -> String {
+        write!(stream, "${}#{:02x}", data, checksum_of(data.as_bytes())).unwrap();
+
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            match byte[0] {
+                b'+' if reply.is_empty() => continue,
+                b'#' => break,
+                _ => reply.push(byte[0]),
+            }
+        }
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum).unwrap();
+        stream.write_all(b"+").unwrap();
+
+        assert_eq!(reply.first(), Some(&b'$'));
+        let payload = String::from_utf8(reply[1..].to_vec()).unwrap();
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(checksum, checksum_of(payload.as_bytes()));
+        payload
+    }
+
+    // Serves `gameboy` to a client running `script`, returning what the
+    // script collected.
+    fn session<F>(gameboy: &mut Gameboy, script: F) -> Vec<String>
+    where
+        F: FnOnce(&mut TcpStream) -> Vec<String> + Send + 'static,
+    {
+        let mut server = GdbServer::bind(0).unwrap();
+        let addr = server.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let replies = script(&mut stream);
+            exchange(&mut stream, "D");
+            replies
+        });
+        server.serve(gameboy).unwrap();
+        client.join().unwrap()
+    }
+
+    #[test]
+    fn test_reads_and_writes_registers() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        gameboy.cpu.set_af(0x01B0);
+        gameboy.cpu.set_hl(0x014D);
+
+        let replies = session(&mut gameboy, |stream| {
+            vec![
+                exchange(stream, "?"),
+                exchange(stream, "g"),
+                exchange(stream, "p5"),
+                exchange(stream, "P1=3412"),
+                exchange(stream, "p9"),
+            ]
+        });
+
+        assert_eq!(replies[0], "S05");
+        assert_eq!(replies[1], "b001000000004d01feff00c0");
+        assert_eq!(replies[2], "00c0");
+        assert_eq!(replies[3], "OK");
+        assert_eq!(replies[4], "E01");
+        assert_eq!(gameboy.cpu.bc(), 0x1234);
+    }
+
+    #[test]
+    fn test_reads_and_writes_memory() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+
+        let replies = session(&mut gameboy, |stream| {
+            vec![exchange(stream, "mc000,4"), exchange(stream, "Mc100,3:aabbcc")]
+        });
+
+        assert_eq!(replies, ["3e120634", "OK"]);
+        assert_eq!(gameboy.memory.read_u8(0xC101), 0xBB);
+    }
+
+    #[test]
+    fn test_memory_reads_fit_in_a_packet() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+
+        let replies = session(&mut gameboy, |stream| vec![exchange(stream, "m0,ffffffff")]);
+
+        assert_eq!(replies[0].len(), 0xFFC);
+    }
+
+    #[test]
+    fn test_memory_writes_patch_rom() {
+        // MBC1, so a write_u8 to 2000 would select a bank instead.
+        let mut rom = vec![0; 0x10000];
+        rom[0x0147] = 0x01;
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        gameboy.insert_cartridge(Cartridge::from_bytes(rom).unwrap(), None);
+
+        let replies = session(&mut gameboy, |stream| vec![exchange(stream, "M2000,2:aabb")]);
+
+        assert_eq!(replies, ["OK"]);
+        assert_eq!(gameboy.memory.read_u8(0x2000), 0xAA);
+        assert_eq!(gameboy.memory.read_u8(0x2001), 0xBB);
+        assert_eq!(gameboy.memory.rom_bank(0x4000), Some(1));
+    }
+
+    #[test]
+    fn test_steps_and_stops_at_breakpoints() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+
+        let replies = session(&mut gameboy, |stream| {
+            vec![
+                exchange(stream, "s"),
+                exchange(stream, "p5"),
+                exchange(stream, "Z0,c004,1"),
+                exchange(stream, "c"),
+                exchange(stream, "p5"),
+                exchange(stream, "z0,c004,1"),
+            ]
+        });
+
+        assert_eq!(replies, ["S05", "02c0", "OK", "S05", "04c0", "OK"]);
+        assert_eq!(gameboy.cpu.a, 0x12);
+        assert_eq!(gameboy.cpu.b, 0x34);
+    }
+
+    #[test]
+    fn test_watchpoints_report_the_address() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        asm::patch(&mut gameboy, 0xC004, "ld [hl], a").unwrap();
+        gameboy.cpu.set_hl(0xC100);
+
+        let replies = session(&mut gameboy, |stream| {
+            vec![exchange(stream, "Z2,c100,1"), exchange(stream, "c"), exchange(stream, "z2,c100,1")]
+        });
+
+        assert_eq!(replies, ["OK", "T05watch:c100;", "OK"]);
+        assert_eq!(gameboy.memory.read_u8(0xC100), 0x12);
+        assert!(gameboy.memory.watchpoints().is_empty());
+    }
+
+    #[test]
+    fn test_long_watchpoints_reach_the_end_of_memory() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+        // Stops with SIGILL at the JP if the watchpoint doesn't reach C100.
+        asm::patch(&mut gameboy, 0xC004, "ld [hl], a\njp $C000").unwrap();
+        gameboy.cpu.set_hl(0xC100);
+
+        let replies = session(&mut gameboy, |stream| vec![exchange(stream, "Z2,c000,10001"), exchange(stream, "c")]);
+
+        assert_eq!(replies, ["OK", "T05watch:c100;"]);
+    }
+
+    #[test]
+    fn test_disconnecting_while_running_ends_the_session() {
+        let mut server = GdbServer::bind(0).unwrap();
+        let addr = server.local_addr().unwrap();
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            let mut gameboy = Gameboy::new();
+            // NOPs all the way round, so only the client going away stops it.
+            gameboy.memory.set_flat_bus(true);
+            done.send(server.serve(&mut gameboy).is_ok())
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "$c#{:02x}", checksum_of(b"c")).unwrap();
+        drop(stream);
+
+        assert_eq!(finished.recv_timeout(Duration::from_secs(10)), Ok(true));
+    }
+
+    #[test]
+    fn test_bad_checksums_are_refused() {
+        let mut gameboy = gameboy_with_program(PROGRAM);
+
+        let replies = session(&mut gameboy, |stream| {
+            stream.write_all(b"$g#00").unwrap();
+            let mut nak = [0];
+            stream.read_exact(&mut nak).unwrap();
+            vec![String::from_utf8(nak.to_vec()).unwrap(), exchange(stream, "qSupported:swbreak+")]
+        });
+
+        assert_eq!(replies, ["-", "PacketSize=1000"]);
+    }
+}
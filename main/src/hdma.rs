@@ -0,0 +1,108 @@
+pub const HDMA1: u16 = 0xFF51;
+pub const HDMA2: u16 = 0xFF52;
+pub const HDMA3: u16 = 0xFF53;
+pub const HDMA4: u16 = 0xFF54;
+pub const HDMA5: u16 = 0xFF55;
+
+const HDMA5_HBLANK: u8 = 1 << 7;
+
+pub const BLOCK_SIZE: u16 = 0x10;
+
+// A block takes 8 us whatever the speed, which is twice the CPU cycles in
+// double speed.
+const BLOCK_CYCLES: u32 = 32;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Transfer {
+    // Everything at once, with the CPU halted until it's done.
+    General,
+    // One block at the start of every HBlank.
+    HBlank,
+}
+
+// CGB VRAM DMA. The controller only tracks addresses and lengths; GbMemory
+// does the copying, since it owns both ends of the transfer.
+pub struct Hdma {
+    source: u16,
+    destination: u16,
+    // Blocks left minus one, as HDMA5 reports it. Wraps to 0x7F when done.
+    length: u8,
+    hblank_active: bool,
+}
+
+impl Default for Hdma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+        Hdma {
+            source: 0,
+            destination: 0x8000,
+            length: 0x7F,
+            hblank_active: false,
+        }
+    }
+
+    pub fn hblank_active(&self) -> bool {
+        self.hblank_active
+    }
+
+    pub fn blocks_remaining(&self) -> u32 {
+        self.length as u32 + 1
+    }
+
+    // Bit 7 clears while an HBlank transfer is running. A finished transfer
+    // reads 0xFF; a cancelled one keeps the blocks it had left.
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            HDMA5 if self.hblank_active => self.length,
+            HDMA5 => HDMA5_HBLANK | self.length,
+            _ => 0xFF,
+        }
+    }
+
+    // Returns the kind of transfer a write to HDMA5 started, if any.
+    pub fn write(&mut self, address: u16, value: u8) -> Option<Transfer> {
+        match address {
+            HDMA1 => self.source = (self.source & 0x00FF) | ((value as u16) << 8),
+            HDMA2 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            HDMA3 => self.destination = (self.destination & 0x00FF) | (((value & 0x1F) as u16) << 8) | 0x8000,
+            HDMA4 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+            HDMA5 => {
+                if self.hblank_active && value & HDMA5_HBLANK == 0 {
+                    self.hblank_active = false;
+                    return None;
+                }
+                self.length = value & 0x7F;
+                if value & HDMA5_HBLANK != 0 {
+                    self.hblank_active = true;
+                    return Some(Transfer::HBlank);
+                }
+                return Some(Transfer::General);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // Source and destination of the next block. Moves the transfer on by one
+    // block and ends it after the last.
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, self.destination);
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = 0x8000 | (self.destination.wrapping_add(BLOCK_SIZE) & 0x1FF0);
+        self.length = self.length.wrapping_sub(1) & 0x7F;
+        if self.length == 0x7F {
+            self.hblank_active = false;
+        }
+        block
+    }
+
+    // CPU cycles the bus is held for each block.
+    pub fn block_cycles(double_speed: bool) -> u32 {
+        if double_speed { BLOCK_CYCLES * 2 } else { BLOCK_CYCLES }
+    }
+}
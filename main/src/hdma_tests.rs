@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::{Gameboy, Model};
+    use crate::hdma::*;
+    use crate::memory::KEY1;
+    use crate::ppu::{LCDC, VBK};
+
+    fn cgb_gameboy() -> Gameboy {
+        let mut gameboy = Gameboy::new();
+        gameboy.set_model(Model::Cgb);
+        for i in 0..0x100 {
+            gameboy.memory.write_u8(0xC000 + i, i as u8);
+        }
+        gameboy
+    }
+
+    // Copies from 0xC000 to 0x8800.
+    fn set_addresses(gameboy: &mut Gameboy) {
+        gameboy.memory.write_u8(HDMA1, 0xC0);
+        gameboy.memory.write_u8(HDMA2, 0x00);
+        gameboy.memory.write_u8(HDMA3, 0x08);
+        gameboy.memory.write_u8(HDMA4, 0x00);
+    }
+
+    // Runs NOPs from the top of memory until `cycles` have passed.
+    fn run(gameboy: &mut Gameboy, cycles: u32) {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            gameboy.cpu.program_counter = 0;
+            elapsed += gameboy.execute_next();
+        }
+    }
+
+    #[test]
+    fn test_general_dma_copies_everything_and_halts_cpu() {
+        let mut gameboy = cgb_gameboy();
+        set_addresses(&mut gameboy);
+
+        // LD (HL),A with HL pointing at HDMA5 starts a 4 block transfer
+        gameboy.cpu.set_hl(HDMA5);
+        gameboy.cpu.set_af(0x0300);
+        gameboy.memory.write_u8(0x0000, 0x77);
+        let cycles = gameboy.execute_next();
+
+        assert_eq!(cycles, 8 + 4 * 32);
+        assert_eq!(gameboy.memory.read_u8(HDMA5), 0xFF);
+        for i in 0..0x40 {
+            assert_eq!(gameboy.memory.read_u8(0x8800 + i), i as u8);
+        }
+        assert_eq!(gameboy.memory.read_u8(0x8840), 0x00);
+    }
+
+    #[test]
+    fn test_general_dma_takes_twice_the_cycles_in_double_speed() {
+        let mut gameboy = cgb_gameboy();
+        gameboy.memory.write_u8(KEY1, 0x01);
+        gameboy.memory.switch_speed();
+        set_addresses(&mut gameboy);
+
+        gameboy.cpu.set_hl(HDMA5);
+        gameboy.cpu.set_af(0x0000);
+        gameboy.memory.write_u8(0x0000, 0x77);
+
+        assert_eq!(gameboy.execute_next(), 8 + 64);
+    }
+
+    #[test]
+    fn test_general_dma_writes_selected_vram_bank() {
+        let mut gameboy = cgb_gameboy();
+        gameboy.memory.write_u8(VBK, 1);
+        set_addresses(&mut gameboy);
+        gameboy.memory.write_u8(HDMA5, 0x00);
+
+        assert_eq!(gameboy.memory.read_u8(0x880F), 0x0F);
+        gameboy.memory.write_u8(VBK, 0);
+        assert_eq!(gameboy.memory.read_u8(0x880F), 0x00);
+    }
+
+    #[test]
+    fn test_hblank_dma_copies_one_block_per_line() {
+        let mut gameboy = cgb_gameboy();
+        gameboy.memory.write_u8(LCDC, 0x91);
+        set_addresses(&mut gameboy);
+        gameboy.memory.write_u8(HDMA5, 0x80 | 0x02);
+
+        assert_eq!(gameboy.memory.read_u8(HDMA5), 0x02);
+        assert_eq!(gameboy.memory.read_u8(0x8800), 0x00);
+
+        // Into the first HBlank
+        run(&mut gameboy, 260);
+        assert_eq!(gameboy.memory.read_u8(HDMA5), 0x01);
+        assert_eq!(gameboy.memory.read_u8(0x880F), 0x0F);
+        assert_eq!(gameboy.memory.read_u8(0x8810), 0x00);
+
+        run(&mut gameboy, 2 * 456);
+        assert_eq!(gameboy.memory.read_u8(HDMA5), 0xFF);
+        assert_eq!(gameboy.memory.read_u8(0x882F), 0x2F);
+        assert_eq!(gameboy.memory.read_u8(0x8830), 0x00);
+    }
+
+    #[test]
+    fn test_hblank_dma_cancel_reports_remaining_length() {
+        let mut gameboy = cgb_gameboy();
+        gameboy.memory.write_u8(LCDC, 0x91);
+        set_addresses(&mut gameboy);
+        gameboy.memory.write_u8(HDMA5, 0x80 | 0x07);
+
+        run(&mut gameboy, 260);
+        gameboy.memory.write_u8(HDMA5, 0x00);
+        assert_eq!(gameboy.memory.read_u8(HDMA5), 0x86);
+
+        run(&mut gameboy, 456);
+        assert_eq!(gameboy.memory.read_u8(HDMA5), 0x86);
+        assert_eq!(gameboy.memory.read_u8(0x8810), 0x00);
+    }
+
+    #[test]
+    fn test_hdma_registers_absent_on_dmg() {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.write_u8(0xC000, 0x12);
+        set_addresses(&mut gameboy);
+        gameboy.memory.write_u8(HDMA5, 0x00);
+
+        assert_eq!(gameboy.memory.read_u8(0x8800), 0x00);
+    }
+}
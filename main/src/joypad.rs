@@ -0,0 +1,81 @@
+pub const P1: u16 = 0xFF00;
+
+const SELECT_DIRECTIONS: u8 = 1 << 4;
+const SELECT_BUTTONS: u8 = 1 << 5;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    // Bit in the pressed mask; the low nibble is the d-pad, the high one the
+    // buttons, each in the order P1 reports them.
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+// P1 selects the d-pad and/or the buttons with bits 4 and 5 (active low) and
+// reads the selected lines back in bits 0-3, where 0 means pressed.
+pub struct Joypad {
+    pressed: u8,
+    select: u8,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            pressed: 0,
+            select: SELECT_DIRECTIONS | SELECT_BUTTONS,
+        }
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button.mask() != 0
+    }
+
+    // Returns true when a selected line went low and the joypad interrupt
+    // should fire.
+    pub fn press(&mut self, button: Button) -> bool {
+        let before = self.lines();
+        self.pressed |= button.mask();
+        before & !self.lines() != 0
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.pressed &= !button.mask();
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | self.lines()
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.select = value & (SELECT_DIRECTIONS | SELECT_BUTTONS);
+    }
+
+    fn lines(&self) -> u8 {
+        let mut low = 0;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            low |= self.pressed & 0x0F;
+        }
+        if self.select & SELECT_BUTTONS == 0 {
+            low |= self.pressed >> 4;
+        }
+        !low & 0x0F
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::Gameboy;
+    use crate::joypad::*;
+    use crate::memory::{INT_JOYPAD, INTERRUPT_FLAG};
+
+    #[test]
+    fn test_nothing_selected_reads_high() {
+        let mut joypad = Joypad::new();
+        joypad.press(Button::A);
+        joypad.press(Button::Down);
+
+        assert_eq!(joypad.read(), 0xFF);
+    }
+
+    #[test]
+    fn test_selected_group_reads_pressed_low() {
+        let mut joypad = Joypad::new();
+        joypad.press(Button::Start);
+        joypad.press(Button::Left);
+
+        joypad.write(0x20);
+        assert_eq!(joypad.read(), 0xE0 | 0x0D);
+
+        joypad.write(0x10);
+        assert_eq!(joypad.read(), 0xD0 | 0x07);
+
+        joypad.release(Button::Start);
+        assert_eq!(joypad.read(), 0xDF);
+        assert!(joypad.is_pressed(Button::Left));
+    }
+
+    #[test]
+    fn test_press_interrupts_only_when_selected() {
+        let mut gameboy = Gameboy::new();
+
+        gameboy.memory.write_u8(P1, 0x20);
+        gameboy.press_button(Button::A);
+        assert_eq!(gameboy.memory.read_u8(INTERRUPT_FLAG) & INT_JOYPAD, 0);
+
+        gameboy.press_button(Button::Up);
+        assert_eq!(gameboy.memory.read_u8(INTERRUPT_FLAG) & INT_JOYPAD, INT_JOYPAD);
+        assert_eq!(gameboy.memory.read_u8(P1), 0xEB);
+    }
+}
@@ -1,4 +1,55 @@
+pub mod apu;
+mod apu_tests;
+pub mod asm;
+mod asm_tests;
+pub mod blargg;
+mod blargg_tests;
+pub mod blip;
+pub mod cartridge;
 pub mod cpu;
+pub mod debugger;
+mod debugger_tests;
+pub mod disasm;
+mod disasm_tests;
 pub mod gameboy;
+mod gameboy_tests;
+pub mod gdb;
+mod gdb_tests;
+pub mod hdma;
+mod hdma_tests;
+pub mod joypad;
+mod joypad_tests;
 pub mod memory;
+pub mod mooneye;
+mod mooneye_tests;
+pub mod opcodes;
+mod opcodes_tests;
 mod ops;
+pub mod palette;
+mod palette_tests;
+pub mod parse;
+mod parse_tests;
+pub mod ppu;
+mod ppu_tests;
+pub mod printer;
+mod printer_tests;
+pub mod recorder;
+mod recorder_tests;
+pub mod rgbds;
+mod rgbds_tests;
+pub mod screenshot;
+mod screenshot_tests;
+pub mod serial;
+pub mod singlestep;
+mod singlestep_tests;
+pub mod terminal;
+mod terminal_tests;
+#[cfg(test)]
+mod testing;
+pub mod timer;
+pub mod trace;
+mod trace_tests;
+pub mod watch;
+mod watch_tests;
+pub mod wav;
+mod wav_tests;
@@ -1,26 +1,449 @@
+use clap::{Parser, Subcommand, ValueEnum};
 use num_enum::FromPrimitive;
-use main::gameboy::Gameboy;
+use main::apu::CPU_CLOCK_HZ;
+use main::debugger::Debugger;
+use main::disasm;
+use main::gameboy::{Gameboy, Model};
+use main::gdb::GdbServer;
+use main::mooneye::{self, Outcome};
+use main::palette::{ColourCorrection, DmgPalette};
+use main::parse::{parse_address, parse_hex};
+use main::recorder::VideoRecorder;
+use main::rgbds;
+use main::screenshot::{self, ImageFormat};
+use main::singlestep;
+use main::terminal::{Hotkey, Terminal};
+use main::trace::{self, TraceFilter, Tracer};
+use main::watch::Watchpoint;
+use main::wav::AudioCapture;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
 
 #[derive(Debug, Eq, PartialEq, FromPrimitive)]
 #[repr(u8)]
 pub enum Opcode {
     Nop = 0x00,
     LdSpU16 = 0x31,
-    XorAA = 0xAf,
+    XorAA = 0xAF,
     #[num_enum(default)]
     Undefined,
 }
 
-fn main() {
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ModelArg {
+    /// Pick from the cartridge header's CGB flag
+    Auto,
+    Dmg,
+    Cgb,
+}
+
+#[derive(Parser)]
+#[command(
+    about = "Runs a Game Boy ROM, headless or in the terminal",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Cartridge ROM to run
+    #[arg(required = true)]
+    rom: Option<PathBuf>,
+
+    /// Boot ROM to run first. Without one the CPU starts at 0x0100 in the
+    /// state the boot ROM would have left it
+    #[arg(long, value_name = "FILE")]
+    boot_rom: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = ModelArg::Auto)]
+    model: ModelArg,
+
+    /// Stop after this many frames
+    #[arg(long, value_name = "N")]
+    frames: Option<u64>,
+
+    /// Stop after this many CPU cycles
+    #[arg(long, value_name = "N")]
+    cycles: Option<u64>,
+
+    /// Stop when the program counter reaches ADDR (hex, e.g. 0x0150 or $150)
+    #[arg(long, value_name = "ADDR", value_parser = parse_address)]
+    until_pc: Option<u16>,
+
+    /// Stop when memory is accessed: read|write|change:ADDR[-END] or
+    /// write:ADDR=XX, in hex. May be given more than once
+    #[arg(long, value_name = "SPEC")]
+    watch: Vec<Watchpoint>,
+
+    /// Save the last frame when the run ends, as PNG or PPM by extension
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["debug", "gdb"])]
+    screenshot: Option<PathBuf>,
+
+    /// DMG shades: grey, green, pocket or four RRGGBB colours
+    #[arg(long, default_value = "grey")]
+    palette: DmgPalette,
+
+    /// CGB colour conversion: raw, lcd, gamma or gamma:EXPONENT
+    #[arg(long, default_value = "raw")]
+    colour_correction: ColourCorrection,
+
+    /// Draw the screen in the terminal and play with the keyboard: arrows,
+    /// X (A), Z (B), Enter (Start), Backspace (Select), Esc to quit. F12 or P
+    /// saves a screenshot_NNNN.png in the current directory
+    #[arg(long)]
+    terminal: bool,
+
+    /// Start in the interactive debugger instead of running freely
+    #[arg(long, conflicts_with = "terminal")]
+    debug: bool,
+
+    /// Wait for a GDB remote connection on this localhost port and let it
+    /// drive the emulator. Use `set architecture z80` in gdb-multiarch
+    #[arg(long, value_name = "PORT", conflicts_with_all = ["terminal", "debug"])]
+    gdb: Option<u16>,
+
+    /// Log the CPU state before each instruction to FILE, in Gameboy Doctor
+    /// format
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Only trace PCs in this range, e.g. 0150-01FF
+    #[arg(long, value_name = "START-END", value_parser = parse_range, requires = "trace")]
+    trace_pc: Option<(u16, u16)>,
+
+    /// Only trace code running from this ROM bank
+    #[arg(long, value_name = "N", requires = "trace")]
+    trace_bank: Option<usize>,
+
+    /// Start tracing after this many instructions
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "trace")]
+    trace_skip: u64,
+
+    /// Stop tracing after this many lines
+    #[arg(long, value_name = "N", requires = "trace")]
+    trace_limit: Option<u64>,
+
+    /// Record every frame to a video, as Y4M or GIF by extension
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["debug", "gdb"])]
+    record: Option<PathBuf>,
+
+    /// Frames to drop between each recorded one
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "record")]
+    record_skip: u32,
+
+    /// Record the audio output to a 16-bit PCM WAV file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["debug", "gdb"])]
+    wav: Option<PathBuf>,
+
+    /// Also write each sound channel to its own WAV file next to --wav
+    #[arg(long, requires = "wav")]
+    wav_per_channel: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the instructions in part of a ROM file
+    Disasm {
+        rom: PathBuf,
+
+        /// File offset to start from, in hex. Banks past the first are shown
+        /// at 4000-7FFF as the CPU would see them
+        #[arg(long, value_name = "OFFSET", default_value = "0", value_parser = parse_offset)]
+        start: usize,
+
+        /// File offset to stop before, in hex. Defaults to the end of the ROM
+        #[arg(long, value_name = "OFFSET", value_parser = parse_offset)]
+        end: Option<usize>,
+
+        /// Follow the code from the reset and interrupt vectors and the entry
+        /// point, and print the whole ROM as RGBDS source with labels
+        #[arg(long, conflicts_with_all = ["start", "end"])]
+        rgbds: bool,
+    },
+
+    /// Run a ROM from the entry point and compare its trace with a
+    /// reference log in Gameboy Doctor format, stopping at the first line
+    /// that differs
+    TraceDiff {
+        rom: PathBuf,
+
+        reference: PathBuf,
+
+        /// Matching lines to show before the divergence
+        #[arg(long, value_name = "LINES", default_value_t = 5)]
+        context: usize,
+    },
+
+    /// Run every DMG test ROM under a directory of Mooneye tests, such as
+    /// the suite's acceptance/, and print whether each passed
+    Mooneye {
+        dir: PathBuf,
+
+        /// Emulated seconds each test gets before it counts as timed out
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        seconds: u64,
+    },
+
+    /// Run a directory of SingleStepTests sm83 vectors, such as the suite's
+    /// v1/, and print how many cases of each opcode passed
+    Singlestep { dir: PathBuf },
+}
+
+fn parse_offset(s: &str) -> Result<usize, String> {
+    parse_hex(s).map_err(|_| format!("invalid offset '{}'", s))
+}
+
+fn parse_range(s: &str) -> Result<(u16, u16), String> {
+    let (start, end) = s.split_once('-').ok_or_else(|| format!("expected START-END, got '{}'", s))?;
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+    if end < start {
+        return Err(format!("range '{}' ends before it starts", s));
+    }
+    Ok((start, end))
+}
+
+// The first screenshot_NNNN.png not already taken.
+fn next_screenshot_path() -> PathBuf {
+    (1..)
+        .map(|n| PathBuf::from(format!("screenshot_{:04}.png", n)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let result = match &args.command {
+        Some(Command::Disasm { rom, start, end, rgbds }) => print_disassembly(rom, *start, *end, *rgbds),
+        Some(Command::TraceDiff { rom, reference, context }) => diff_trace(rom, reference, *context),
+        Some(Command::Mooneye { dir, seconds }) => run_mooneye(dir, *seconds),
+        Some(Command::Singlestep { dir }) => run_singlestep(dir),
+        None => run(&args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn finish_trace(gameboy: &mut Gameboy) -> Result<(), String> {
+    match gameboy.tracer.take() {
+        Some(tracer) => tracer.finish().map_err(|err| format!("trace: {}", err)),
+        None => Ok(()),
+    }
+}
+
+// An unimplemented instruction ends the run as an error.
+fn check_fault(gameboy: &Gameboy) -> Result<(), String> {
+    match gameboy.fault() {
+        Some(fault) => Err(fault.to_string()),
+        None => Ok(()),
+    }
+}
+
+fn print_disassembly(path: &Path, start: usize, end: Option<usize>, rgbds: bool) -> Result<(), String> {
+    let rom = std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if rgbds {
+        print!("{}", rgbds::source(&rom));
+        return Ok(());
+    }
+    if start >= rom.len() {
+        return Err(format!("{}: offset {:X} is past the end of the ROM", path.display(), start));
+    }
+    print!("{}", disasm::listing(&rom, start, end.unwrap_or(rom.len())));
+    Ok(())
+}
+
+// Fails unless every test passed, after printing the table.
+fn run_mooneye(dir: &Path, seconds: u64) -> Result<(), String> {
+    let results = mooneye::run_dir(dir, seconds * u64::from(CPU_CLOCK_HZ))?;
+    if results.is_empty() {
+        return Err(format!("{}: no DMG tests", dir.display()));
+    }
+    print!("{}", mooneye::table(&results));
+    let failed = results.iter().filter(|(_, outcome)| *outcome != Outcome::Passed).count();
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} tests didn't pass", failed, results.len())),
+    }
+}
+
+// Fails unless every opcode passed, after printing the table.
+fn run_singlestep(dir: &Path) -> Result<(), String> {
+    let reports = singlestep::run_dir(dir)?;
+    if reports.is_empty() {
+        return Err(format!("{}: no vectors", dir.display()));
+    }
+    print!("{}", singlestep::table(&reports));
+    let failed = reports.iter().filter(|report| !report.all_passed()).count();
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} opcodes didn't pass", failed, reports.len())),
+    }
+}
+
+fn diff_trace(rom: &Path, reference: &Path, context: usize) -> Result<(), String> {
     let mut gameboy = Gameboy::new();
-    let _rom_size = gameboy.load_rom(0x00, "./dmg_boot.bin");
-    //let video_memory: [u8; 8*1024] = [0; 8*1024];
+    gameboy
+        .load_cartridge(rom, None)
+        .map_err(|err| format!("{}: {}", rom.display(), err))?;
+    gameboy.skip_boot_rom();
+
+    let file = File::open(reference).map_err(|err| format!("{}: {}", reference.display(), err))?;
+    match trace::diff(&mut gameboy, BufReader::new(file), context) {
+        Ok(Ok(lines)) => {
+            println!("all {} lines match", lines);
+            Ok(())
+        }
+        Ok(Err(divergence)) => Err(divergence.to_string()),
+        Err(err) => Err(format!("{}: {}", reference.display(), err)),
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let rom = args.rom.as_ref().expect("clap requires a ROM without a subcommand");
+    let mut gameboy = Gameboy::new();
+
+    let model = match args.model {
+        ModelArg::Auto => None,
+        ModelArg::Dmg => Some(Model::Dmg),
+        ModelArg::Cgb => Some(Model::Cgb),
+    };
+    gameboy
+        .load_cartridge(rom, model)
+        .map_err(|err| format!("{}: {}", rom.display(), err))?;
+
+    match &args.boot_rom {
+        Some(path) => gameboy
+            .load_boot_rom(path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?,
+        None => gameboy.skip_boot_rom(),
+    }
+
+    for watchpoint in &args.watch {
+        gameboy.memory.add_watchpoint(*watchpoint);
+    }
+
+    if let Some(path) = &args.trace {
+        let filter = TraceFilter {
+            pc: args.trace_pc,
+            bank: args.trace_bank,
+            skip: args.trace_skip,
+            limit: args.trace_limit,
+        };
+        gameboy.tracer = Some(Tracer::create(path, filter).map_err(|err| format!("{}: {}", path.display(), err))?);
+    }
 
-    loop {
-        if gameboy.running {
-            gameboy.execute_next();
-        } else {
+    gameboy.memory.ppu.set_dmg_palette(args.palette);
+    gameboy.memory.ppu.set_colour_correction(args.colour_correction);
+
+    let mut audio_capture = match &args.wav {
+        Some(path) => Some(
+            AudioCapture::create(&mut gameboy, path, args.wav_per_channel)
+                .map_err(|err| format!("{}: {}", path.display(), err))?,
+        ),
+        None => None,
+    };
+
+    if args.debug {
+        let mut debugger = Debugger::new();
+        let interrupted = debugger.interrupt_flag();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::Relaxed)).map_err(|err| err.to_string())?;
+        let stdin = std::io::stdin();
+        debugger
+            .run(&mut gameboy, stdin.lock(), std::io::stdout())
+            .map_err(|err| err.to_string())?;
+        finish_trace(&mut gameboy)?;
+        return check_fault(&gameboy);
+    }
+
+    if let Some(port) = args.gdb {
+        let mut server = GdbServer::bind(port).map_err(|err| format!("port {}: {}", port, err))?;
+        let addr = server.local_addr().map_err(|err| err.to_string())?;
+        eprintln!("waiting for GDB on {}", addr);
+        server.serve(&mut gameboy).map_err(|err| err.to_string())?;
+        finish_trace(&mut gameboy)?;
+        return check_fault(&gameboy);
+    }
+
+    let mut recorder = match &args.record {
+        Some(path) => Some(VideoRecorder::create_by_extension(path, args.record_skip).map_err(|err| err.to_string())?),
+        None => None,
+    };
+
+    let mut terminal = if args.terminal {
+        Some(Terminal::new().map_err(|err| err.to_string())?)
+    } else {
+        None
+    };
+
+    let mut frames = 0;
+    let mut reached_pc = false;
+    while gameboy.running && args.frames.is_none_or(|limit| frames < limit) {
+        if let Some(terminal) = terminal.as_mut() {
+            let hotkeys = terminal.poll_input(&mut gameboy).map_err(|err| err.to_string())?;
+            if hotkeys.contains(&Hotkey::Quit) {
+                break;
+            }
+            if hotkeys.contains(&Hotkey::Screenshot) {
+                gameboy
+                    .save_screenshot(next_screenshot_path(), ImageFormat::Png)
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+
+        let stopped = gameboy.run_frame_until(|gameboy| {
+            args.until_pc == Some(gameboy.cpu.program_counter)
+                || args.cycles.is_some_and(|limit| gameboy.cycles >= limit)
+                || gameboy.memory.has_watch_hits()
+        });
+        frames += 1;
+
+        if let Some(capture) = audio_capture.as_mut() {
+            capture.write_frame(&gameboy).map_err(|err| err.to_string())?;
+        }
+
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.write_frame(&gameboy).map_err(|err| err.to_string())?;
+        }
+
+        if let Some(terminal) = terminal.as_mut() {
+            terminal.draw(&gameboy).map_err(|err| err.to_string())?;
+            terminal.wait_for_next_frame();
+        }
+
+        for hit in gameboy.memory.take_watch_hits() {
+            println!("watchpoint: {}", hit);
+        }
+
+        if stopped {
+            reached_pc = args.until_pc == Some(gameboy.cpu.program_counter);
             break;
         }
     }
+
+    drop(terminal);
+    finish_trace(&mut gameboy)?;
+
+    if let Some(recorder) = recorder {
+        recorder.finish().map_err(|err| err.to_string())?;
+    }
+
+    if let Some(path) = &args.screenshot {
+        screenshot::save_by_extension(&gameboy.memory.ppu, path).map_err(|err| err.to_string())?;
+    }
+
+    check_fault(&gameboy)?;
+    match args.until_pc {
+        Some(address) if !reached_pc => Err(format!("stopped before PC reached {:04X}", address)),
+        _ => Ok(()),
+    }
 }
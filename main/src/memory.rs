@@ -1,32 +1,379 @@
-use byteorder::{BigEndian, ByteOrder};
+use crate::apu::Apu;
+use crate::cartridge::Cartridge;
+use crate::hdma::{BLOCK_SIZE, HDMA1, HDMA5, Hdma, Transfer};
+use crate::joypad::{Joypad, P1};
+use crate::ppu::Ppu;
+use crate::serial::Serial;
+use crate::timer::Timer;
+use crate::watch::{WatchHit, Watchpoint};
+use std::cell::RefCell;
+
+pub const INTERRUPT_FLAG: u16 = 0xFF0F;
+pub const DMA: u16 = 0xFF46;
+pub const KEY1: u16 = 0xFF4D;
+pub const BOOT: u16 = 0xFF50;
+pub const SVBK: u16 = 0xFF70;
+
+pub const INT_VBLANK: u8 = 1 << 0;
+pub const INT_STAT: u8 = 1 << 1;
+pub const INT_TIMER: u8 = 1 << 2;
+pub const INT_SERIAL: u8 = 1 << 3;
+pub const INT_JOYPAD: u8 = 1 << 4;
+
+const WRAM_BANK_SIZE: usize = 0x1000;
+
+// A CPU read or write, as logged on the flat bus.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BusAccess {
+    Read(u16, u8),
+    Write(u16, u8),
+}
 
 pub struct GbMemory {
-    memory: [u8; 8 * 1024],
+    memory: Box<[u8]>,
+    wram: Box<[u8]>,
+    wram_bank: u8,
+    cgb: bool,
+    double_speed: bool,
+    speed_switch_armed: bool,
+    dma_stall_cycles: u32,
+    boot_rom: Option<Box<[u8]>>,
+    watchpoints: Vec<Watchpoint>,
+    // Reads take &self, so hits are collected through a RefCell.
+    watch_hits: RefCell<Vec<WatchHit>>,
+    // Set while an instruction runs; accesses from anywhere else aren't watched.
+    instruction_pc: Option<u16>,
+    // With the flat bus on, all 64KB is plain RAM, nothing ticks and every
+    // access is logged. CPU test vectors are written against such a bus.
+    flat: bool,
+    bus_log: RefCell<Vec<BusAccess>>,
+    pub cartridge: Option<Cartridge>,
+    pub joypad: Joypad,
+    pub serial: Serial,
+    pub timer: Timer,
+    pub apu: Apu,
+    pub ppu: Ppu,
+    pub hdma: Hdma,
+}
+
+impl Default for GbMemory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GbMemory {
     pub fn new() -> Self {
         GbMemory {
-            memory: [0; 8 * 1024],
+            memory: vec![0; 64 * 1024].into_boxed_slice(),
+            wram: vec![0; 8 * WRAM_BANK_SIZE].into_boxed_slice(),
+            wram_bank: 1,
+            cgb: false,
+            double_speed: false,
+            speed_switch_armed: false,
+            dma_stall_cycles: 0,
+            boot_rom: None,
+            watchpoints: Vec::new(),
+            watch_hits: RefCell::new(Vec::new()),
+            instruction_pc: None,
+            flat: false,
+            bus_log: RefCell::new(Vec::new()),
+            cartridge: None,
+            joypad: Joypad::new(),
+            serial: Serial::new(),
+            timer: Timer::new(),
+            apu: Apu::new(),
+            ppu: Ppu::new(),
+            hdma: Hdma::new(),
         }
     }
-    
+
+    pub fn set_cgb(&mut self, cgb: bool) {
+        self.cgb = cgb;
+        self.ppu.set_cgb(cgb);
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    // Called by STOP. Returns true if KEY1 was armed and the speed changed.
+    pub fn switch_speed(&mut self) -> bool {
+        if !self.cgb || !self.speed_switch_armed {
+            return false;
+        }
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        self.timer.set_double_speed(self.double_speed);
+        true
+    }
+
+    // Maps a boot ROM over the cartridge until the program writes to BOOT. A
+    // CGB boot ROM is 2KB and leaves the header at 0x0100-0x01FF visible.
+    pub fn load_boot_rom(&mut self, rom: Vec<u8>) {
+        self.boot_rom = Some(rom.into_boxed_slice());
+    }
+
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    // CPU cycles VRAM DMA has held the bus for since the last call.
+    pub fn take_dma_stall_cycles(&mut self) -> u32 {
+        std::mem::take(&mut self.dma_stall_cycles)
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        (index < self.watchpoints.len()).then(|| self.watchpoints.remove(index))
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // Accesses made from here on are reported as coming from the instruction
+    // at `pc`, until it's cleared with None.
+    pub fn set_instruction_pc(&mut self, pc: Option<u16>) {
+        self.instruction_pc = pc;
+    }
+
+    pub fn has_watch_hits(&self) -> bool {
+        !self.watch_hits.borrow().is_empty()
+    }
+
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        self.watch_hits.take()
+    }
+
+    pub fn set_flat_bus(&mut self, flat: bool) {
+        self.flat = flat;
+    }
+
+    // Accesses since the last call, oldest first. Only the flat bus logs them.
+    pub fn take_bus_log(&mut self) -> Vec<BusAccess> {
+        self.bus_log.take()
+    }
+
     pub fn read_u8(&self, address: u16) -> u8 {
-        return self.memory[address as usize];
+        let value = self.read(address);
+        if self.flat {
+            self.bus_log.borrow_mut().push(BusAccess::Read(address, value));
+        }
+        if let Some(pc) = self.instruction_pc {
+            for watchpoint in &self.watchpoints {
+                if watchpoint.on_read() && watchpoint.contains(address) {
+                    self.watch_hits.borrow_mut().push(WatchHit {
+                        kind: watchpoint.kind,
+                        pc,
+                        address,
+                        old: value,
+                        new: value,
+                    });
+                }
+            }
+        }
+        value
     }
-    
+
+    fn read(&self, address: u16) -> u8 {
+        if self.flat {
+            return self.memory[address as usize];
+        }
+        if let Some(value) = self.boot_rom_byte(address) {
+            return value;
+        }
+
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF if self.cartridge.is_some() => {
+                self.cartridge.as_ref().unwrap().read(address)
+            }
+            0x8000..=0x9FFF => self.ppu.read_vram(address),
+            0xC000..=0xFDFF => self.wram[self.wram_offset(address)],
+            0xFE00..=0xFE9F => self.ppu.oam[(address - 0xFE00) as usize],
+            P1 => self.joypad.read(),
+            0xFF01..=0xFF02 => self.serial.read(address),
+            0xFF04..=0xFF07 => self.timer.read(address),
+            0xFF10..=0xFF3F => self.apu.read(address),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B | 0xFF4F | 0xFF68..=0xFF6C => self.ppu.read(address),
+            HDMA1..=HDMA5 if self.cgb => self.hdma.read(address),
+            KEY1 if self.cgb => 0x7E | ((self.double_speed as u8) << 7) | self.speed_switch_armed as u8,
+            SVBK if self.cgb => 0xF8 | self.wram_bank,
+            _ => self.memory[address as usize],
+        }
+    }
+
+    // 16-bit values are little-endian, low byte first.
     pub fn read_u16(&self, address: u16) -> u16 {
-        return BigEndian::read_u16(&self.memory[address as usize..(address + 2) as usize]);
+        u16::from_le_bytes([self.read_u8(address), self.read_u8(address.wrapping_add(1))])
     }
 
     pub fn write_u16(&mut self, address: u16, value: u16) {
-        BigEndian::write_u16(
-            &mut self.memory[address as usize..(address + 2) as usize],
-            value,
-        );
+        let [low, high] = value.to_le_bytes();
+        self.write_u8(address, low);
+        self.write_u8(address.wrapping_add(1), high);
+    }
+
+    // The cartridge ROM bank that `address` reads from, if it's in ROM.
+    pub fn rom_bank(&self, address: u16) -> Option<usize> {
+        match &self.cartridge {
+            Some(cartridge) if address < 0x8000 && self.boot_rom_byte(address).is_none() => {
+                Some(cartridge.bank_at(address))
+            }
+            _ => None,
+        }
+    }
+
+    // Like `write_u8`, except that cartridge ROM is changed rather than the
+    // write going to its MBC.
+    pub fn patch_u8(&mut self, address: u16, value: u8) {
+        match self.cartridge.as_mut() {
+            Some(cartridge) if address < 0x8000 => cartridge.patch(address, value),
+            _ => self.write_u8(address, value),
+        }
     }
 
     pub fn write_u8(&mut self, address: u16, value: u8) {
-        self.memory[address as usize] = value;
+        if let Some(pc) = self.instruction_pc
+            && self.watchpoints.iter().any(|watchpoint| watchpoint.contains(address))
+        {
+            let old = self.read(address);
+            for watchpoint in &self.watchpoints {
+                if watchpoint.contains(address) && watchpoint.triggers_on_write(old, value) {
+                    self.watch_hits.borrow_mut().push(WatchHit {
+                        kind: watchpoint.kind,
+                        pc,
+                        address,
+                        old,
+                        new: value,
+                    });
+                }
+            }
+        }
+        if self.flat {
+            self.bus_log.borrow_mut().push(BusAccess::Write(address, value));
+        }
+        self.write(address, value);
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if self.flat {
+            self.memory[address as usize] = value;
+            return;
+        }
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF if self.cartridge.is_some() => {
+                self.cartridge.as_mut().unwrap().write(address, value)
+            }
+            0x8000..=0x9FFF => self.ppu.write_vram(address, value),
+            0xC000..=0xFDFF => self.wram[self.wram_offset(address)] = value,
+            0xFE00..=0xFE9F => self.ppu.oam[(address - 0xFE00) as usize] = value,
+            P1 => self.joypad.write(value),
+            0xFF01..=0xFF02 => self.serial.write(address, value),
+            0xFF04..=0xFF07 => {
+                if self.timer.write(address, value) {
+                    self.request_interrupt(INT_TIMER);
+                }
+            }
+            0xFF10..=0xFF3F => self.apu.write(address, value),
+            DMA => self.oam_dma(value),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B | 0xFF4F | 0xFF68..=0xFF6C => self.ppu.write(address, value),
+            HDMA1..=HDMA5 if self.cgb => match self.hdma.write(address, value) {
+                Some(Transfer::General) => {
+                    for _ in 0..self.hdma.blocks_remaining() {
+                        self.hdma_block();
+                    }
+                }
+                // Without the LCD there's no HBlank to wait for
+                Some(Transfer::HBlank) if !self.ppu.lcd_enabled() => self.hdma_block(),
+                _ => {}
+            },
+            BOOT => {
+                if value & 0x01 != 0 {
+                    self.boot_rom = None;
+                }
+                self.memory[address as usize] = value;
+            }
+            KEY1 if self.cgb => self.speed_switch_armed = value & 0x01 != 0,
+            SVBK if self.cgb => self.wram_bank = (value & 0x07).max(1),
+            _ => self.memory[address as usize] = value,
+        }
+    }
+
+    pub fn request_interrupt(&mut self, interrupt: u8) {
+        self.memory[INTERRUPT_FLAG as usize] |= interrupt;
+    }
+
+    // Advances every memory-mapped device by the given number of T-cycles.
+    // In double speed the PPU and APU keep running at the normal rate.
+    pub fn tick(&mut self, cycles: u32) {
+        if self.flat {
+            return;
+        }
+        if self.serial.tick(cycles) {
+            self.request_interrupt(INT_SERIAL);
+        }
+        if self.timer.tick(cycles) {
+            self.request_interrupt(INT_TIMER);
+        }
+
+        let dots = if self.double_speed { cycles / 2 } else { cycles };
+        let interrupts = self.ppu.tick(dots);
+        self.request_interrupt(interrupts);
+        if self.ppu.take_hblank() && self.hdma.hblank_active() {
+            self.hdma_block();
+        }
+
+        self.apu.tick(dots);
+        for _ in 0..self.timer.take_frame_sequencer_steps() {
+            self.apu.step_frame_sequencer();
+        }
+    }
+
+    fn boot_rom_byte(&self, address: u16) -> Option<u8> {
+        let rom = self.boot_rom.as_ref()?;
+        match address {
+            0x0100..=0x01FF => None,
+            _ => rom.get(address as usize).copied(),
+        }
+    }
+
+    // 0xC000-0xCFFF is always bank 0; SVBK picks what shows at 0xD000 on the CGB.
+    // 0xE000-0xFDFF echoes the same memory.
+    fn wram_offset(&self, address: u16) -> usize {
+        let offset = (address as usize - 0xC000) & 0x1FFF;
+        if offset < WRAM_BANK_SIZE {
+            offset
+        } else {
+            let bank = if self.cgb { self.wram_bank as usize } else { 1 };
+            bank * WRAM_BANK_SIZE + offset - WRAM_BANK_SIZE
+        }
+    }
+
+    // Copies 16 bytes into the VRAM bank selected by VBK.
+    fn hdma_block(&mut self) {
+        let (source, destination) = self.hdma.next_block();
+        for i in 0..BLOCK_SIZE {
+            let value = self.read_u8(source.wrapping_add(i));
+            self.ppu.write_vram(destination + i, value);
+        }
+        self.dma_stall_cycles += Hdma::block_cycles(self.double_speed);
+    }
+
+    // Copies 160 bytes from XX00 into OAM. Done all at once rather than over
+    // the 160 cycles the hardware takes.
+    fn oam_dma(&mut self, page: u8) {
+        self.memory[DMA as usize] = page;
+        let source = (page as u16) << 8;
+        for i in 0..0xA0 {
+            self.ppu.oam[i as usize] = self.read_u8(source + i);
+        }
     }
 }
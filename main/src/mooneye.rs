@@ -0,0 +1,123 @@
+use crate::gameboy::{Gameboy, Model};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Mooneye's test ROMs finish with LD B,B. On a pass B, C, D, E, H and L hold
+// the start of the Fibonacci sequence; on a failure they all hold 42.
+pub const FIBONACCI: [u8; 6] = [3, 5, 8, 13, 21, 34];
+pub const FAILURE: [u8; 6] = [0x42; 6];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    // Finished with registers that aren't either signature.
+    Unknown,
+    // Never reached LD B,B within the budget, or stopped.
+    Timeout,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Outcome::Passed => "PASS",
+            Outcome::Failed => "FAIL",
+            Outcome::Unknown => "????",
+            Outcome::Timeout => "TIME",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// Runs a test ROM that's already loaded until it executes LD B,B, stops or
+// `budget` T-cycles have passed.
+pub fn run(gameboy: &mut Gameboy, budget: u64) -> Outcome {
+    let start = gameboy.cycles;
+    gameboy.break_on_ld_b_b = true;
+    gameboy.take_ld_b_b_hit();
+
+    let mut outcome = Outcome::Timeout;
+    while gameboy.running && gameboy.cycles - start < budget {
+        gameboy.execute_next();
+        if gameboy.take_ld_b_b_hit() {
+            let cpu = &gameboy.cpu;
+            outcome = match [cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l] {
+                FIBONACCI => Outcome::Passed,
+                FAILURE => Outcome::Failed,
+                _ => Outcome::Unknown,
+            };
+            break;
+        }
+    }
+    gameboy.break_on_ld_b_b = false;
+    outcome
+}
+
+// Whether a test is meant for the DMG, going by the hardware named after its
+// last '-': "dmgABC" or "dmgABCmgb" for particular DMG revisions, "GS" for
+// groups, where G is the DMG and MGB. "dmg0" is the early DMG, which isn't
+// emulated, and tests without a suffix run anywhere.
+pub fn runs_on_dmg(name: &str) -> bool {
+    let Some((_, models)) = name.rsplit_once('-') else {
+        return true;
+    };
+    if models.starts_with("dmg0") {
+        false
+    } else if models.starts_with("dmg") {
+        true
+    } else if models.bytes().all(|b| b.is_ascii_uppercase()) {
+        models.contains('G')
+    } else {
+        !["mgb", "sgb", "sgb2", "cgb", "agb", "ags"]
+            .iter()
+            .any(|model| models.starts_with(model))
+    }
+}
+
+// Every .gb file under `dir` meant for the DMG, sorted, with its path
+// relative to `dir` and what happened when it ran.
+pub fn run_dir(dir: &Path, budget: u64) -> Result<Vec<(String, Outcome)>, String> {
+    let mut paths = Vec::new();
+    find_roms(dir, &mut paths).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    paths.sort();
+
+    let mut results = Vec::new();
+    for path in paths {
+        let name = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().into_owned();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        if !runs_on_dmg(&stem) {
+            continue;
+        }
+
+        let mut gameboy = Gameboy::new();
+        gameboy
+            .load_cartridge(&path, Some(Model::Dmg))
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        gameboy.skip_boot_rom();
+        results.push((name, run(&mut gameboy, budget)));
+    }
+    Ok(results)
+}
+
+fn find_roms(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_roms(&path, paths)?;
+        } else if path.extension().is_some_and(|extension| extension == "gb") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+// One "PASS name" line per test, then how many passed.
+pub fn table(results: &[(String, Outcome)]) -> String {
+    let mut text = String::new();
+    for (name, outcome) in results {
+        text += &format!("{}  {}\n", outcome, name);
+    }
+    let passed = results.iter().filter(|(_, outcome)| *outcome == Outcome::Passed).count();
+    text += &format!("{}/{} passed\n", passed, results.len());
+    text
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm;
+    use crate::gameboy::Gameboy;
+    use crate::mooneye::*;
+    use crate::testing::{TempPath, gameboy_with_rom_program};
+    use std::path::PathBuf;
+
+    // Emulated seconds each test gets.
+    const BUDGET: u64 = 10 * 4_194_304;
+
+    // Acceptance tests that have to pass; the rest have to fail, so a test
+    // that starts passing has to be added here. None do while the CPU has no
+    // jumps or calls.
+    const PASSING: &[&str] = &[];
+
+    // A ROM-only cartridge.
+    fn gameboy_running(program: &str) -> Gameboy {
+        gameboy_with_rom_program(program, 0x00, 0x00)
+    }
+
+    #[test]
+    fn test_reads_the_registers_at_ld_b_b() {
+        let passing = "ld b, 3\nld c, 5\nld d, 8\nld e, 13\nld h, 21\nld l, 34\nld b, b\nld b, 0";
+        let mut gameboy = gameboy_running(passing);
+        assert_eq!(run(&mut gameboy, 1000), Outcome::Passed);
+        assert_eq!(gameboy.cpu.program_counter, 0x015D);
+
+        let failing = "ld a, $42\nld b, a\nld c, a\nld d, a\nld e, a\nld h, a\nld l, a\nld b, b";
+        assert_eq!(run(&mut gameboy_running(failing), 1000), Outcome::Failed);
+        assert_eq!(run(&mut gameboy_running("ld b, 1\nld b, b"), 1000), Outcome::Unknown);
+        assert_eq!(run(&mut gameboy_running("nop"), 1000), Outcome::Timeout);
+    }
+
+    #[test]
+    fn test_picks_dmg_tests() {
+        assert!(runs_on_dmg("add_sp_e_timing"));
+        assert!(runs_on_dmg("boot_regs-dmgABC"));
+        assert!(runs_on_dmg("boot_hwio-dmgABCmgb"));
+        assert!(runs_on_dmg("di_timing-GS"));
+        assert!(!runs_on_dmg("boot_div-dmg0"));
+        assert!(!runs_on_dmg("boot_regs-sgb2"));
+        assert!(!runs_on_dmg("boot_regs-mgb"));
+        assert!(!runs_on_dmg("boot_div-cgbABCDE"));
+        assert!(!runs_on_dmg("boot_hwio-S"));
+    }
+
+    #[test]
+    fn test_runs_a_directory() {
+        let dir = TempPath::new("mooneye_run_dir");
+        std::fs::create_dir_all(dir.join("bits")).unwrap();
+        let rom = |program: &str| {
+            let mut rom = vec![0; 0x8000];
+            let code = asm::assemble_at(program, 0x0100).unwrap();
+            rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+            rom
+        };
+        let passing = rom("ld b, 3\nld c, 5\nld d, 8\nld e, 13\nld h, 21\nld l, 34\nld b, b");
+        std::fs::write(dir.join("bits/pass.gb"), &passing).unwrap();
+        std::fs::write(dir.join("fail-GS.gb"), rom("ld b, b")).unwrap();
+        std::fs::write(dir.join("skipped-cgb.gb"), &passing).unwrap();
+
+        let results = run_dir(&dir, 1000).unwrap();
+
+        let expected = [
+            ("bits/pass.gb".to_string(), Outcome::Passed),
+            ("fail-GS.gb".to_string(), Outcome::Unknown),
+        ];
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_table() {
+        let results = [
+            ("bits/mem_oam.gb".to_string(), Outcome::Passed),
+            ("ei_sequence.gb".to_string(), Outcome::Timeout),
+        ];
+        assert_eq!(table(&results), "PASS  bits/mem_oam.gb\nTIME  ei_sequence.gb\n1/2 passed\n");
+    }
+
+    // Needs the suite in testdata/mooneye; run it with `cargo test --
+    // --ignored`. `main mooneye <dir>` prints the whole table.
+    #[test]
+    #[ignore = "needs the Mooneye ROMs in testdata/mooneye"]
+    fn test_acceptance_suite() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/mooneye/acceptance");
+        assert!(dir.exists(), "{} isn't there", dir.display());
+
+        let results = run_dir(&dir, BUDGET).unwrap();
+        assert!(!results.is_empty(), "no DMG tests in {}", dir.display());
+        for name in PASSING {
+            assert!(results.iter().any(|(test, _)| test == name), "{} didn't run", name);
+        }
+        let wrong: Vec<String> = results
+            .iter()
+            .filter(|(name, outcome)| PASSING.contains(&name.as_str()) != (*outcome == Outcome::Passed))
+            .map(|(name, outcome)| match outcome {
+                Outcome::Passed => format!("{} passes now, add it to PASSING", name),
+                _ => format!("{}: {}", name, outcome),
+            })
+            .collect();
+        assert!(wrong.is_empty(), "{}", wrong.join("\n"));
+    }
+}
@@ -0,0 +1,59 @@
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+// Mnemonics, lengths and timings for every opcode, read from dmgops.json.
+// Operands in names are placeholders: u8, u16 and i8 for immediates.
+
+#[derive(Debug, Deserialize)]
+pub struct FlagEffects {
+    #[serde(rename = "Z")]
+    pub z: String,
+    #[serde(rename = "N")]
+    pub n: String,
+    #[serde(rename = "H")]
+    pub h: String,
+    #[serde(rename = "C")]
+    pub c: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OpcodeInfo {
+    pub name: String,
+    pub group: String,
+    pub t_cycles_branch: u32,
+    pub t_cycles_no_branch: u32,
+    pub length: u16,
+    pub flags: FlagEffects,
+}
+
+#[derive(Deserialize)]
+struct OpcodeTable {
+    #[serde(rename = "Unprefixed")]
+    unprefixed: Vec<OpcodeInfo>,
+    #[serde(rename = "CBPrefixed")]
+    cb_prefixed: Vec<OpcodeInfo>,
+}
+
+static OPCODES: Lazy<OpcodeTable> = Lazy::new(|| {
+    serde_json::from_str(include_str!("../../dmgops.json")).expect("dmgops.json is malformed")
+});
+
+pub fn unprefixed(opcode: u8) -> &'static OpcodeInfo {
+    &OPCODES.unprefixed[opcode as usize]
+}
+
+// The opcode byte after 0xCB. Lengths include the prefix.
+pub fn cb_prefixed(opcode: u8) -> &'static OpcodeInfo {
+    &OPCODES.cb_prefixed[opcode as usize]
+}
+
+// The entry for the instruction starting with `opcode`, where `next` is the
+// byte after it.
+pub fn lookup(opcode: u8, next: u8) -> &'static OpcodeInfo {
+    if opcode == 0xCB {
+        cb_prefixed(next)
+    } else {
+        unprefixed(opcode)
+    }
+}
@@ -0,0 +1,25 @@
+#[cfg(test)]
+mod tests {
+    use crate::opcodes::*;
+
+    #[test]
+    fn test_unprefixed_entries() {
+        let jr = unprefixed(0x20);
+        assert_eq!(jr.name, "JR NZ,i8");
+        assert_eq!(jr.length, 2);
+        assert_eq!((jr.t_cycles_branch, jr.t_cycles_no_branch), (12, 8));
+
+        assert_eq!(unprefixed(0xD3).group, "unused");
+    }
+
+    #[test]
+    fn test_cb_prefix_lookup() {
+        let bit = lookup(0xCB, 0x7C);
+        assert_eq!(bit.name, "BIT 7,H");
+        assert_eq!(bit.length, 2);
+        assert_eq!(bit.flags.z, "Z");
+        assert_eq!(bit.flags.c, "-");
+
+        assert_eq!(lookup(0x00, 0x7C).name, "NOP");
+    }
+}
@@ -0,0 +1,153 @@
+use crate::cpu::{Gbz80, Reg8};
+use crate::gameboy::Gameboy;
+
+impl Gameboy {
+    pub fn add(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        self.cpu.a = self.add_to_a(value, false);
+    }
+
+    pub fn adc(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        let carry = self.cpu.flag(Gbz80::FLAG_C);
+        self.cpu.a = self.add_to_a(value, carry);
+    }
+
+    pub fn sub(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        self.cpu.a = self.subtract_from_a(value, false);
+    }
+
+    pub fn sbc(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        let carry = self.cpu.flag(Gbz80::FLAG_C);
+        self.cpu.a = self.subtract_from_a(value, carry);
+    }
+
+    // A SUB that only keeps the flags.
+    pub fn cp(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        self.subtract_from_a(value, false);
+    }
+
+    pub fn and(&mut self, opcode: u8) {
+        let value = self.cpu.a & self.alu_operand(opcode);
+        self.cpu.set_flags(value == 0, false, true);
+        self.cpu.set_flag(Gbz80::FLAG_C, false);
+        self.cpu.a = value;
+    }
+
+    pub fn or(&mut self, opcode: u8) {
+        let value = self.cpu.a | self.alu_operand(opcode);
+        self.cpu.set_flags(value == 0, false, false);
+        self.cpu.set_flag(Gbz80::FLAG_C, false);
+        self.cpu.a = value;
+    }
+
+    // INC r and DEC r leave C alone.
+    pub fn inc(&mut self, opcode: u8) {
+        let index = (opcode >> 3) & 0x07;
+        let value = self.reg8_or_hl(index);
+        let result = value.wrapping_add(1);
+        self.cpu.set_flags(result == 0, false, value & 0x0F == 0x0F);
+        self.write_reg8_or_hl(index, result);
+    }
+
+    pub fn dec(&mut self, opcode: u8) {
+        let index = (opcode >> 3) & 0x07;
+        let value = self.reg8_or_hl(index);
+        let result = value.wrapping_sub(1);
+        self.cpu.set_flags(result == 0, true, value & 0x0F == 0);
+        self.write_reg8_or_hl(index, result);
+    }
+
+    // Turns the result of adding or subtracting two BCD numbers back into BCD,
+    // going by N, H and C from the instruction before.
+    pub fn daa(&mut self, _opcode: u8) {
+        let mut a = self.cpu.a;
+        let mut carry = self.cpu.flag(Gbz80::FLAG_C);
+        let half_carry = self.cpu.flag(Gbz80::FLAG_H);
+        if self.cpu.flag(Gbz80::FLAG_N) {
+            if carry {
+                a = a.wrapping_sub(0x60);
+            }
+            if half_carry {
+                a = a.wrapping_sub(0x06);
+            }
+        } else {
+            if carry || a > 0x99 {
+                a = a.wrapping_add(0x60);
+                carry = true;
+            }
+            if half_carry || a & 0x0F > 0x09 {
+                a = a.wrapping_add(0x06);
+            }
+        }
+        self.cpu.set_flag(Gbz80::FLAG_Z, a == 0);
+        self.cpu.set_flag(Gbz80::FLAG_H, false);
+        self.cpu.set_flag(Gbz80::FLAG_C, carry);
+        self.cpu.a = a;
+    }
+
+    pub fn cpl(&mut self, _opcode: u8) {
+        self.cpu.a = !self.cpu.a;
+        self.cpu.set_flag(Gbz80::FLAG_N, true);
+        self.cpu.set_flag(Gbz80::FLAG_H, true);
+    }
+
+    pub fn scf(&mut self, _opcode: u8) {
+        self.cpu.set_flag(Gbz80::FLAG_N, false);
+        self.cpu.set_flag(Gbz80::FLAG_H, false);
+        self.cpu.set_flag(Gbz80::FLAG_C, true);
+    }
+
+    pub fn ccf(&mut self, _opcode: u8) {
+        let carry = self.cpu.flag(Gbz80::FLAG_C);
+        self.cpu.set_flag(Gbz80::FLAG_N, false);
+        self.cpu.set_flag(Gbz80::FLAG_H, false);
+        self.cpu.set_flag(Gbz80::FLAG_C, !carry);
+    }
+
+    // The second operand: the register in the low 3 bits, (HL), or for the
+    // opcodes from C0 up the byte after the opcode.
+    fn alu_operand(&mut self, opcode: u8) -> u8 {
+        if opcode >= 0xC0 {
+            self.read_u8_increment_pc()
+        } else {
+            self.reg8_or_hl(opcode & 0x07)
+        }
+    }
+
+    // Registers as opcodes number them, where 6 is (HL) rather than F.
+    fn reg8_or_hl(&self, index: u8) -> u8 {
+        match index {
+            6 => self.memory.read_u8(self.cpu.hl()),
+            _ => self.cpu.reg8(Reg8::from_u8(index)),
+        }
+    }
+
+    fn write_reg8_or_hl(&mut self, index: u8, value: u8) {
+        match index {
+            6 => self.memory.write_u8(self.cpu.hl(), value),
+            _ => self.cpu.write_reg8(Reg8::from_u8(index), value),
+        }
+    }
+
+    fn add_to_a(&mut self, value: u8, carry: bool) -> u8 {
+        let (a, carry) = (self.cpu.a, carry as u8);
+        let result = a.wrapping_add(value).wrapping_add(carry);
+        let half_carry = (a & 0x0F) + (value & 0x0F) + carry > 0x0F;
+        self.cpu.set_flags(result == 0, false, half_carry);
+        self.cpu.set_flag(Gbz80::FLAG_C, a as u16 + value as u16 + carry as u16 > 0xFF);
+        result
+    }
+
+    fn subtract_from_a(&mut self, value: u8, carry: bool) -> u8 {
+        let (a, carry) = (self.cpu.a, carry as u8);
+        let result = a.wrapping_sub(value).wrapping_sub(carry);
+        let half_borrow = (a & 0x0F) < (value & 0x0F) + carry;
+        self.cpu.set_flags(result == 0, true, half_borrow);
+        self.cpu.set_flag(Gbz80::FLAG_C, (a as u16) < value as u16 + carry as u16);
+        result
+    }
+}
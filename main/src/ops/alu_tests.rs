@@ -0,0 +1,230 @@
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::Gameboy;
+    use crate::opcodes;
+    use proptest::prelude::*;
+    use proptest::sample::select;
+
+    const CODE: u16 = 0xC000;
+    const DATA: u16 = 0xD000;
+
+    fn alu_opcodes() -> Vec<u8> {
+        (0..=0xFF).filter(|&opcode| opcodes::unprefixed(opcode).group == "x8/alu").collect()
+    }
+
+    // The SM83's 8-bit ALU written from its flag rules, working in wider
+    // integers instead of nibbles. Takes the mnemonic from dmgops.json and the
+    // operand, and returns the result (the new A, or the new INC/DEC operand)
+    // and F.
+    fn reference(mnemonic: &str, a: u8, f: u8, value: u8) -> (u8, u8) {
+        let (z, n, h, c) = (f & 0x80 != 0, f & 0x40 != 0, f & 0x20 != 0, f & 0x10 != 0);
+        let flags =
+            |z: bool, n: bool, h: bool, c: bool| (z as u8) << 7 | (n as u8) << 6 | (h as u8) << 5 | (c as u8) << 4;
+        // Bit 4 of a sum or difference differs from the operands' when the low
+        // nibble carried or borrowed.
+        let nibble_carry = |x: u8, y: u8, result: u8| (x ^ y ^ result) & 0x10 != 0;
+
+        match mnemonic {
+            "ADD" | "ADC" => {
+                let sum = a as u16 + value as u16 + (mnemonic == "ADC" && c) as u16;
+                let result = sum as u8;
+                (result, flags(result == 0, false, nibble_carry(a, value, result), sum > 0xFF))
+            }
+            "SUB" | "SBC" | "CP" => {
+                let difference = a as i16 - value as i16 - (mnemonic == "SBC" && c) as i16;
+                let result = difference as u8;
+                let flags = flags(result == 0, true, nibble_carry(a, value, result), difference < 0);
+                (if mnemonic == "CP" { a } else { result }, flags)
+            }
+            "AND" => (a & value, flags(a & value == 0, false, true, false)),
+            "XOR" => (a ^ value, flags(a ^ value == 0, false, false, false)),
+            "OR" => (a | value, flags(a | value == 0, false, false, false)),
+            "INC" => {
+                let result = value.wrapping_add(1);
+                (result, flags(result == 0, false, nibble_carry(value, 1, result), c))
+            }
+            "DEC" => {
+                let result = value.wrapping_sub(1);
+                (result, flags(result == 0, true, nibble_carry(value, 1, result), c))
+            }
+            "DAA" => {
+                let mut correction = 0;
+                if h || (!n && a & 0x0F > 0x09) {
+                    correction |= 0x06;
+                }
+                if c || (!n && a > 0x99) {
+                    correction |= 0x60;
+                }
+                let result = if n { a.wrapping_sub(correction) } else { a.wrapping_add(correction) };
+                (result, flags(result == 0, n, false, correction & 0x60 != 0))
+            }
+            "CPL" => (!a, flags(z, true, true, c)),
+            "SCF" => (a, flags(z, false, false, true)),
+            "CCF" => (a, flags(z, false, false, !c)),
+            _ => panic!("{} isn't an ALU instruction", mnemonic),
+        }
+    }
+
+    // The state an instruction starts from. B-L are set as given, except that
+    // HL points at DATA for the (HL) forms.
+    #[derive(Debug, Clone)]
+    struct State {
+        a: u8,
+        f: u8,
+        registers: [u8; 6],
+        memory: u8,
+        immediate: u8,
+    }
+
+    fn run(gameboy: &mut Gameboy, opcode: u8, state: &State) {
+        let cpu = &mut gameboy.cpu;
+        (cpu.a, cpu.f) = (state.a, state.f);
+        [cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l] = state.registers;
+        if opcodes::unprefixed(opcode).name.contains("(HL)") {
+            cpu.set_hl(DATA);
+        }
+        cpu.program_counter = CODE;
+        gameboy.memory.write_u8(CODE, opcode);
+        gameboy.memory.write_u8(CODE + 1, state.immediate);
+        gameboy.memory.write_u8(DATA, state.memory);
+        gameboy.execute_next();
+    }
+
+    fn flat_gameboy() -> Gameboy {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.set_flat_bus(true);
+        gameboy
+    }
+
+    // Runs `opcode` and checks A, F and what INC or DEC changed against the
+    // reference.
+    fn check(gameboy: &mut Gameboy, opcode: u8, state: &State) -> Result<(), TestCaseError> {
+        let name = &opcodes::unprefixed(opcode).name;
+        let (mnemonic, operands) = name.split_once(' ').unwrap_or((name, ""));
+        let operand = operands.rsplit(',').next().unwrap();
+        let [b, c, d, e, h, l] = state.registers;
+        let value_of = |operand: &str| match operand {
+            "A" => state.a,
+            "B" => b,
+            "C" => c,
+            "D" => d,
+            "E" => e,
+            "H" => h,
+            "L" => l,
+            "(HL)" => state.memory,
+            "u8" => state.immediate,
+            _ => 0,
+        };
+
+        run(gameboy, opcode, state);
+        let (result, f) = reference(mnemonic, state.a, state.f, value_of(operand));
+
+        let cpu = &gameboy.cpu;
+        prop_assert_eq!(cpu.f, f, "F after {} with {:?}", name, state);
+        if mnemonic == "INC" || mnemonic == "DEC" {
+            let actual = match operand {
+                "A" => cpu.a,
+                "B" => cpu.b,
+                "C" => cpu.c,
+                "D" => cpu.d,
+                "E" => cpu.e,
+                "H" => cpu.h,
+                "L" => cpu.l,
+                _ => gameboy.memory.read_u8(DATA),
+            };
+            prop_assert_eq!(actual, result, "{} after {} with {:?}", operand, name, state);
+        } else {
+            prop_assert_eq!(cpu.a, result, "A after {} with {:?}", name, state);
+        }
+        Ok(())
+    }
+
+    fn state() -> impl Strategy<Value = State> {
+        (any::<u8>(), 0..16u8, any::<[u8; 6]>(), any::<u8>(), any::<u8>()).prop_map(
+            |(a, flags, registers, memory, immediate)| State {
+                a,
+                f: flags << 4,
+                registers,
+                memory,
+                immediate,
+            },
+        )
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(4096))]
+
+        #[test]
+        fn test_alu_matches_reference(opcode in select(alu_opcodes()), state in state()) {
+            check(&mut flat_gameboy(), opcode, &state)?;
+        }
+    }
+
+    // Every A, operand and carry for the u8 forms, and every A and N/H/C for DAA.
+    #[test]
+    fn test_every_operand() {
+        let mut gameboy = flat_gameboy();
+        let immediate_forms = alu_opcodes()
+            .into_iter()
+            .filter(|&opcode| opcodes::unprefixed(opcode).name.ends_with("u8"));
+        for opcode in immediate_forms {
+            for a in 0..=0xFF {
+                for immediate in 0..=0xFF {
+                    for f in [0x00, 0x10] {
+                        let state = State {
+                            a,
+                            f,
+                            registers: [0; 6],
+                            memory: 0,
+                            immediate,
+                        };
+                        check(&mut gameboy, opcode, &state).unwrap();
+                    }
+                }
+            }
+        }
+
+        for a in 0..=0xFF {
+            for flags in 0..8 {
+                let state = State {
+                    a,
+                    f: flags << 4,
+                    registers: [0; 6],
+                    memory: 0,
+                    immediate: 0,
+                };
+                check(&mut gameboy, 0x27, &state).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_corner_cases() {
+        let mut gameboy = flat_gameboy();
+        let state = |a: u8, f: u8, immediate: u8| State {
+            a,
+            f,
+            registers: [0; 6],
+            memory: 0,
+            immediate,
+        };
+
+        // ADD A,u8: 0F+01 half-carries, FF+01 carries to zero.
+        run(&mut gameboy, 0xC6, &state(0x0F, 0x00, 0x01));
+        assert_eq!((gameboy.cpu.a, gameboy.cpu.f), (0x10, 0x20));
+        run(&mut gameboy, 0xC6, &state(0xFF, 0x00, 0x01));
+        assert_eq!((gameboy.cpu.a, gameboy.cpu.f), (0x00, 0xB0));
+        // ADC A,u8 counts the carry in the half-carry too.
+        run(&mut gameboy, 0xCE, &state(0x0E, 0x10, 0x01));
+        assert_eq!((gameboy.cpu.a, gameboy.cpu.f), (0x10, 0x20));
+        // SBC A,u8: 00-00-1 borrows from both nibbles.
+        run(&mut gameboy, 0xDE, &state(0x00, 0x10, 0x00));
+        assert_eq!((gameboy.cpu.a, gameboy.cpu.f), (0xFF, 0x70));
+        // CP A,u8 leaves A.
+        run(&mut gameboy, 0xFE, &state(0x42, 0x00, 0x42));
+        assert_eq!((gameboy.cpu.a, gameboy.cpu.f), (0x42, 0xC0));
+        // XOR A,A clears C.
+        run(&mut gameboy, 0xAF, &state(0x42, 0x10, 0x00));
+        assert_eq!((gameboy.cpu.a, gameboy.cpu.f), (0x00, 0x80));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::cpu::{Reg8, Reg16};
+use crate::cpu::{Gbz80, Reg8, Reg16};
 use crate::gameboy::Gameboy;
 
 impl Gameboy {
@@ -12,7 +12,7 @@ impl Gameboy {
             0x40..=0x7F => self.ld_r_r(opcode),
 
             // LD r,n
-            0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x3E => self.ld_r_n(opcode),
+            0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => self.ld_r_n(opcode),
 
             // LD A,(rr)
             0x0A => self.ld_a_rr(Reg16::BC),
@@ -22,6 +22,12 @@ impl Gameboy {
             0x02 => self.ld_rr_a(Reg16::BC),
             0x12 => self.ld_rr_a(Reg16::DE),
 
+            // LD (HL+),A, LD (HL-),A, LD A,(HL+) and LD A,(HL-)
+            0x22 => self.ld_hli_a(1),
+            0x32 => self.ld_hli_a(-1),
+            0x2A => self.ld_a_hli(1),
+            0x3A => self.ld_a_hli(-1),
+
             0x01 => self.ld_rr_nn(Reg16::BC),
             0x11 => self.ld_rr_nn(Reg16::DE),
             0x21 => self.ld_rr_nn(Reg16::HL),
@@ -33,6 +39,21 @@ impl Gameboy {
             // LD (nn),A
             0xEA => self.ld_nn_a(),
 
+            // LDH (n),A, LDH A,(n), LD (C),A and LD A,(C)
+            0xE0 => {
+                let address = 0xFF00 | self.read_u8_increment_pc() as u16;
+                self.memory.write_u8(address, self.cpu.a);
+            }
+            0xF0 => {
+                let address = 0xFF00 | self.read_u8_increment_pc() as u16;
+                self.cpu.a = self.memory.read_u8(address);
+            }
+            0xE2 => self.memory.write_u8(0xFF00 | self.cpu.c as u16, self.cpu.a),
+            0xF2 => self.cpu.a = self.memory.read_u8(0xFF00 | self.cpu.c as u16),
+
+            0xF8 => self.ld_hl_sp_e(),
+            0xF9 => self.cpu.stack_pointer = self.cpu.hl(),
+
             _ => self.not_implemented(opcode),
         }
     }
@@ -49,14 +70,18 @@ impl Gameboy {
     }
 
     fn ld_r_r(&mut self, opcode: u8) {
-        let dest = ((opcode >> 3) & 0x07) as u8;
-        let src = (opcode & 0x07) as u8;
+        let dest = (opcode >> 3) & 0x07;
+        let src = opcode & 0x07;
 
         if dest == 6 && src == 6 {
             // LD (HL),(HL) is actually HALT, not LD
             self.halt();
             return;
         }
+        // LD B,B
+        if opcode == 0x40 {
+            self.hit_ld_b_b();
+        }
 
         let value = if src == 6 {
             let addr = self.cpu.reg16(Reg16::HL);
@@ -74,7 +99,7 @@ impl Gameboy {
     }
 
     fn ld_r_n(&mut self, opcode: u8) {
-        let dest = ((opcode >> 3) & 0x07) as u8;
+        let dest = (opcode >> 3) & 0x07;
         let imm = self.read_u8_increment_pc();
 
         if dest == 6 {
@@ -86,7 +111,7 @@ impl Gameboy {
     }
 
     fn ld_r_hl(&mut self, opcode: u8) {
-        let dest = ((opcode >> 3) & 0x07) as u8;
+        let dest = (opcode >> 3) & 0x07;
         let addr = self.cpu.hl();
         let value = self.memory.read_u8(addr);
 
@@ -94,7 +119,7 @@ impl Gameboy {
     }
 
     fn ld_hl_r(&mut self, opcode: u8) {
-        let src = (opcode & 0x07) as u8;
+        let src = opcode & 0x07;
         let addr = self.cpu.reg16(Reg16::HL);
 
         let value = self.cpu.reg8(Reg8::from_u8(src));
@@ -113,6 +138,27 @@ impl Gameboy {
         self.memory.write_u8(addr, value);
     }
 
+    fn ld_hli_a(&mut self, step: i16) {
+        let addr = self.cpu.hl();
+        self.memory.write_u8(addr, self.cpu.a);
+        self.cpu.set_hl(addr.wrapping_add_signed(step));
+    }
+
+    fn ld_a_hli(&mut self, step: i16) {
+        let addr = self.cpu.hl();
+        self.cpu.a = self.memory.read_u8(addr);
+        self.cpu.set_hl(addr.wrapping_add_signed(step));
+    }
+
+    // H and C come from adding the offset to SP's low byte as unsigned.
+    fn ld_hl_sp_e(&mut self) {
+        let offset = self.read_u8_increment_pc();
+        let sp = self.cpu.stack_pointer;
+        self.cpu.set_hl(sp.wrapping_add_signed(offset as i8 as i16));
+        self.cpu.set_flags(false, false, (sp & 0x0F) + (offset as u16 & 0x0F) > 0x0F);
+        self.cpu.set_flag(Gbz80::FLAG_C, (sp & 0xFF) + offset as u16 > 0xFF);
+    }
+
     fn ld_a_nn(&mut self) {
         let addr = self.read_u16_increment_pc();
         let value = self.memory.read_u8(addr);
@@ -1,7 +1,7 @@
 
 #[cfg(test)]
-mod ld_tests {
-    use crate::cpu::{Reg16, Reg8};
+mod tests {
+    use crate::cpu::{Gbz80, Reg16, Reg8};
     use crate::gameboy::Gameboy;
     fn ld_opcode(dest: Reg8, src: Reg8) -> u8 {
         0x40 | ((dest as u8) << 3) | (src as u8)
@@ -170,5 +170,210 @@ mod ld_tests {
         }
     }
 
+    //
+    // LD B,B as a software breakpoint
+    //
+    #[test]
+    fn test_ld_b_b_breakpoint() {
+        let mut gb = Gameboy::new();
+        gb.cpu.write_reg8(Reg8::B, 0x12);
+
+        gb.ld(0x40);
+        assert!(!gb.take_ld_b_b_hit(), "LD B,B hit a breakpoint that was off");
+
+        gb.break_on_ld_b_b = true;
+        gb.ld(0x49);
+        assert!(!gb.take_ld_b_b_hit(), "LD C,C hit the LD B,B breakpoint");
+        gb.ld(0x40);
+        assert!(gb.take_ld_b_b_hit(), "LD B,B didn't hit the breakpoint");
+        assert!(!gb.take_ld_b_b_hit(), "breakpoint hit wasn't cleared");
+        assert_eq!(gb.cpu.reg8(Reg8::B), 0x12);
+    }
+
+    //
+    // LD rr,nn and LD SP,nn take the low byte first
+    //
+    #[test]
+    fn test_ld_rr_nn_little_endian() {
+        let tests = [(0x01, Reg16::BC), (0x11, Reg16::DE), (0x21, Reg16::HL)];
+
+        for &(opcode, pair) in &tests {
+            let mut gb = Gameboy::new();
+            gb.memory.write_u8(0, 0x34); // immediate at PC
+            gb.memory.write_u8(1, 0x12);
+
+            gb.ld(opcode);
+
+            assert_eq!(gb.cpu.reg16(pair), 0x1234, "LD {:?},nn failed", pair);
+            assert_eq!(gb.cpu.program_counter, 2);
+        }
+
+        let mut gb = Gameboy::new();
+        gb.memory.write_u8(0, 0xFE);
+        gb.memory.write_u8(1, 0xDF);
+        gb.ld(0x31);
+        assert_eq!(gb.cpu.stack_pointer, 0xDFFE);
+    }
+
+    //
+    // LD (nn),A and LD A,(nn)
+    //
+    #[test]
+    fn test_ld_nn_a() {
+        let mut gb = Gameboy::new();
+        gb.cpu.write_reg8(Reg8::A, 0x99);
+        gb.memory.write_u8(0, 0x10); // address at PC, low byte first
+        gb.memory.write_u8(1, 0xC0);
+        gb.ld(0xEA);
+        assert_eq!(gb.memory.read_u8(0xC010), 0x99);
+
+        let mut gb = Gameboy::new();
+        gb.memory.write_u8(0xC020, 0x66);
+        gb.memory.write_u8(0, 0x20);
+        gb.memory.write_u8(1, 0xC0);
+        gb.ld(0xFA);
+        assert_eq!(gb.cpu.reg8(Reg8::A), 0x66);
+    }
+
+    // A 16-bit read or write at FFFF wraps round to 0000.
+    #[test]
+    fn test_u16_access_wraps() {
+        let mut gb = Gameboy::new();
+        gb.memory.set_flat_bus(true);
+        gb.memory.write_u16(0xFFFF, 0xBEEF);
+        assert_eq!(gb.memory.read_u8(0xFFFF), 0xEF);
+        assert_eq!(gb.memory.read_u8(0x0000), 0xBE);
+        assert_eq!(gb.memory.read_u16(0xFFFF), 0xBEEF);
+    }
+
+    //
+    // LD (HL),n
+    //
+    #[test]
+    fn test_ld_hl_n() {
+        let mut gb = Gameboy::new();
+        gb.cpu.write_reg16(Reg16::HL, 0xC123);
+        gb.memory.write_u8(0, 0x77); // immediate at PC
+
+        gb.ld(0x36);
+
+        assert_eq!(gb.memory.read_u8(0xC123), 0x77);
+        assert_eq!(gb.cpu.program_counter, 1);
+    }
+
+    //
+    // LD (HL+),A, LD (HL-),A, LD A,(HL+) and LD A,(HL-)
+    //
+    #[test]
+    fn test_ld_hl_increment_decrement() {
+        let stores = [(0x22, 0xC001), (0x32, 0xBFFF)];
+        for &(opcode, hl_after) in &stores {
+            let mut gb = Gameboy::new();
+            gb.cpu.write_reg16(Reg16::HL, 0xC000);
+            gb.cpu.write_reg8(Reg8::A, 0x3C);
+
+            gb.ld(opcode);
+
+            assert_eq!(gb.memory.read_u8(0xC000), 0x3C, "opcode 0x{:02X} didn't store A", opcode);
+            assert_eq!(gb.cpu.reg16(Reg16::HL), hl_after, "opcode 0x{:02X} left HL wrong", opcode);
+        }
+
+        let loads = [(0x2A, 0xC001), (0x3A, 0xBFFF)];
+        for &(opcode, hl_after) in &loads {
+            let mut gb = Gameboy::new();
+            gb.cpu.write_reg16(Reg16::HL, 0xC000);
+            gb.memory.write_u8(0xC000, 0xC3);
+
+            gb.ld(opcode);
+
+            assert_eq!(gb.cpu.reg8(Reg8::A), 0xC3, "opcode 0x{:02X} didn't load A", opcode);
+            assert_eq!(gb.cpu.reg16(Reg16::HL), hl_after, "opcode 0x{:02X} left HL wrong", opcode);
+        }
+
+        // HL wraps at both ends.
+        let mut gb = Gameboy::new();
+        gb.cpu.write_reg16(Reg16::HL, 0xFFFF);
+        gb.ld(0x22);
+        assert_eq!(gb.cpu.reg16(Reg16::HL), 0x0000);
+        gb.ld(0x3A);
+        assert_eq!(gb.cpu.reg16(Reg16::HL), 0xFFFF);
+    }
+
+    //
+    // LDH (n),A, LDH A,(n), LD (C),A and LD A,(C)
+    //
+    #[test]
+    fn test_ld_high_page() {
+        let mut gb = Gameboy::new();
+        gb.cpu.write_reg8(Reg8::A, 0x5A);
+        gb.memory.write_u8(0, 0x90); // immediate at PC
+        gb.ld(0xE0);
+        assert_eq!(gb.memory.read_u8(0xFF90), 0x5A);
+        assert_eq!(gb.cpu.program_counter, 1);
+
+        let mut gb = Gameboy::new();
+        gb.memory.write_u8(0xFF91, 0xA5);
+        gb.memory.write_u8(0, 0x91);
+        gb.ld(0xF0);
+        assert_eq!(gb.cpu.reg8(Reg8::A), 0xA5);
+        assert_eq!(gb.cpu.program_counter, 1);
+
+        let mut gb = Gameboy::new();
+        gb.cpu.write_reg8(Reg8::A, 0x42);
+        gb.cpu.write_reg8(Reg8::C, 0x92);
+        gb.ld(0xE2);
+        assert_eq!(gb.memory.read_u8(0xFF92), 0x42);
+
+        let mut gb = Gameboy::new();
+        gb.memory.write_u8(0xFF93, 0x24);
+        gb.cpu.write_reg8(Reg8::C, 0x93);
+        gb.ld(0xF2);
+        assert_eq!(gb.cpu.reg8(Reg8::A), 0x24);
+        assert_eq!(gb.cpu.program_counter, 0);
+    }
+
+    //
+    // LD HL,SP+e
+    //
+    #[test]
+    fn test_ld_hl_sp_e() {
+        // SP, e, HL after, F after. H and C come from the low byte of SP plus
+        // e as unsigned, Z and N are always cleared.
+        let tests = [
+            (0xFFF8, 0x02, 0xFFFA, 0x00),
+            (0x000F, 0x01, 0x0010, Gbz80::FLAG_H),
+            (0x00F0, 0x10, 0x0100, Gbz80::FLAG_C),
+            (0x00FF, 0x01, 0x0100, Gbz80::FLAG_H | Gbz80::FLAG_C),
+            (0x0001, 0xFF, 0x0000, Gbz80::FLAG_H | Gbz80::FLAG_C),
+            (0x1000, 0x80, 0x0F80, 0x00),
+        ];
+
+        for &(sp, offset, hl, flags) in &tests {
+            let mut gb = Gameboy::new();
+            gb.cpu.stack_pointer = sp;
+            gb.cpu.write_reg8(Reg8::F, 0xF0);
+            gb.memory.write_u8(0, offset); // immediate at PC
+
+            gb.ld(0xF8);
+
+            assert_eq!(gb.cpu.reg16(Reg16::HL), hl, "LD HL,SP{:+} with SP={:04X}", offset as i8, sp);
+            assert_eq!(gb.cpu.reg8(Reg8::F), flags, "flags of LD HL,SP{:+} with SP={:04X}", offset as i8, sp);
+            assert_eq!(gb.cpu.stack_pointer, sp);
+        }
+    }
+
+    //
+    // LD SP,HL
+    //
+    #[test]
+    fn test_ld_sp_hl() {
+        let mut gb = Gameboy::new();
+        gb.cpu.write_reg16(Reg16::HL, 0xDFF0);
+
+        gb.ld(0xF9);
+
+        assert_eq!(gb.cpu.stack_pointer, 0xDFF0);
+    }
+
     // You’d then continue with LD A,(nn), LD (nn),A, LD A,(C), LD (C),A, etc.
 }
\ No newline at end of file
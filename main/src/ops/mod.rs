@@ -1,4 +1,6 @@
 
+mod alu;
+mod alu_tests;
 pub mod ld;
 mod ld_tests;
 mod xor;
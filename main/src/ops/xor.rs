@@ -1,5 +1,5 @@
 use crate::gameboy::Gameboy;
-use crate::cpu::{Reg8, Reg16, Gbz80};
+use crate::cpu::{Gbz80, Reg8, Reg16};
 
 impl Gameboy {
     pub fn xor(&mut self, opcode: u8) {
@@ -17,6 +17,7 @@ impl Gameboy {
         let value = self.cpu.reg8(Reg8::A) ^ source_value;
         
         self.cpu.set_flags(value == 0, false, false);
+        self.cpu.set_flag(Gbz80::FLAG_C, false);
 
         self.cpu.write_reg8(Reg8::A, value);
     }
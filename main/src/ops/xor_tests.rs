@@ -1,5 +1,5 @@
 #[cfg(test)]
-mod ld_tests {
+mod tests {
     use crate::cpu::{Reg8, Reg16};
     use crate::gameboy::Gameboy;
     fn xor_opcode(src: Reg8) -> u8 {
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+// How CGB RGB555 colours are turned into 8-bit RGB for display.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColourCorrection {
+    // Each 5-bit channel scaled straight up to 8 bits.
+    Raw,
+    // Mixes the channels the way the CGB's LCD bleeds them into each other,
+    // which also pulls the brightest colours down.
+    GbcLcd,
+    // Raises each channel to the given power, darkening the midtones.
+    Gamma(f32),
+}
+
+impl ColourCorrection {
+    pub fn to_rgb(self, colour: u16) -> [u8; 3] {
+        let r = (colour & 0x1F) as u32;
+        let g = ((colour >> 5) & 0x1F) as u32;
+        let b = ((colour >> 10) & 0x1F) as u32;
+
+        match self {
+            ColourCorrection::Raw => [expand(r), expand(g), expand(b)],
+            ColourCorrection::GbcLcd => {
+                let mix = |value: u32| (value.min(960) >> 2) as u8;
+                [
+                    mix(r * 26 + g * 4 + b * 2),
+                    mix(g * 24 + b * 8),
+                    mix(r * 6 + g * 4 + b * 22),
+                ]
+            }
+            ColourCorrection::Gamma(gamma) => {
+                let adjust = |value: u32| ((value as f32 / 31.0).powf(gamma) * 255.0).round() as u8;
+                [adjust(r), adjust(g), adjust(b)]
+            }
+        }
+    }
+}
+
+impl FromStr for ColourCorrection {
+    type Err = String;
+
+    // "raw", "lcd" or "gamma", optionally with an exponent as in "gamma:1.8".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "raw" => Ok(ColourCorrection::Raw),
+            None if s == "lcd" => Ok(ColourCorrection::GbcLcd),
+            None if s == "gamma" => Ok(ColourCorrection::Gamma(DEFAULT_GAMMA)),
+            Some(("gamma", exponent)) => exponent
+                .parse()
+                .map(ColourCorrection::Gamma)
+                .map_err(|_| format!("invalid gamma '{}'", exponent)),
+            _ => Err(format!("unknown colour correction '{}'", s)),
+        }
+    }
+}
+
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+// The four shades a DMG framebuffer is drawn in, lightest first.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DmgPalette(pub [[u8; 3]; 4]);
+
+impl DmgPalette {
+    pub const GREYS: DmgPalette =
+        DmgPalette([[0xFF, 0xFF, 0xFF], [0xAA, 0xAA, 0xAA], [0x55, 0x55, 0x55], [0x00, 0x00, 0x00]]);
+    pub const CLASSIC_GREEN: DmgPalette =
+        DmgPalette([[0x9B, 0xBC, 0x0F], [0x8B, 0xAC, 0x0F], [0x30, 0x62, 0x30], [0x0F, 0x38, 0x0F]]);
+    pub const POCKET: DmgPalette =
+        DmgPalette([[0xC4, 0xCF, 0xA1], [0x8B, 0x95, 0x6D], [0x4D, 0x53, 0x3C], [0x1F, 0x1F, 0x1F]]);
+
+    pub fn shade(&self, shade: u16) -> [u8; 3] {
+        self.0[shade as usize & 0x03]
+    }
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        DmgPalette::GREYS
+    }
+}
+
+impl FromStr for DmgPalette {
+    type Err = String;
+
+    // A preset name, or four RRGGBB colours separated by commas.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grey" | "greys" => return Ok(DmgPalette::GREYS),
+            "green" => return Ok(DmgPalette::CLASSIC_GREEN),
+            "pocket" => return Ok(DmgPalette::POCKET),
+            _ => {}
+        }
+
+        let colours: Vec<&str> = s.split(',').collect();
+        if colours.len() != 4 {
+            return Err(format!("expected a preset or four colours, got '{}'", s));
+        }
+
+        let mut shades = [[0; 3]; 4];
+        for (shade, colour) in shades.iter_mut().zip(colours) {
+            let colour = colour.trim().trim_start_matches('#');
+            let value = match u32::from_str_radix(colour, 16) {
+                Ok(value) if colour.len() == 6 => value,
+                _ => return Err(format!("invalid colour '{}'", colour)),
+            };
+            *shade = [(value >> 16) as u8, (value >> 8) as u8, value as u8];
+        }
+        Ok(DmgPalette(shades))
+    }
+}
+
+fn expand(channel: u32) -> u8 {
+    ((channel << 3) | (channel >> 2)) as u8
+}
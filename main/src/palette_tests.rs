@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use crate::memory::GbMemory;
+    use crate::palette::*;
+    use crate::ppu::{BCPD, BCPS, LCDC};
+
+    #[test]
+    fn test_raw_expands_channels() {
+        assert_eq!(ColourCorrection::Raw.to_rgb(0x7FFF), [0xFF, 0xFF, 0xFF]);
+        assert_eq!(ColourCorrection::Raw.to_rgb(0x001F), [0xFF, 0x00, 0x00]);
+        assert_eq!(ColourCorrection::Raw.to_rgb(0x0210), [0x84, 0x84, 0x00]);
+    }
+
+    #[test]
+    fn test_lcd_correction_bleeds_and_dims() {
+        assert_eq!(ColourCorrection::GbcLcd.to_rgb(0x0000), [0x00, 0x00, 0x00]);
+        assert_eq!(ColourCorrection::GbcLcd.to_rgb(0x7FFF), [240, 240, 240]);
+
+        // Pure red picks up some blue on the LCD
+        let [r, g, b] = ColourCorrection::GbcLcd.to_rgb(0x001F);
+        assert_eq!(g, 0);
+        assert!(b > 0 && b < r);
+    }
+
+    #[test]
+    fn test_gamma_darkens_midtones() {
+        let gamma = ColourCorrection::Gamma(DEFAULT_GAMMA);
+        assert_eq!(gamma.to_rgb(0x7FFF), [0xFF, 0xFF, 0xFF]);
+        assert_eq!(gamma.to_rgb(0x0000), [0x00, 0x00, 0x00]);
+
+        let [mid, _, _] = gamma.to_rgb(0x0010);
+        assert!(mid < ColourCorrection::Raw.to_rgb(0x0010)[0] / 2);
+    }
+
+    #[test]
+    fn test_parse_colour_correction() {
+        assert_eq!("raw".parse(), Ok(ColourCorrection::Raw));
+        assert_eq!("lcd".parse(), Ok(ColourCorrection::GbcLcd));
+        assert_eq!("gamma".parse(), Ok(ColourCorrection::Gamma(DEFAULT_GAMMA)));
+        assert_eq!("gamma:1.5".parse(), Ok(ColourCorrection::Gamma(1.5)));
+        assert!("gamma:x".parse::<ColourCorrection>().is_err());
+        assert!("vivid".parse::<ColourCorrection>().is_err());
+    }
+
+    #[test]
+    fn test_parse_dmg_palette() {
+        assert_eq!("green".parse(), Ok(DmgPalette::CLASSIC_GREEN));
+        assert_eq!("pocket".parse(), Ok(DmgPalette::POCKET));
+        assert_eq!(
+            "#E0F8D0,88c070,346856,081820".parse(),
+            Ok(DmgPalette([[0xE0, 0xF8, 0xD0], [0x88, 0xC0, 0x70], [0x34, 0x68, 0x56], [0x08, 0x18, 0x20]]))
+        );
+        assert!("e0f8d0,88c070,346856".parse::<DmgPalette>().is_err());
+        assert!("e0f8d0,88c070,346856,08182".parse::<DmgPalette>().is_err());
+    }
+
+    #[test]
+    fn test_framebuffer_uses_dmg_palette() {
+        let mut memory = GbMemory::new();
+        memory.ppu.set_dmg_palette(DmgPalette::CLASSIC_GREEN);
+        memory.write_u8(LCDC, 0x91);
+        memory.ppu.tick(81);
+
+        assert_eq!(memory.ppu.framebuffer_rgb()[..3], [0x9B, 0xBC, 0x0F]);
+    }
+
+    #[test]
+    fn test_framebuffer_uses_colour_correction() {
+        let mut memory = GbMemory::new();
+        memory.set_cgb(true);
+        memory.write_u8(BCPS, 0x80);
+        memory.write_u8(BCPD, 0xFF);
+        memory.write_u8(BCPD, 0x7F);
+        memory.ppu.set_colour_correction(ColourCorrection::GbcLcd);
+        memory.write_u8(LCDC, 0x91);
+        memory.ppu.tick(81);
+
+        assert_eq!(memory.ppu.framebuffer_rgb()[..3], [240, 240, 240]);
+    }
+}
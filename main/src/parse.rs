@@ -0,0 +1,15 @@
+use num_traits::Num;
+
+// Hex with or without 0x, 0X or $ in front. The debugger, watchpoints and
+// the command line all take numbers this way.
+pub fn parse_hex<T: Num>(s: &str) -> Result<T, String> {
+    let digits = ["0x", "0X", "$"]
+        .iter()
+        .find_map(|prefix| s.strip_prefix(prefix))
+        .unwrap_or(s);
+    T::from_str_radix(digits, 16).map_err(|_| format!("invalid number '{}'", s))
+}
+
+pub fn parse_address(s: &str) -> Result<u16, String> {
+    parse_hex(s).map_err(|_| format!("invalid address '{}'", s))
+}
@@ -0,0 +1,14 @@
+#[cfg(test)]
+mod tests {
+    use crate::parse::*;
+
+    #[test]
+    fn test_parse_hex_prefixes() {
+        for s in ["C0DE", "0xC0DE", "0XC0DE", "$C0DE", "c0de"] {
+            assert_eq!(parse_address(s), Ok(0xC0DE), "{}", s);
+        }
+        assert_eq!(parse_hex::<usize>("0x14000"), Ok(0x14000));
+        assert_eq!(parse_address("10000"), Err("invalid address '10000'".to_string()));
+        assert_eq!(parse_address("0x"), Err("invalid address '0x'".to_string()));
+    }
+}
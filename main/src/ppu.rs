@@ -0,0 +1,506 @@
+use crate::memory::{INT_STAT, INT_VBLANK};
+use crate::palette::{ColourCorrection, DmgPalette};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const LCDC: u16 = 0xFF40;
+pub const STAT: u16 = 0xFF41;
+pub const SCY: u16 = 0xFF42;
+pub const SCX: u16 = 0xFF43;
+pub const LY: u16 = 0xFF44;
+pub const LYC: u16 = 0xFF45;
+pub const BGP: u16 = 0xFF47;
+pub const OBP0: u16 = 0xFF48;
+pub const OBP1: u16 = 0xFF49;
+pub const WY: u16 = 0xFF4A;
+pub const WX: u16 = 0xFF4B;
+pub const VBK: u16 = 0xFF4F;
+pub const BCPS: u16 = 0xFF68;
+pub const BCPD: u16 = 0xFF69;
+pub const OCPS: u16 = 0xFF6A;
+pub const OCPD: u16 = 0xFF6B;
+pub const OPRI: u16 = 0xFF6C;
+
+const LCDC_BG_ENABLE: u8 = 1 << 0;
+const LCDC_OBJ_ENABLE: u8 = 1 << 1;
+const LCDC_OBJ_SIZE: u8 = 1 << 2;
+const LCDC_BG_MAP: u8 = 1 << 3;
+const LCDC_TILE_DATA: u8 = 1 << 4;
+const LCDC_WINDOW_ENABLE: u8 = 1 << 5;
+const LCDC_WINDOW_MAP: u8 = 1 << 6;
+const LCDC_LCD_ENABLE: u8 = 1 << 7;
+
+const STAT_LYC_INT: u8 = 1 << 6;
+const STAT_MODE2_INT: u8 = 1 << 5;
+const STAT_MODE1_INT: u8 = 1 << 4;
+const STAT_MODE0_INT: u8 = 1 << 3;
+
+// Shared by BG map attributes (VRAM bank 1) and OAM flags.
+const ATTR_PRIORITY: u8 = 1 << 7;
+const ATTR_Y_FLIP: u8 = 1 << 6;
+const ATTR_X_FLIP: u8 = 1 << 5;
+const ATTR_DMG_PALETTE: u8 = 1 << 4;
+const ATTR_VRAM_BANK: u8 = 1 << 3;
+
+const DOTS_PER_LINE: u32 = 456;
+const OAM_SCAN_DOTS: u32 = 80;
+const DRAWING_DOTS: u32 = 172;
+const LINES_PER_FRAME: u8 = 154;
+const SPRITES_PER_LINE: usize = 10;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+// Scanline renderer for both the DMG and the CGB. The framebuffer holds a
+// shade (0-3) per pixel on the DMG and a raw RGB555 colour on the CGB.
+pub struct Ppu {
+    cgb: bool,
+    vram: Box<[u8]>,
+    vram_bank: u8,
+    pub oam: [u8; 0xA0],
+    lcdc: u8,
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    bg_palette_ram: [u8; 64],
+    bcps: u8,
+    obj_palette_ram: [u8; 64],
+    ocps: u8,
+    opri: u8,
+    mode: Mode,
+    dot: u32,
+    window_line: u8,
+    stat_line: bool,
+    hblank_started: bool,
+    framebuffer: Vec<u16>,
+    frame_ready: bool,
+    colour_correction: ColourCorrection,
+    dmg_palette: DmgPalette,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu {
+            cgb: false,
+            vram: vec![0; 2 * 0x2000].into_boxed_slice(),
+            vram_bank: 0,
+            oam: [0; 0xA0],
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            bg_palette_ram: [0xFF; 64],
+            bcps: 0,
+            obj_palette_ram: [0xFF; 64],
+            ocps: 0,
+            opri: 1,
+            mode: Mode::HBlank,
+            dot: 0,
+            window_line: 0,
+            stat_line: false,
+            hblank_started: false,
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+            colour_correction: ColourCorrection::Raw,
+            dmg_palette: DmgPalette::GREYS,
+        }
+    }
+
+    pub fn set_cgb(&mut self, cgb: bool) {
+        self.cgb = cgb;
+        self.opri = if cgb { 0 } else { 1 };
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        self.cgb
+    }
+
+    pub fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCDC_LCD_ENABLE != 0
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn framebuffer(&self) -> &[u16] {
+        &self.framebuffer
+    }
+
+    pub fn colour_correction(&self) -> ColourCorrection {
+        self.colour_correction
+    }
+
+    pub fn set_colour_correction(&mut self, correction: ColourCorrection) {
+        self.colour_correction = correction;
+    }
+
+    pub fn dmg_palette(&self) -> DmgPalette {
+        self.dmg_palette
+    }
+
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.dmg_palette = palette;
+    }
+
+    // The current frame as packed 8-bit RGB, through the colour correction on
+    // the CGB and the DMG palette otherwise.
+    pub fn framebuffer_rgb(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.framebuffer.len() * 3);
+        for &pixel in &self.framebuffer {
+            if self.cgb {
+                rgb.extend_from_slice(&self.colour_correction.to_rgb(pixel));
+            } else {
+                rgb.extend_from_slice(&self.dmg_palette.shade(pixel));
+            }
+        }
+        rgb
+    }
+
+    // True once per frame, when the PPU enters VBlank.
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    // True once per visible line, when the PPU enters HBlank.
+    pub fn take_hblank(&mut self) -> bool {
+        std::mem::take(&mut self.hblank_started)
+    }
+
+    pub fn read_vram(&self, address: u16) -> u8 {
+        self.vram[self.vram_bank as usize * 0x2000 + (address as usize - 0x8000)]
+    }
+
+    pub fn write_vram(&mut self, address: u16, value: u8) {
+        self.vram[self.vram_bank as usize * 0x2000 + (address as usize - 0x8000)] = value;
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            LCDC => self.lcdc,
+            STAT => {
+                let coincidence = ((self.ly == self.lyc) as u8) << 2;
+                0x80 | self.stat | coincidence | self.mode as u8
+            }
+            SCY => self.scy,
+            SCX => self.scx,
+            LY => self.ly,
+            LYC => self.lyc,
+            BGP => self.bgp,
+            OBP0 => self.obp0,
+            OBP1 => self.obp1,
+            WY => self.wy,
+            WX => self.wx,
+            VBK if self.cgb => 0xFE | self.vram_bank,
+            BCPS if self.cgb => self.bcps | 0x40,
+            BCPD if self.cgb => self.bg_palette_ram[(self.bcps & 0x3F) as usize],
+            OCPS if self.cgb => self.ocps | 0x40,
+            OCPD if self.cgb => self.obj_palette_ram[(self.ocps & 0x3F) as usize],
+            OPRI if self.cgb => 0xFE | self.opri,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            LCDC => {
+                if value & LCDC_LCD_ENABLE == 0 && self.lcdc & LCDC_LCD_ENABLE != 0 {
+                    self.ly = 0;
+                    self.dot = 0;
+                    self.mode = Mode::HBlank;
+                    self.window_line = 0;
+                }
+                if value & LCDC_LCD_ENABLE != 0 && self.lcdc & LCDC_LCD_ENABLE == 0 {
+                    self.mode = Mode::OamScan;
+                }
+                self.lcdc = value;
+            }
+            STAT => self.stat = value & 0x78,
+            SCY => self.scy = value,
+            SCX => self.scx = value,
+            LYC => self.lyc = value,
+            BGP => self.bgp = value,
+            OBP0 => self.obp0 = value,
+            OBP1 => self.obp1 = value,
+            WY => self.wy = value,
+            WX => self.wx = value,
+            VBK if self.cgb => self.vram_bank = value & 0x01,
+            BCPS if self.cgb => self.bcps = value & 0xBF,
+            BCPD if self.cgb => {
+                self.bg_palette_ram[(self.bcps & 0x3F) as usize] = value;
+                self.bcps = auto_increment(self.bcps);
+            }
+            OCPS if self.cgb => self.ocps = value & 0xBF,
+            OCPD if self.cgb => {
+                self.obj_palette_ram[(self.ocps & 0x3F) as usize] = value;
+                self.ocps = auto_increment(self.ocps);
+            }
+            OPRI if self.cgb => self.opri = value & 0x01,
+            _ => {}
+        }
+    }
+
+    // Advances by `dots` (normal-speed cycles) and returns the interrupts raised.
+    pub fn tick(&mut self, dots: u32) -> u8 {
+        if self.lcdc & LCDC_LCD_ENABLE == 0 {
+            return 0;
+        }
+
+        let mut interrupts = 0;
+        for _ in 0..dots {
+            self.dot += 1;
+            if self.dot == DOTS_PER_LINE {
+                self.dot = 0;
+                self.ly = (self.ly + 1) % LINES_PER_FRAME;
+                if self.ly == 0 {
+                    self.window_line = 0;
+                }
+            }
+
+            let mode = if self.ly >= SCREEN_HEIGHT as u8 {
+                Mode::VBlank
+            } else if self.dot < OAM_SCAN_DOTS {
+                Mode::OamScan
+            } else if self.dot < OAM_SCAN_DOTS + DRAWING_DOTS {
+                Mode::Drawing
+            } else {
+                Mode::HBlank
+            };
+
+            if mode != self.mode {
+                self.mode = mode;
+                match mode {
+                    Mode::Drawing => self.render_line(),
+                    Mode::HBlank => self.hblank_started = true,
+                    Mode::VBlank => {
+                        self.frame_ready = true;
+                        interrupts |= INT_VBLANK;
+                    }
+                    _ => {}
+                }
+            }
+
+            if self.update_stat_line() {
+                interrupts |= INT_STAT;
+            }
+        }
+        interrupts
+    }
+
+    // STAT fires on the rising edge of the OR of all enabled sources.
+    fn update_stat_line(&mut self) -> bool {
+        let line = (self.stat & STAT_LYC_INT != 0 && self.ly == self.lyc)
+            || (self.stat & STAT_MODE0_INT != 0 && self.mode == Mode::HBlank)
+            || (self.stat & STAT_MODE1_INT != 0 && self.mode == Mode::VBlank)
+            || (self.stat & STAT_MODE2_INT != 0 && self.mode == Mode::OamScan);
+
+        let rising = line && !self.stat_line;
+        self.stat_line = line;
+        rising
+    }
+
+    fn vram_byte(&self, bank: usize, address: u16) -> u8 {
+        self.vram[bank * 0x2000 + (address as usize & 0x1FFF)]
+    }
+
+    // Colour index (0-3) of one pixel of a tile.
+    fn tile_pixel(&self, bank: usize, tile_address: u16, x: u8, y: u8) -> u8 {
+        let low = self.vram_byte(bank, tile_address + y as u16 * 2);
+        let high = self.vram_byte(bank, tile_address + y as u16 * 2 + 1);
+        let bit = 7 - x;
+        (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+    }
+
+    fn tile_address(&self, tile: u8) -> u16 {
+        if self.lcdc & LCDC_TILE_DATA != 0 {
+            0x8000 + tile as u16 * 16
+        } else {
+            (0x9000i32 + (tile as i8) as i32 * 16) as u16
+        }
+    }
+
+    fn render_line(&mut self) {
+        let ly = self.ly;
+        let mut bg_colour = [0u8; SCREEN_WIDTH];
+        let mut bg_attributes = [0u8; SCREEN_WIDTH];
+        let mut line = [0u16; SCREEN_WIDTH];
+
+        // On the DMG LCDC bit 0 blanks the background and window. On the CGB
+        // they are always drawn and the bit strips them of their priority.
+        let bg_visible = self.cgb || self.lcdc & LCDC_BG_ENABLE != 0;
+        let window_visible = bg_visible
+            && self.lcdc & LCDC_WINDOW_ENABLE != 0
+            && self.wy <= ly
+            && self.wx <= 166;
+        let mut window_drawn = false;
+
+        for x in 0..SCREEN_WIDTH as u8 {
+            if !bg_visible {
+                line[x as usize] = self.dmg_shade(self.bgp, 0);
+                continue;
+            }
+
+            let in_window = window_visible && x as u16 + 7 >= self.wx as u16;
+            let (map, map_x, map_y) = if in_window {
+                window_drawn = true;
+                let map = if self.lcdc & LCDC_WINDOW_MAP != 0 { 0x9C00 } else { 0x9800 };
+                (map, (x as u16 + 7 - self.wx as u16) as u8, self.window_line)
+            } else {
+                let map = if self.lcdc & LCDC_BG_MAP != 0 { 0x9C00 } else { 0x9800 };
+                (map, x.wrapping_add(self.scx), ly.wrapping_add(self.scy))
+            };
+
+            let map_address = map + (map_y as u16 / 8) * 32 + map_x as u16 / 8;
+            let tile = self.vram_byte(0, map_address);
+            let attributes = if self.cgb { self.vram_byte(1, map_address) } else { 0 };
+
+            let mut tile_x = map_x % 8;
+            let mut tile_y = map_y % 8;
+            if attributes & ATTR_X_FLIP != 0 {
+                tile_x = 7 - tile_x;
+            }
+            if attributes & ATTR_Y_FLIP != 0 {
+                tile_y = 7 - tile_y;
+            }
+            let bank = (attributes & ATTR_VRAM_BANK != 0) as usize;
+            let colour = self.tile_pixel(bank, self.tile_address(tile), tile_x, tile_y);
+
+            bg_colour[x as usize] = colour;
+            bg_attributes[x as usize] = attributes;
+            line[x as usize] = if self.cgb {
+                cgb_colour(&self.bg_palette_ram, attributes & 0x07, colour)
+            } else {
+                self.dmg_shade(self.bgp, colour)
+            };
+        }
+
+        if window_drawn {
+            self.window_line += 1;
+        }
+
+        if self.lcdc & LCDC_OBJ_ENABLE != 0 {
+            self.render_sprites(&mut line, &bg_colour, &bg_attributes);
+        }
+
+        let start = ly as usize * SCREEN_WIDTH;
+        self.framebuffer[start..start + SCREEN_WIDTH].copy_from_slice(&line);
+    }
+
+    fn render_sprites(&self, line: &mut [u16; SCREEN_WIDTH], bg_colour: &[u8], bg_attributes: &[u8]) {
+        let ly = self.ly as i16;
+        let height = if self.lcdc & LCDC_OBJ_SIZE != 0 { 16 } else { 8 };
+
+        // The first ten sprites in OAM that touch this line.
+        let mut sprites: Vec<usize> = (0..40)
+            .filter(|&i| {
+                let y = self.oam[i * 4] as i16 - 16;
+                ly >= y && ly < y + height
+            })
+            .take(SPRITES_PER_LINE)
+            .collect();
+
+        // CGB priority goes by OAM index alone; the DMG (and OPRI bit 0) lets
+        // the leftmost sprite win, falling back to OAM index on a tie.
+        if self.opri & 0x01 != 0 {
+            sprites.sort_by_key(|&i| (self.oam[i * 4 + 1], i));
+        }
+
+        for x in 0..SCREEN_WIDTH {
+            for &i in &sprites {
+                let sprite_x = self.oam[i * 4 + 1] as i16 - 8;
+                if (x as i16) < sprite_x || x as i16 >= sprite_x + 8 {
+                    continue;
+                }
+
+                let y = self.oam[i * 4] as i16 - 16;
+                let mut tile = self.oam[i * 4 + 2];
+                let flags = self.oam[i * 4 + 3];
+
+                let mut tile_x = (x as i16 - sprite_x) as u8;
+                let mut tile_y = (ly - y) as u8;
+                if flags & ATTR_X_FLIP != 0 {
+                    tile_x = 7 - tile_x;
+                }
+                if flags & ATTR_Y_FLIP != 0 {
+                    tile_y = height as u8 - 1 - tile_y;
+                }
+                if height == 16 {
+                    tile = (tile & 0xFE) + tile_y / 8;
+                    tile_y %= 8;
+                }
+
+                let bank = (self.cgb && flags & ATTR_VRAM_BANK != 0) as usize;
+                let colour = self.tile_pixel(bank, 0x8000 + tile as u16 * 16, tile_x, tile_y);
+                if colour == 0 {
+                    continue;
+                }
+
+                if self.bg_has_priority(flags, bg_colour[x], bg_attributes[x]) {
+                    break;
+                }
+
+                line[x] = if self.cgb {
+                    cgb_colour(&self.obj_palette_ram, flags & 0x07, colour)
+                } else {
+                    let palette = if flags & ATTR_DMG_PALETTE != 0 { self.obp1 } else { self.obp0 };
+                    self.dmg_shade(palette, colour)
+                };
+                break;
+            }
+        }
+    }
+
+    fn bg_has_priority(&self, sprite_flags: u8, bg_colour: u8, bg_attributes: u8) -> bool {
+        if bg_colour == 0 {
+            return false;
+        }
+        if self.cgb && self.lcdc & LCDC_BG_ENABLE == 0 {
+            return false;
+        }
+        sprite_flags & ATTR_PRIORITY != 0 || bg_attributes & ATTR_PRIORITY != 0
+    }
+
+    fn dmg_shade(&self, palette: u8, colour: u8) -> u16 {
+        ((palette >> (colour * 2)) & 0x03) as u16
+    }
+}
+
+fn cgb_colour(palette_ram: &[u8; 64], palette: u8, colour: u8) -> u16 {
+    let index = (palette as usize * 4 + colour as usize) * 2;
+    u16::from_le_bytes([palette_ram[index], palette_ram[index + 1]]) & 0x7FFF
+}
+
+// BCPS/OCPS bit 7 moves the index on after each data write.
+fn auto_increment(spec: u8) -> u8 {
+    if spec & 0x80 != 0 {
+        0x80 | ((spec + 1) & 0x3F)
+    } else {
+        spec
+    }
+}
@@ -0,0 +1,207 @@
+#[cfg(test)]
+mod tests {
+    use crate::cartridge::Cartridge;
+    use crate::gameboy::{Gameboy, Model};
+    use crate::memory::{GbMemory, KEY1, SVBK};
+    use crate::ppu::*;
+
+    fn cgb_memory() -> GbMemory {
+        let mut memory = GbMemory::new();
+        memory.set_cgb(true);
+        memory
+    }
+
+    // Writes one RGB555 colour through BCPD or OCPD with auto-increment.
+    fn write_colour(memory: &mut GbMemory, spec: u16, palette: u8, colour: u8, rgb: u16) {
+        memory.write_u8(spec, 0x80 | ((palette * 4 + colour) * 2));
+        let [low, high] = rgb.to_le_bytes();
+        memory.write_u8(spec + 1, low);
+        memory.write_u8(spec + 1, high);
+    }
+
+    // Tile data where every pixel of every row uses `colour`.
+    fn fill_tile(memory: &mut GbMemory, address: u16, colour: u8) {
+        for row in 0..8 {
+            memory.write_u8(address + row * 2, if colour & 1 != 0 { 0xFF } else { 0x00 });
+            memory.write_u8(address + row * 2 + 1, if colour & 2 != 0 { 0xFF } else { 0x00 });
+        }
+    }
+
+    fn render_first_line(memory: &mut GbMemory) {
+        memory.write_u8(LCDC, 0x93);
+        memory.ppu.tick(81);
+    }
+
+    fn cartridge_with_cgb_flag(flag: u8) -> Cartridge {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = flag;
+        Cartridge::from_bytes(rom).unwrap()
+    }
+
+    #[test]
+    fn test_svbk_switches_wram_on_cgb_only() {
+        let mut memory = cgb_memory();
+        memory.write_u8(SVBK, 2);
+        memory.write_u8(0xD000, 0x22);
+        memory.write_u8(SVBK, 3);
+        memory.write_u8(0xD000, 0x33);
+
+        assert_eq!(memory.read_u8(0xD000), 0x33);
+        memory.write_u8(SVBK, 2);
+        assert_eq!(memory.read_u8(0xD000), 0x22);
+        assert_eq!(memory.read_u8(0xF000), 0x22);
+
+        // Bank 0 selects bank 1
+        memory.write_u8(SVBK, 0);
+        assert_eq!(memory.read_u8(SVBK), 0xF9);
+
+        let mut memory = GbMemory::new();
+        memory.write_u8(0xD000, 0x11);
+        memory.write_u8(SVBK, 2);
+        assert_eq!(memory.read_u8(0xD000), 0x11);
+    }
+
+    #[test]
+    fn test_vbk_switches_vram_bank() {
+        let mut memory = cgb_memory();
+        memory.write_u8(0x8000, 0xAA);
+        memory.write_u8(VBK, 1);
+        memory.write_u8(0x8000, 0xBB);
+
+        assert_eq!(memory.read_u8(VBK), 0xFF);
+        assert_eq!(memory.read_u8(0x8000), 0xBB);
+        memory.write_u8(VBK, 0);
+        assert_eq!(memory.read_u8(0x8000), 0xAA);
+    }
+
+    #[test]
+    fn test_palette_data_auto_increments() {
+        let mut memory = cgb_memory();
+        memory.write_u8(BCPS, 0x80 | 0x3E);
+        memory.write_u8(BCPD, 0x12);
+        memory.write_u8(BCPD, 0x34);
+
+        // The index wraps and keeps the increment flag
+        assert_eq!(memory.read_u8(BCPS), 0xC0);
+
+        memory.write_u8(BCPS, 0x3E);
+        assert_eq!(memory.read_u8(BCPD), 0x12);
+        memory.write_u8(BCPD, 0x56);
+        assert_eq!(memory.read_u8(BCPS), 0x7E);
+        assert_eq!(memory.read_u8(BCPD), 0x56);
+    }
+
+    #[test]
+    fn test_stop_switches_speed_when_armed() {
+        let mut gameboy = Gameboy::new();
+        gameboy.set_model(Model::Cgb);
+
+        // STOP without KEY1 armed leaves the speed alone
+        gameboy.memory.write_u8(0x0000, 0x10);
+        gameboy.execute_next();
+        assert!(!gameboy.memory.double_speed());
+        assert_eq!(gameboy.memory.read_u8(KEY1), 0x7E);
+
+        gameboy.memory.write_u8(KEY1, 0x01);
+        assert_eq!(gameboy.memory.read_u8(KEY1), 0x7F);
+
+        gameboy.cpu.program_counter = 0;
+        gameboy.execute_next();
+        assert!(gameboy.memory.double_speed());
+        assert_eq!(gameboy.memory.read_u8(KEY1), 0xFE);
+    }
+
+    #[test]
+    fn test_key1_ignored_on_dmg() {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.write_u8(KEY1, 0x01);
+        gameboy.memory.write_u8(0x0000, 0x10);
+        gameboy.execute_next();
+
+        assert!(!gameboy.memory.double_speed());
+    }
+
+    #[test]
+    fn test_cgb_background_attributes() {
+        let mut memory = cgb_memory();
+
+        // Tile 1 in bank 1: left half colour 1, right half colour 2
+        memory.write_u8(VBK, 1);
+        for row in 0..8 {
+            memory.write_u8(0x8010 + row * 2, 0xF0);
+            memory.write_u8(0x8010 + row * 2 + 1, 0x0F);
+        }
+        // First map entry: palette 2, tile from bank 1, flipped horizontally
+        memory.write_u8(0x9800, 0x02 | 0x08 | 0x20);
+        memory.write_u8(VBK, 0);
+        memory.write_u8(0x9800, 0x01);
+
+        write_colour(&mut memory, BCPS, 2, 1, 0x001F);
+        write_colour(&mut memory, BCPS, 2, 2, 0x7C00);
+
+        render_first_line(&mut memory);
+
+        let line = &memory.ppu.framebuffer()[..8];
+        assert_eq!(line[0], 0x7C00);
+        assert_eq!(line[7], 0x001F);
+    }
+
+    // Two overlapping sprites: OAM entry 0 sits to the right of entry 1.
+    fn overlapping_sprites(memory: &mut GbMemory) {
+        fill_tile(memory, 0x8010, 1);
+        fill_tile(memory, 0x8020, 2);
+        memory.ppu.oam[0..4].copy_from_slice(&[16, 12, 1, 0]);
+        memory.ppu.oam[4..8].copy_from_slice(&[16, 8, 2, 0]);
+    }
+
+    #[test]
+    fn test_dmg_sprite_priority_by_x() {
+        let mut memory = GbMemory::new();
+        memory.write_u8(OBP0, 0xE4);
+        overlapping_sprites(&mut memory);
+
+        render_first_line(&mut memory);
+
+        // The leftmost sprite wins where they overlap
+        assert_eq!(memory.ppu.framebuffer()[5], 2);
+    }
+
+    #[test]
+    fn test_cgb_sprite_priority_by_oam_index() {
+        let mut memory = cgb_memory();
+        write_colour(&mut memory, OCPS, 0, 1, 0x001F);
+        write_colour(&mut memory, OCPS, 0, 2, 0x03E0);
+        overlapping_sprites(&mut memory);
+
+        render_first_line(&mut memory);
+
+        assert_eq!(memory.ppu.framebuffer()[2], 0x03E0);
+        assert_eq!(memory.ppu.framebuffer()[5], 0x001F);
+    }
+
+    #[test]
+    fn test_model_from_cartridge_header() {
+        let mut gameboy = Gameboy::new();
+        assert_eq!(gameboy.insert_cartridge(cartridge_with_cgb_flag(0x80), None), Model::Cgb);
+        assert!(gameboy.memory.ppu.is_cgb());
+
+        let mut gameboy = Gameboy::new();
+        assert_eq!(gameboy.insert_cartridge(cartridge_with_cgb_flag(0x00), None), Model::Dmg);
+
+        let mut gameboy = Gameboy::new();
+        let model = gameboy.insert_cartridge(cartridge_with_cgb_flag(0xC0), Some(Model::Dmg));
+        assert_eq!(model, Model::Dmg);
+        assert!(!gameboy.memory.ppu.is_cgb());
+    }
+
+    #[test]
+    fn test_skip_boot_rom_reports_model_in_a() {
+        let mut gameboy = Gameboy::new();
+        gameboy.set_model(Model::Cgb);
+        gameboy.skip_boot_rom();
+
+        assert_eq!(gameboy.cpu.af() >> 8, 0x11);
+        assert_eq!(gameboy.cpu.program_counter, 0x0100);
+        assert_eq!(gameboy.memory.read_u8(LCDC), 0x91);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::serial::SerialDevice;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+pub const COMMAND_INIT: u8 = 0x01;
+pub const COMMAND_PRINT: u8 = 0x02;
+pub const COMMAND_DATA: u8 = 0x04;
+pub const COMMAND_BREAK: u8 = 0x08;
+pub const COMMAND_STATUS: u8 = 0x0F;
+
+pub const STATUS_CHECKSUM_ERROR: u8 = 1 << 0;
+pub const STATUS_PRINTING: u8 = 1 << 1;
+pub const STATUS_IMAGE_FULL: u8 = 1 << 2;
+pub const STATUS_UNPROCESSED: u8 = 1 << 3;
+pub const STATUS_PACKET_ERROR: u8 = 1 << 4;
+
+// Reply to the first of the two trailing bytes of every packet.
+const DEVICE_ID: u8 = 0x81;
+
+pub const PRINT_WIDTH: usize = 160;
+// One data packet carries two rows of 20 tiles.
+const BAND_BYTES: usize = 640;
+const BAND_LINES: usize = 16;
+// The printer has 8KB of RAM, enough for 9 bands (a full 160x144 screen).
+const BUFFER_BYTES: usize = 9 * BAND_BYTES;
+// Blank pixel rows fed per unit of margin.
+const MARGIN_LINES: usize = 8;
+// Status polls answered with "printing" after a print command.
+const PRINT_BUSY_POLLS: u8 = 4;
+
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum PacketState {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+// Game Boy Printer on the link port. Printed strips are stacked into a page
+// which is written out as a PNG once the game feeds a bottom margin.
+pub struct GbPrinter {
+    output_dir: PathBuf,
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    packet: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+    status: u8,
+    busy_polls: u8,
+    buffer: Vec<u8>,
+    page: Vec<u8>,
+    pages_printed: Vec<PathBuf>,
+    // Why the last page couldn't be written, until it's taken.
+    write_error: Option<std::io::Error>,
+}
+
+impl GbPrinter {
+    pub fn new<P: AsRef<Path>>(output_dir: P) -> Self {
+        GbPrinter {
+            output_dir: output_dir.as_ref().to_path_buf(),
+            state: PacketState::Magic1,
+            command: 0,
+            compressed: false,
+            length: 0,
+            packet: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            status: 0,
+            busy_polls: 0,
+            buffer: Vec::with_capacity(BUFFER_BYTES),
+            page: Vec::new(),
+            pages_printed: Vec::new(),
+            write_error: None,
+        }
+    }
+
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+
+    pub fn pages_printed(&self) -> &[PathBuf] {
+        &self.pages_printed
+    }
+
+    // The error from the last page a print command failed to write. The page
+    // is kept, so a later print or `flush_page` tries again.
+    pub fn take_write_error(&mut self) -> Option<std::io::Error> {
+        self.write_error.take()
+    }
+
+    // Height in pixels of the page that has been printed but not yet cut off.
+    pub fn pending_lines(&self) -> usize {
+        self.page.len() / PRINT_WIDTH
+    }
+
+    // Writes whatever is on the current page to disk, even without a bottom margin.
+    pub fn flush_page(&mut self) -> std::io::Result<Option<PathBuf>> {
+        if self.page.is_empty() {
+            return Ok(None);
+        }
+
+        let path = self
+            .output_dir
+            .join(format!("print_{:04}.png", self.pages_printed.len() + 1));
+        write_page_png(&path, &self.page)?;
+
+        self.page.clear();
+        self.pages_printed.push(path.clone());
+        Ok(Some(path))
+    }
+
+    fn receive(&mut self, byte: u8) {
+        self.state = match self.state {
+            PacketState::Magic1 if byte == 0x88 => PacketState::Magic2,
+            PacketState::Magic1 => PacketState::Magic1,
+            PacketState::Magic2 if byte == 0x33 => PacketState::Command,
+            PacketState::Magic2 => PacketState::Magic1,
+            PacketState::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                self.packet.clear();
+                PacketState::Compression
+            }
+            PacketState::Compression => {
+                self.compressed = byte & 0x01 != 0;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                PacketState::LengthLow
+            }
+            PacketState::LengthLow => {
+                self.length = byte as u16;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                PacketState::LengthHigh
+            }
+            PacketState::LengthHigh => {
+                self.length |= (byte as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.length == 0 {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::Data => {
+                self.packet.push(byte);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.packet.len() == self.length as usize {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::ChecksumLow => {
+                self.received_checksum = byte as u16;
+                PacketState::ChecksumHigh
+            }
+            PacketState::ChecksumHigh => {
+                self.received_checksum |= (byte as u16) << 8;
+                self.process_packet();
+                PacketState::Alive
+            }
+            PacketState::Alive => PacketState::Status,
+            PacketState::Status => PacketState::Magic1,
+        };
+    }
+
+    fn process_packet(&mut self) {
+        if self.received_checksum != self.checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match self.command {
+            COMMAND_INIT => {
+                self.buffer.clear();
+                self.busy_polls = 0;
+                self.status = 0;
+            }
+            COMMAND_DATA => self.receive_data(),
+            COMMAND_PRINT => self.print(),
+            COMMAND_BREAK => {
+                self.buffer.clear();
+                self.busy_polls = 0;
+                self.status &= !(STATUS_PRINTING | STATUS_IMAGE_FULL | STATUS_UNPROCESSED);
+            }
+            COMMAND_STATUS => {
+                if self.busy_polls > 0 {
+                    self.busy_polls -= 1;
+                    if self.busy_polls == 0 {
+                        self.status &= !STATUS_PRINTING;
+                    }
+                }
+            }
+            _ => self.status |= STATUS_PACKET_ERROR,
+        }
+    }
+
+    fn receive_data(&mut self) {
+        // An empty data packet marks the end of the image.
+        if self.packet.is_empty() {
+            self.status |= STATUS_IMAGE_FULL;
+            return;
+        }
+
+        let data = if self.compressed {
+            decompress(&self.packet)
+        } else {
+            std::mem::take(&mut self.packet)
+        };
+
+        let room = BUFFER_BYTES - self.buffer.len();
+        self.buffer.extend_from_slice(&data[..data.len().min(room)]);
+        self.status |= STATUS_UNPROCESSED;
+        if self.buffer.len() == BUFFER_BYTES {
+            self.status |= STATUS_IMAGE_FULL;
+        }
+    }
+
+    fn print(&mut self) {
+        if self.packet.len() < 4 {
+            self.status |= STATUS_PACKET_ERROR;
+            return;
+        }
+
+        // Byte 3 is the exposure, which only affects how dark the thermal paper gets.
+        let margin_before = (self.packet[1] >> 4) as usize;
+        let margin_after = (self.packet[1] & 0x0F) as usize;
+        // A palette of zero is treated as the default 0xE4 by the printer.
+        let palette = match self.packet[2] {
+            0 => 0xE4,
+            palette => palette,
+        };
+
+        self.feed(margin_before);
+        for band in self.buffer.chunks_exact(BAND_BYTES) {
+            render_band(band, palette, &mut self.page);
+        }
+        self.feed(margin_after);
+
+        self.buffer.clear();
+        self.status &= !(STATUS_IMAGE_FULL | STATUS_UNPROCESSED);
+        self.status |= STATUS_PRINTING;
+        self.busy_polls = PRINT_BUSY_POLLS;
+
+        if margin_after > 0
+            && let Err(err) = self.flush_page()
+        {
+            self.write_error = Some(err);
+        }
+    }
+
+    fn feed(&mut self, margin: usize) {
+        let lines = margin * MARGIN_LINES;
+        self.page.resize(self.page.len() + lines * PRINT_WIDTH, SHADES[0]);
+    }
+}
+
+impl SerialDevice for GbPrinter {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let reply = match self.state {
+            PacketState::Alive => DEVICE_ID,
+            PacketState::Status => self.status,
+            _ => 0x00,
+        };
+        self.receive(byte);
+        reply
+    }
+}
+
+// Printer RLE: a control byte with bit 7 set repeats the next byte (n & 0x7F) + 2
+// times, otherwise the next n + 1 bytes are copied as they are.
+pub fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(BAND_BYTES);
+    let mut i = 0;
+
+    while i < data.len() {
+        let control = data[i];
+        i += 1;
+
+        if control & 0x80 != 0 {
+            let count = (control & 0x7F) as usize + 2;
+            if let Some(&value) = data.get(i) {
+                out.resize(out.len() + count, value);
+            }
+            i += 1;
+        } else {
+            let count = control as usize + 1;
+            let end = (i + count).min(data.len());
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+    out
+}
+
+// Turns 40 tiles (two rows of 20) of 2bpp data into 16 lines of grey pixels.
+fn render_band(band: &[u8], palette: u8, page: &mut Vec<u8>) {
+    for line in 0..BAND_LINES {
+        let tile_row = line / 8;
+        let row_in_tile = line % 8;
+
+        for x in 0..PRINT_WIDTH {
+            let tile = tile_row * 20 + x / 8;
+            let offset = tile * 16 + row_in_tile * 2;
+            let bit = 7 - (x % 8);
+
+            let low = (band[offset] >> bit) & 1;
+            let high = (band[offset + 1] >> bit) & 1;
+            let colour = (high << 1) | low;
+            let shade = (palette >> (colour * 2)) & 0x03;
+
+            page.push(SHADES[shade as usize]);
+        }
+    }
+}
+
+fn write_page_png(path: &Path, page: &[u8]) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    let height = page.len() / PRINT_WIDTH;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), PRINT_WIDTH as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(page)?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::Gameboy;
+    use crate::memory::INTERRUPT_FLAG;
+    use crate::printer::*;
+    use crate::serial::{SerialDevice, SB, SC};
+    use crate::testing::TempPath;
+
+    fn packet(command: u8, compressed: bool, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x88, 0x33, command, compressed as u8];
+        bytes.push(data.len() as u8);
+        bytes.push((data.len() >> 8) as u8);
+        bytes.extend_from_slice(data);
+
+        let checksum = bytes[2..]
+            .iter()
+            .fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        bytes.push(checksum as u8);
+        bytes.push((checksum >> 8) as u8);
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        bytes
+    }
+
+    fn send(printer: &mut GbPrinter, bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().map(|&b| printer.exchange(b)).collect()
+    }
+
+    fn output_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("gb_printer_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_status_packet_replies() {
+        let mut printer = GbPrinter::new(std::env::temp_dir());
+
+        let replies = send(&mut printer, &packet(COMMAND_STATUS, false, &[]));
+
+        // Only the two trailing bytes are answered.
+        assert!(replies[..replies.len() - 2].iter().all(|&b| b == 0));
+        assert_eq!(replies[replies.len() - 2], 0x81);
+        assert_eq!(replies[replies.len() - 1], 0x00);
+    }
+
+    #[test]
+    fn test_bad_checksum_sets_error() {
+        let mut printer = GbPrinter::new(std::env::temp_dir());
+        let mut bytes = packet(COMMAND_INIT, false, &[]);
+        let checksum_low = bytes.len() - 4;
+        bytes[checksum_low] ^= 0xFF;
+
+        let replies = send(&mut printer, &bytes);
+
+        assert_eq!(replies.last(), Some(&STATUS_CHECKSUM_ERROR));
+    }
+
+    #[test]
+    fn test_data_marks_unprocessed() {
+        let mut printer = GbPrinter::new(std::env::temp_dir());
+        send(&mut printer, &packet(COMMAND_INIT, false, &[]));
+        send(&mut printer, &packet(COMMAND_DATA, false, &[0u8; 640]));
+
+        let replies = send(&mut printer, &packet(COMMAND_STATUS, false, &[]));
+
+        assert_eq!(replies.last(), Some(&STATUS_UNPROCESSED));
+    }
+
+    #[test]
+    fn test_decompress() {
+        // 3 literal bytes, then 0xAB repeated 5 times
+        let data = [0x02, 0x01, 0x02, 0x03, 0x83, 0xAB];
+
+        assert_eq!(
+            decompress(&data),
+            vec![0x01, 0x02, 0x03, 0xAB, 0xAB, 0xAB, 0xAB, 0xAB]
+        );
+    }
+
+    #[test]
+    fn test_print_writes_png() {
+        let dir = output_dir("print");
+        let mut printer = GbPrinter::new(&dir);
+
+        // Every pixel uses colour 3: low and high bitplanes all set.
+        // Sent compressed as five runs of 128 bytes.
+        let compressed = [0xFE, 0xFF].repeat(5);
+        send(&mut printer, &packet(COMMAND_INIT, false, &[]));
+        send(&mut printer, &packet(COMMAND_DATA, true, &compressed));
+        send(&mut printer, &packet(COMMAND_DATA, false, &[]));
+
+        // 1 sheet, no margin before, one after, default palette, default exposure
+        send(&mut printer, &packet(COMMAND_PRINT, false, &[0x01, 0x01, 0xE4, 0x40]));
+        let replies = send(&mut printer, &packet(COMMAND_STATUS, false, &[]));
+
+        assert_eq!(replies.last().unwrap() & STATUS_PRINTING, STATUS_PRINTING);
+        assert_eq!(printer.pages_printed().len(), 1);
+
+        let decoder = png::Decoder::new(std::fs::File::open(&printer.pages_printed()[0]).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!(info.width, 160);
+        // One band of 16 lines followed by one margin unit
+        assert_eq!(info.height, 16 + 8);
+        assert!(pixels[..160 * 16].iter().all(|&p| p == 0x00));
+        assert!(pixels[160 * 16..].iter().all(|&p| p == 0xFF));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_errors_are_kept() {
+        let missing = TempPath::new("printer_missing");
+        let mut printer = GbPrinter::new(&missing);
+        send(&mut printer, &packet(COMMAND_INIT, false, &[]));
+        send(&mut printer, &packet(COMMAND_DATA, false, &[0u8; 640]));
+
+        send(&mut printer, &packet(COMMAND_PRINT, false, &[0x01, 0x01, 0xE4, 0x40]));
+
+        assert!(printer.pages_printed().is_empty());
+        assert_eq!(printer.take_write_error().unwrap().kind(), std::io::ErrorKind::NotFound);
+        assert!(printer.take_write_error().is_none());
+        assert_eq!(printer.pending_lines(), 16 + 8);
+    }
+
+    #[test]
+    fn test_serial_transfer_through_bus() {
+        let mut gameboy = Gameboy::new();
+        gameboy
+            .memory
+            .serial
+            .attach(Box::new(GbPrinter::new(std::env::temp_dir())));
+
+        // First byte of a packet; the printer only answers the trailing bytes.
+        gameboy.memory.write_u8(SB, 0x88);
+        gameboy.memory.write_u8(SC, 0x81);
+        gameboy.memory.tick(4095);
+        assert_eq!(gameboy.memory.read_u8(SC) & 0x80, 0x80);
+
+        gameboy.memory.tick(1);
+        assert_eq!(gameboy.memory.read_u8(SC) & 0x80, 0x00);
+        assert_eq!(gameboy.memory.read_u8(SB), 0x00);
+        assert_eq!(gameboy.memory.read_u8(INTERRUPT_FLAG) & 0x08, 0x08);
+    }
+}
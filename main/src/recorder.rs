@@ -0,0 +1,167 @@
+use crate::apu::CPU_CLOCK_HZ;
+use crate::gameboy::{CYCLES_PER_FRAME, Gameboy};
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VideoFormat {
+    // Uncompressed YUV 4:4:4 that ffmpeg and most players take as is.
+    Y4m,
+    Gif,
+}
+
+impl VideoFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "y4m" => Some(VideoFormat::Y4m),
+            "gif" => Some(VideoFormat::Gif),
+            _ => None,
+        }
+    }
+}
+
+enum Encoder {
+    Y4m(BufWriter<File>),
+    Gif(gif::Encoder<BufWriter<File>>),
+}
+
+// Writes emulated frames to a video file, keeping one frame in every
+// `frame_skip + 1`.
+pub struct VideoRecorder {
+    encoder: Encoder,
+    frame_skip: u32,
+    frames_seen: u64,
+    frames_written: u64,
+    // GIF delays are whole hundredths of a second, so the time they fall
+    // short of is carried over to the next frame.
+    gif_time: u64,
+}
+
+impl VideoRecorder {
+    pub fn create<P: AsRef<Path>>(path: P, format: VideoFormat, frame_skip: u32) -> std::io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let encoder = match format {
+            VideoFormat::Y4m => {
+                writeln!(
+                    out,
+                    "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444",
+                    SCREEN_WIDTH,
+                    SCREEN_HEIGHT,
+                    CPU_CLOCK_HZ,
+                    CYCLES_PER_FRAME as u64 * (frame_skip as u64 + 1)
+                )?;
+                Encoder::Y4m(out)
+            }
+            VideoFormat::Gif => {
+                let mut encoder =
+                    gif::Encoder::new(out, SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16, &[]).map_err(Error::other)?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(Error::other)?;
+                Encoder::Gif(encoder)
+            }
+        };
+
+        Ok(VideoRecorder {
+            encoder,
+            frame_skip,
+            frames_seen: 0,
+            frames_written: 0,
+            gif_time: 0,
+        })
+    }
+
+    // Picks the format from the file extension.
+    pub fn create_by_extension<P: AsRef<Path>>(path: P, frame_skip: u32) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let format = VideoFormat::from_path(path).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{}: recordings must be .y4m or .gif", path.display()),
+            )
+        })?;
+        Self::create(path, format, frame_skip)
+    }
+
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    // Call once per `run_frame`.
+    pub fn write_frame(&mut self, gameboy: &Gameboy) -> std::io::Result<()> {
+        let keep = self.frames_seen.is_multiple_of(self.frame_skip as u64 + 1);
+        self.frames_seen += 1;
+        if !keep {
+            return Ok(());
+        }
+        self.write_rgb(&gameboy.memory.ppu.framebuffer_rgb())
+    }
+
+    pub fn write_rgb(&mut self, rgb: &[u8]) -> std::io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Y4m(out) => {
+                out.write_all(b"FRAME\n")?;
+                out.write_all(&rgb_to_yuv444(rgb))?;
+            }
+            Encoder::Gif(encoder) => {
+                let mut frame = gif_frame(rgb);
+
+                // Centiseconds from the start of the recording to the end of
+                // this frame, less what earlier frames already took.
+                let cycles = (self.frames_written + 1) * CYCLES_PER_FRAME as u64 * (self.frame_skip as u64 + 1);
+                let end = cycles * 100 / u64::from(CPU_CLOCK_HZ);
+                frame.delay = (end - self.gif_time) as u16;
+                self.gif_time = end;
+
+                encoder.write_frame(&frame).map_err(Error::other)?;
+            }
+        }
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    // Writes out anything buffered and, for GIFs, the trailer.
+    pub fn finish(self) -> std::io::Result<()> {
+        match self.encoder {
+            Encoder::Y4m(mut out) => out.flush(),
+            Encoder::Gif(encoder) => encoder.into_inner()?.flush(),
+        }
+    }
+}
+
+// BT.601 studio range, planar Y then Cb then Cr at full resolution.
+fn rgb_to_yuv444(rgb: &[u8]) -> Vec<u8> {
+    let pixels = rgb.len() / 3;
+    let mut yuv = vec![0; pixels * 3];
+    for (i, pixel) in rgb.chunks_exact(3).enumerate() {
+        let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+        yuv[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+        yuv[pixels + i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+        yuv[pixels * 2 + i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+    }
+    yuv
+}
+
+// Frames with up to 256 colours keep them exactly. Busier CGB frames go
+// through the encoder's quantiser.
+fn gif_frame(rgb: &[u8]) -> gif::Frame<'static> {
+    let mut palette = Vec::new();
+    let mut indices = HashMap::new();
+    let mut pixels = Vec::with_capacity(rgb.len() / 3);
+
+    for pixel in rgb.chunks_exact(3) {
+        let next = indices.len();
+        let index = *indices.entry([pixel[0], pixel[1], pixel[2]]).or_insert(next);
+        if index == next {
+            if next == 256 {
+                return gif::Frame::from_rgb_speed(SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16, rgb, 10);
+            }
+            palette.extend_from_slice(pixel);
+        }
+        pixels.push(index as u8);
+    }
+
+    gif::Frame::from_palette_pixels(SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16, pixels, palette, None)
+}
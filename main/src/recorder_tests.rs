@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::Gameboy;
+    use crate::ppu::{BGP, LCDC, SCREEN_HEIGHT, SCREEN_WIDTH};
+    use crate::recorder::*;
+    use crate::testing::TempPath;
+    use std::fs::File;
+    use std::path::Path;
+
+    const FRAME_BYTES: usize = SCREEN_WIDTH * SCREEN_HEIGHT * 3;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(VideoFormat::from_path(Path::new("a.y4m")), Some(VideoFormat::Y4m));
+        assert_eq!(VideoFormat::from_path(Path::new("a.GIF")), Some(VideoFormat::Gif));
+        assert_eq!(VideoFormat::from_path(Path::new("a.mp4")), None);
+        assert!(VideoRecorder::create_by_extension(TempPath::new("recorder.mp4"), 0).is_err());
+    }
+
+    #[test]
+    fn test_y4m_frames_and_skip() {
+        let path = TempPath::new("recorder.y4m");
+        let gameboy = Gameboy::new();
+        let mut recorder = VideoRecorder::create(&path, VideoFormat::Y4m, 2).unwrap();
+        for _ in 0..7 {
+            recorder.write_frame(&gameboy).unwrap();
+        }
+        assert_eq!(recorder.frames_written(), 3);
+        recorder.finish().unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let header = b"YUV4MPEG2 W160 H144 F4194304:210672 Ip A1:1 C444\n";
+        assert_eq!(data[..header.len()], header[..]);
+        assert_eq!(data.len(), header.len() + 3 * (b"FRAME\n".len() + FRAME_BYTES));
+
+        // A white screen is peak luma with neutral chroma
+        let frame = &data[header.len() + 6..];
+        assert_eq!(frame[0], 235);
+        assert_eq!(frame[SCREEN_WIDTH * SCREEN_HEIGHT], 128);
+        assert_eq!(frame[SCREEN_WIDTH * SCREEN_HEIGHT * 2], 128);
+    }
+
+    #[test]
+    fn test_gif_keeps_colours_and_timing() {
+        let path = TempPath::new("recorder.gif");
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.write_u8(BGP, 0xE4);
+        gameboy.memory.write_u8(LCDC, 0x91);
+        gameboy.memory.ppu.tick(81);
+
+        let mut recorder = VideoRecorder::create(&path, VideoFormat::Gif, 1).unwrap();
+        for _ in 0..6 {
+            recorder.write_frame(&gameboy).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.buffer[..4], [0xFF, 0xFF, 0xFF, 0xFF]);
+            delays.push(frame.delay);
+        }
+
+        // Two emulated frames are 3.35 centiseconds
+        assert_eq!(delays, [3, 3, 4]);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::disasm::{self, Instruction, ROM_BANK_SIZE};
+use crate::opcodes;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+// Where execution can start without anything jumping there: the RST and
+// interrupt vectors and the cartridge entry point.
+const ENTRY_POINTS: [(usize, &str); 14] = [
+    (0x00, "Rst00"),
+    (0x08, "Rst08"),
+    (0x10, "Rst10"),
+    (0x18, "Rst18"),
+    (0x20, "Rst20"),
+    (0x28, "Rst28"),
+    (0x30, "Rst30"),
+    (0x38, "Rst38"),
+    (0x40, "VBlankInterrupt"),
+    (0x48, "LCDCInterrupt"),
+    (0x50, "TimerInterrupt"),
+    (0x58, "SerialInterrupt"),
+    (0x60, "JoypadInterrupt"),
+    (0x100, "Entry"),
+];
+
+// Bytes per `db` line.
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum LabelKind {
+    Jump,
+    Call,
+    Entry(&'static str),
+}
+
+// Which bytes of a ROM are reachable code, found by following every branch
+// from the entry points. Offsets are into the ROM file.
+pub struct CodeMap {
+    // Whether each byte belongs to an instruction.
+    code: Vec<bool>,
+    // Whether an instruction starts at each byte.
+    starts: Vec<bool>,
+    pub labels: BTreeMap<usize, LabelKind>,
+    // Branch instructions to the offset they were found to go to.
+    pub targets: HashMap<usize, usize>,
+}
+
+impl CodeMap {
+    // Code past 4000 can only be placed in a bank by guessing. Branches from
+    // a switchable bank stay in it, and from bank 0 they go to the last bank
+    // written to 2000-3FFF with `LD A,n` / `LD (nn),A`, or bank 1.
+    pub fn trace(rom: &[u8]) -> Self {
+        let mut map = CodeMap {
+            code: vec![false; rom.len()],
+            starts: vec![false; rom.len()],
+            labels: BTreeMap::new(),
+            targets: HashMap::new(),
+        };
+
+        let mut pending = Vec::new();
+        for (offset, name) in ENTRY_POINTS {
+            if offset < rom.len() {
+                map.labels.insert(offset, LabelKind::Entry(name));
+                pending.push(offset);
+            }
+        }
+
+        while let Some(start) = pending.pop() {
+            let bank = start / ROM_BANK_SIZE;
+            let bank_end = ((bank + 1) * ROM_BANK_SIZE).min(rom.len());
+            let mut offset = start;
+            let mut a = None;
+            let mut switched_bank = None;
+
+            while offset < bank_end && !map.starts[offset] {
+                let instruction = disasm::decode(&rom[offset..bank_end], disasm::rom_address(offset).1);
+                let end = offset + instruction.bytes.len();
+                if is_illegal(&instruction) || end > bank_end || map.code[offset..end].contains(&true) {
+                    break;
+                }
+                map.starts[offset] = true;
+                map.code[offset..end].fill(true);
+
+                let opcode = instruction.bytes[0];
+                if opcode == 0xEA
+                    && let Some(value) = a
+                    && (0x2000..0x4000).contains(&operand_u16(&instruction))
+                {
+                    switched_bank = Some((value as usize).max(1));
+                }
+                a = if opcode == 0x3E { Some(instruction.bytes[1]) } else { None };
+
+                if let Some(target) = instruction.target()
+                    && let Some(to) = rom_offset(target, bank, switched_bank).filter(|to| *to < rom.len())
+                {
+                    map.targets.insert(offset, to);
+                    let kind = if is_call(opcode) { LabelKind::Call } else { LabelKind::Jump };
+                    let label = map.labels.entry(to).or_insert(kind);
+                    *label = (*label).max(kind);
+                    pending.push(to);
+                }
+
+                // JR, JP, JP HL, RET and RETI don't fall through.
+                if matches!(opcode, 0x18 | 0xC3 | 0xE9 | 0xC9 | 0xD9) {
+                    break;
+                }
+                offset = end;
+            }
+        }
+
+        map
+    }
+
+    pub fn is_code(&self, offset: usize) -> bool {
+        self.code[offset]
+    }
+
+    pub fn is_instruction_start(&self, offset: usize) -> bool {
+        self.starts[offset]
+    }
+
+    // Labels only go where an instruction starts, since a branch into the
+    // middle of one can't be written as a label.
+    pub fn label(&self, offset: usize) -> Option<String> {
+        if !self.starts.get(offset).copied().unwrap_or(false) {
+            return None;
+        }
+        let (bank, address) = disasm::rom_address(offset);
+        self.labels.get(&offset).map(|kind| match kind {
+            LabelKind::Entry(name) => name.to_string(),
+            LabelKind::Call => format!("Call_{:03X}_{:04X}", bank, address),
+            LabelKind::Jump => format!("Jump_{:03X}_{:04X}", bank, address),
+        })
+    }
+}
+
+// RGBDS source for the whole ROM: traced code as instructions with labels on
+// branch targets and everything else as `db`. Assembled and linked it gives
+// the same bytes back, as long as the ROM is a whole number of banks.
+pub fn source(rom: &[u8]) -> String {
+    let map = CodeMap::trace(rom);
+    let mut text = String::new();
+
+    for bank in 0..rom.len().div_ceil(ROM_BANK_SIZE) {
+        if bank > 0 {
+            text.push('\n');
+        }
+        match bank {
+            0 => writeln!(text, "SECTION \"ROM Bank $000\", ROM0[$0000]").unwrap(),
+            _ => writeln!(
+                text,
+                "SECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:03X}]",
+                bank, bank
+            )
+            .unwrap(),
+        }
+
+        let bank_end = ((bank + 1) * ROM_BANK_SIZE).min(rom.len());
+        let mut offset = bank * ROM_BANK_SIZE;
+        while offset < bank_end {
+            if map.is_instruction_start(offset) {
+                let instruction = disasm::decode(&rom[offset..bank_end], disasm::rom_address(offset).1);
+                if let Some(label) = map.label(offset) {
+                    writeln!(text, "\n{}:", label).unwrap();
+                }
+                let target = map.targets.get(&offset).and_then(|to| map.label(*to));
+                writeln!(text, "    {}", instruction_text(&instruction, target)).unwrap();
+                offset += instruction.bytes.len();
+            } else {
+                let mut end = offset + 1;
+                while end < bank_end && end - offset < DATA_PER_LINE && !map.is_code(end) {
+                    end += 1;
+                }
+                writeln!(text, "    {}", data(&rom[offset..end])).unwrap();
+                offset = end;
+            }
+        }
+    }
+    text
+}
+
+// An instruction in RGBDS syntax, with `target` in place of its branch
+// address when there's a label for it.
+pub fn instruction_text(instruction: &Instruction, target: Option<String>) -> String {
+    let bytes = &instruction.bytes;
+    let info = opcodes::lookup(bytes[0], bytes.get(1).copied().unwrap_or(0));
+    let u8_operand = || format!("${:02X}", bytes[1]);
+    let u16_operand = || format!("${:04X}", operand_u16(instruction));
+
+    match info.name.as_str() {
+        // RGBDS always follows STOP with a padding byte, which may not be
+        // what's in the ROM.
+        "STOP" => return format!("{} ; stop", data(bytes)),
+        // Assemblers may shorten these to LDH.
+        "LD (u16),A" if operand_u16(instruction) >= 0xFF00 => {
+            return format!("{} ; ld [{}], a", data(bytes), u16_operand());
+        }
+        "LD A,(u16)" if operand_u16(instruction) >= 0xFF00 => {
+            return format!("{} ; ld a, [{}]", data(bytes), u16_operand());
+        }
+        "LD (FF00+u8),A" => return format!("ldh [$FF{:02X}], a", bytes[1]),
+        "LD A,(FF00+u8)" => return format!("ldh a, [$FF{:02X}]", bytes[1]),
+        "LD (FF00+C),A" => return "ldh [c], a".to_string(),
+        "LD A,(FF00+C)" => return "ldh a, [c]".to_string(),
+        "LD HL,SP+i8" => {
+            let offset = bytes[1] as i8;
+            let sign = if offset < 0 { '-' } else { '+' };
+            return format!("ld hl, sp {} {}", sign, offset.unsigned_abs());
+        }
+        _ => {}
+    }
+
+    let Some((mnemonic, operands)) = info.name.split_once(' ') else {
+        return info.name.to_lowercase();
+    };
+    if mnemonic == "RST" {
+        return format!("rst ${}", &operands[..2]);
+    }
+
+    let operands: Vec<String> = operands
+        .split(',')
+        .map(|operand| match operand {
+            "u8" => u8_operand(),
+            "u16" => target.clone().unwrap_or_else(u16_operand),
+            "(u16)" => format!("[{}]", u16_operand()),
+            "i8" if mnemonic == "JR" => target
+                .clone()
+                .unwrap_or_else(|| format!("${:04X}", instruction.target().unwrap())),
+            "i8" => (bytes[1] as i8).to_string(),
+            _ => operand.replace('(', "[").replace(')', "]").to_lowercase(),
+        })
+        .collect();
+    format!("{} {}", mnemonic.to_lowercase(), operands.join(", "))
+}
+
+fn data(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("${:02X}", byte)).collect();
+    format!("db {}", bytes.join(", "))
+}
+
+fn is_illegal(instruction: &Instruction) -> bool {
+    opcodes::unprefixed(instruction.bytes[0]).group == "unused"
+}
+
+// CALL and RST.
+fn is_call(opcode: u8) -> bool {
+    matches!(opcode, 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC) || opcode & 0xC7 == 0xC7
+}
+
+fn operand_u16(instruction: &Instruction) -> u16 {
+    u16::from_le_bytes([instruction.bytes[1], instruction.bytes[2]])
+}
+
+// The file offset behind a ROM address seen from `bank`, or None outside ROM.
+fn rom_offset(address: u16, bank: usize, switched_bank: Option<usize>) -> Option<usize> {
+    let address = address as usize;
+    match address {
+        0..ROM_BANK_SIZE => Some(address),
+        ROM_BANK_SIZE..0x8000 => {
+            let bank = match bank {
+                0 => switched_bank.unwrap_or(1),
+                _ => bank,
+            };
+            Some(bank * ROM_BANK_SIZE + address - ROM_BANK_SIZE)
+        }
+        _ => None,
+    }
+}
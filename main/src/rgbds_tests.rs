@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm;
+    use crate::disasm::{ROM_BANK_SIZE, decode};
+    use crate::rgbds::*;
+
+    fn text(bytes: &[u8]) -> String {
+        instruction_text(&decode(bytes, 0x0150), None)
+    }
+
+    // Entry jumps to 0150, which clears VRAM, calls 0200 and a routine in
+    // bank 1, then spins. The logo area in between is data.
+    fn test_rom() -> Vec<u8> {
+        let mut rom = vec![0xFF; ROM_BANK_SIZE * 2];
+        rom[0x00] = 0xC9;
+        rom[0x08] = 0xD3;
+        rom[0x38] = 0xC9;
+        rom[0x40] = 0xD9;
+        rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        let program = [
+            0x21, 0xFF, 0x9F, // ld hl, $9FFF
+            0x32, // ld [hl-], a
+            0xCB, 0x7C, // bit 7, h
+            0x20, 0xFB, // jr nz, 0153
+            0xCD, 0x00, 0x02, // call 0200
+            0x3E, 0x01, // ld a, 1
+            0xEA, 0x00, 0x20, // ld [$2000], a
+            0xCD, 0x10, 0x40, // call 4010
+            0x18, 0xFE, // jr 0163
+        ];
+        rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+        rom[0x200] = 0xC9;
+        rom[ROM_BANK_SIZE + 0x10] = 0xAF;
+        rom[ROM_BANK_SIZE + 0x11] = 0xC9;
+        rom
+    }
+
+    #[test]
+    fn test_instructions_use_rgbds_syntax() {
+        assert_eq!(text(&[0x32]), "ld [hl-], a");
+        assert_eq!(text(&[0x2A]), "ld a, [hl+]");
+        assert_eq!(text(&[0x3E, 0x12]), "ld a, $12");
+        assert_eq!(text(&[0xEA, 0x00, 0xC0]), "ld [$C000], a");
+        assert_eq!(text(&[0xE0, 0x40]), "ldh [$FF40], a");
+        assert_eq!(text(&[0xF2]), "ldh a, [c]");
+        assert_eq!(text(&[0xF8, 0xFE]), "ld hl, sp - 2");
+        assert_eq!(text(&[0xE8, 0x05]), "add sp, 5");
+        assert_eq!(text(&[0xCB, 0x86]), "res 0, [hl]");
+        assert_eq!(text(&[0xFF]), "rst $38");
+        assert_eq!(text(&[0xE9]), "jp hl");
+        assert_eq!(text(&[0x20, 0xFE]), "jr nz, $0150");
+    }
+
+    #[test]
+    fn test_ambiguous_encodings_are_data() {
+        assert_eq!(text(&[0xEA, 0x40, 0xFF]), "db $EA, $40, $FF ; ld [$FF40], a");
+        assert_eq!(text(&[0xFA, 0x44, 0xFF]), "db $FA, $44, $FF ; ld a, [$FF44]");
+        assert_eq!(text(&[0x10]), "db $10 ; stop");
+    }
+
+    #[test]
+    fn test_branch_targets_use_labels() {
+        let instruction = decode(&[0xCD, 0x00, 0x02], 0x0150);
+        assert_eq!(instruction_text(&instruction, Some("Call_000_0200".to_string())), "call Call_000_0200");
+    }
+
+    #[test]
+    fn test_trace_follows_branches() {
+        let rom = test_rom();
+        let map = CodeMap::trace(&rom);
+
+        assert!(map.is_instruction_start(0x101));
+        // Nintendo logo and header.
+        assert!(!map.is_code(0x104));
+        assert!(map.is_instruction_start(0x156));
+        assert!(map.is_code(0x157));
+        assert!(!map.is_instruction_start(0x157));
+        assert!(map.is_instruction_start(0x200));
+        assert!(!map.is_code(0x201));
+        assert!(map.is_instruction_start(ROM_BANK_SIZE + 0x10));
+
+        assert_eq!(map.label(0x100).as_deref(), Some("Entry"));
+        assert_eq!(map.label(0x153).as_deref(), Some("Jump_000_0153"));
+        assert_eq!(map.label(0x200).as_deref(), Some("Call_000_0200"));
+        assert_eq!(map.label(ROM_BANK_SIZE + 0x10).as_deref(), Some("Call_001_4010"));
+        // Vectors that don't hold code get no label.
+        assert_eq!(map.label(0x08), None);
+    }
+
+    #[test]
+    fn test_source_has_sections_and_labels() {
+        let source = source(&test_rom());
+
+        assert!(source.starts_with("SECTION \"ROM Bank $000\", ROM0[$0000]\n"));
+        assert!(source.contains("\nSECTION \"ROM Bank $001\", ROMX[$4000], BANK[$001]\n"));
+        assert!(source.contains("\nEntry:\n    nop\n    jp Jump_000_0150\n    db $FF,"));
+        assert!(source.contains("    jr nz, Jump_000_0153\n    call Call_000_0200\n"));
+        assert!(source.contains("    call Call_001_4010\n"));
+        assert!(source.contains("\nCall_001_4010:\n    xor a, a\n    ret\n"));
+    }
+
+    // Data, bytes that don't decode and instructions cut off by the end of a
+    // bank all have to come back as they were.
+    #[test]
+    fn test_source_reassembles_to_the_rom() {
+        let mut rom = test_rom();
+        rom[0x104..0x134].copy_from_slice(&(0..0x30).collect::<Vec<u8>>());
+        rom[0x201..0x210].copy_from_slice(&[0xD3, 0xCB, 0xEA, 0x40, 0xFF, 0x10, 0x00, 0xE8, 0xFE, 0xDD, 0, 1, 2, 3, 4]);
+        rom[ROM_BANK_SIZE - 2..ROM_BANK_SIZE].copy_from_slice(&[0xC3, 0x00]);
+        rom[ROM_BANK_SIZE * 2 - 1] = 0xCB;
+
+        assert_eq!(asm::assemble(&source(&rom)).unwrap(), rom);
+    }
+}
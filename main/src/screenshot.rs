@@ -0,0 +1,129 @@
+use crate::ppu::{Ppu, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImageFormat {
+    Png,
+    // Binary (P6) PPM: a short text header and the raw RGB bytes.
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+pub fn write_png<W: Write>(out: W, width: usize, height: usize, rgb: &[u8]) -> std::io::Result<()> {
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgb)?;
+    Ok(())
+}
+
+pub fn write_ppm<W: Write>(mut out: W, width: usize, height: usize, rgb: &[u8]) -> std::io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(rgb)?;
+    out.flush()
+}
+
+// Writes the PPU's current frame in the given format.
+pub fn save<P: AsRef<Path>>(ppu: &Ppu, path: P, format: ImageFormat) -> std::io::Result<()> {
+    let out = BufWriter::new(File::create(path)?);
+    let rgb = ppu.framebuffer_rgb();
+    match format {
+        ImageFormat::Png => write_png(out, SCREEN_WIDTH, SCREEN_HEIGHT, &rgb),
+        ImageFormat::Ppm => write_ppm(out, SCREEN_WIDTH, SCREEN_HEIGHT, &rgb),
+    }
+}
+
+// The same, picking the format from the file extension.
+pub fn save_by_extension<P: AsRef<Path>>(ppu: &Ppu, path: P) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{}: screenshots must be .png or .ppm", path.display()),
+        )
+    })?;
+    save(ppu, path, format)
+}
+
+// Reads a PNG as 8-bit RGB, whatever its colour type.
+pub fn read_png<P: AsRef<Path>>(path: P) -> std::io::Result<(usize, usize, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut pixels)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    pixels.truncate(info.buffer_size());
+
+    let rgb = match info.color_type {
+        png::ColorType::Rgb => pixels,
+        png::ColorType::Rgba => pixels.chunks(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&grey| [grey; 3]).collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks(2).flat_map(|pixel| [pixel[0]; 3]).collect(),
+        png::ColorType::Indexed => unreachable!("palettes are expanded"),
+    };
+    Ok((info.width as usize, info.height as usize, rgb))
+}
+
+// The frame, the reference and where they differ, side by side: pixels that
+// match are shown faded and ones that don't in red. Returns the image and
+// how many pixels differ.
+pub fn diff_image(actual: &[u8], expected: &[u8]) -> (Vec<u8>, usize) {
+    let mut diff = Vec::with_capacity(actual.len() * 3);
+    let mut differing = 0;
+    for y in 0..SCREEN_HEIGHT {
+        let row = y * SCREEN_WIDTH * 3..(y + 1) * SCREEN_WIDTH * 3;
+        diff.extend_from_slice(&actual[row.clone()]);
+        diff.extend_from_slice(&expected[row.clone()]);
+        for (a, e) in actual[row.clone()].chunks(3).zip(expected[row].chunks(3)) {
+            if a == e {
+                diff.extend(a.iter().map(|&channel| 0xC0 + channel / 4));
+            } else {
+                diff.extend_from_slice(&[0xFF, 0x00, 0x00]);
+                differing += 1;
+            }
+        }
+    }
+    (diff, differing)
+}
+
+// Compares the PPU's current frame with a reference PNG. When they differ,
+// the diff image is written to `diff_path` and the error says how much.
+pub fn check_reference<P: AsRef<Path>, Q: AsRef<Path>>(ppu: &Ppu, reference: P, diff_path: Q) -> Result<(), String> {
+    let (reference, diff_path) = (reference.as_ref(), diff_path.as_ref());
+    let (width, height, expected) = read_png(reference).map_err(|err| format!("{}: {}", reference.display(), err))?;
+    if (width, height) != (SCREEN_WIDTH, SCREEN_HEIGHT) {
+        return Err(format!("{}: is {}x{}, not the screen's size", reference.display(), width, height));
+    }
+
+    let actual = ppu.framebuffer_rgb();
+    if actual == expected {
+        return Ok(());
+    }
+
+    let (diff, differing) = diff_image(&actual, &expected);
+    File::create(diff_path)
+        .and_then(|file| write_png(BufWriter::new(file), SCREEN_WIDTH * 3, SCREEN_HEIGHT, &diff))
+        .map_err(|err| format!("{}: {}", diff_path.display(), err))?;
+    Err(format!(
+        "{} pixels differ from {}, see {}",
+        differing,
+        reference.display(),
+        diff_path.display()
+    ))
+}
@@ -0,0 +1,193 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm;
+    use crate::gameboy::{CYCLES_PER_FRAME, Gameboy, Model};
+    use crate::palette::{ColourCorrection, DmgPalette};
+    use crate::ppu::{BGP, LCDC, OBP0, SCREEN_HEIGHT, SCREEN_WIDTH, SCX, SCY};
+    use crate::screenshot::*;
+    use crate::testing::{PROGRAM_START, TempPath, gameboy_with_program};
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+
+    // The acid2 ROMs, what to run them as, the frames they need and their
+    // reference images, all in testdata/acid2.
+    const ACID2: [(&str, Model, usize, &str); 2] = [
+        ("dmg-acid2.gb", Model::Dmg, 60, "dmg-acid2.png"),
+        ("cgb-acid2.gbc", Model::Cgb, 60, "cgb-acid2.png"),
+    ];
+
+    // A DMG frame whose first line is drawn in shade 3.
+    fn rendered_gameboy() -> Gameboy {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.write_u8(BGP, 0x03);
+        gameboy.memory.write_u8(LCDC, 0x91);
+        gameboy.memory.ppu.tick(81);
+        gameboy
+    }
+
+    // Code that stores each byte from `address` on with LD [HL+],A.
+    fn fill(address: u16, bytes: &[u8]) -> String {
+        let mut source = format!("ld hl, ${:04X}\n", address);
+        for byte in bytes {
+            source += &format!("ld a, ${:02X}\nld [hl+], a\n", byte);
+        }
+        source
+    }
+
+    // A frame set up by an assembled program: a scrolled background of
+    // stripes and boxes in all four shades, and a box sprite over it.
+    fn scene() -> Gameboy {
+        let stripes = [0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x00].repeat(2);
+        let bordered: Vec<u8> = [0xFF; 2].into_iter().chain([0x81; 12]).chain([0xFF; 2]).collect();
+        let map: Vec<u8> = (0..32 * 19).map(|i: usize| ((i % 32 + i / 32) % 3) as u8).collect();
+        let sprite = [16 + 40, 8 + 64, 2, 0];
+
+        let mut source = fill(0x8010, &stripes) + &fill(0x8020, &bordered);
+        source += &fill(0x9800, &map);
+        source += &fill(0xFE00, &sprite);
+        source += &fill(SCX, &[3]);
+        source += &fill(SCY, &[5]);
+        source += &fill(BGP, &[0xE4]);
+        source += &fill(OBP0, &[0x1B]);
+        source += &fill(LCDC, &[0x93]);
+
+        let end = PROGRAM_START + asm::assemble_at(&source, PROGRAM_START).unwrap().len() as u16;
+        let mut gameboy = gameboy_with_program(&source);
+        while gameboy.cpu.program_counter != end {
+            gameboy.execute_next();
+        }
+        gameboy.memory.ppu.set_dmg_palette(DmgPalette::GREYS);
+        gameboy.memory.ppu.tick(CYCLES_PER_FRAME);
+        gameboy
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(ImageFormat::from_path(Path::new("a.png")), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path(Path::new("dir/a.PPM")), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path(Path::new("a.bmp")), None);
+        assert_eq!(ImageFormat::from_path(Path::new("png")), None);
+    }
+
+    #[test]
+    fn test_ppm_is_header_and_raw_rgb() {
+        let mut out = Vec::new();
+        write_ppm(&mut out, 2, 1, &[1, 2, 3, 4, 5, 6]).unwrap();
+
+        assert_eq!(out, b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+    }
+
+    #[test]
+    fn test_screenshots_match_framebuffer() {
+        let gameboy = rendered_gameboy();
+        let rgb = gameboy.memory.ppu.framebuffer_rgb();
+        assert_eq!(rgb[..3], [0x00, 0x00, 0x00]);
+
+        let ppm_path = TempPath::new("screenshot.ppm");
+        let png_path = TempPath::new("screenshot.png");
+        gameboy.save_screenshot(&ppm_path, ImageFormat::Ppm).unwrap();
+        gameboy.save_screenshot(&png_path, ImageFormat::Png).unwrap();
+
+        let ppm = std::fs::read(&ppm_path).unwrap();
+        assert_eq!(ppm[ppm.len() - rgb.len()..], rgb[..]);
+
+        let decoder = png::Decoder::new(File::open(&png_path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32));
+        assert_eq!(pixels, rgb);
+    }
+
+    #[test]
+    fn test_unknown_extension_is_rejected() {
+        let gameboy = Gameboy::new();
+        let path = TempPath::new("screenshot.bmp");
+
+        let err = save_by_extension(&gameboy.memory.ppu, &path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_frame_matches_its_own_screenshot() {
+        let gameboy = rendered_gameboy();
+        let reference = TempPath::new("reference_match.png");
+        let diff = TempPath::new("reference_match.diff.png");
+        gameboy.save_screenshot(&reference, ImageFormat::Png).unwrap();
+
+        assert_eq!(check_reference(&gameboy.memory.ppu, &reference, &diff), Ok(()));
+        assert!(!diff.exists());
+    }
+
+    #[test]
+    fn test_mismatch_writes_a_diff_image() {
+        let mut gameboy = rendered_gameboy();
+        let reference = TempPath::new("reference_mismatch.png");
+        let diff = TempPath::new("reference_mismatch.diff.png");
+        gameboy.save_screenshot(&reference, ImageFormat::Png).unwrap();
+        // The first line comes out white instead.
+        gameboy.memory.ppu.set_dmg_palette(DmgPalette([[0xFF; 3]; 4]));
+
+        let err = check_reference(&gameboy.memory.ppu, &reference, &diff).unwrap_err();
+        assert_eq!(
+            err,
+            format!("{} pixels differ from {}, see {}", SCREEN_WIDTH, reference.display(), diff.display())
+        );
+
+        let (width, height, pixels) = read_png(&diff).unwrap();
+        assert_eq!((width, height), (SCREEN_WIDTH * 3, SCREEN_HEIGHT));
+        let pixel = |x: usize, y: usize| &pixels[(y * width + x) * 3..][..3];
+        assert_eq!(pixel(0, 0), [0xFF, 0xFF, 0xFF]);
+        assert_eq!(pixel(SCREEN_WIDTH, 0), [0x00, 0x00, 0x00]);
+        assert_eq!(pixel(SCREEN_WIDTH * 2, 0), [0xFF, 0x00, 0x00]);
+        assert_eq!(pixel(SCREEN_WIDTH * 2, 1), [0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_reads_greyscale_png() {
+        let path = TempPath::new("greyscale.png");
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 2, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[0x55, 0xAA]).unwrap();
+
+        assert_eq!(read_png(&path).unwrap(), (2, 1, vec![0x55, 0x55, 0x55, 0xAA, 0xAA, 0xAA]));
+    }
+
+    #[test]
+    fn test_scene_matches_reference() {
+        let gameboy = scene();
+        let reference = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/scene.png");
+        let diff = TempPath::new("scene.diff.png");
+        assert_eq!(check_reference(&gameboy.memory.ppu, &reference, &diff), Ok(()));
+    }
+
+    // Needs the ROMs and references listed in testdata/acid2/README.md; run
+    // it with `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs the acid2 ROMs in testdata/acid2"]
+    fn test_acid2() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/acid2");
+        let mut failed = Vec::new();
+        for (rom, model, frames, reference) in ACID2 {
+            let (rom, reference) = (dir.join(rom), dir.join(reference));
+            assert!(rom.exists() && reference.exists(), "{} or {} is missing", rom.display(), reference.display());
+
+            let mut gameboy = Gameboy::new();
+            gameboy.load_cartridge(&rom, Some(model)).unwrap();
+            gameboy.skip_boot_rom();
+            gameboy.memory.ppu.set_dmg_palette(DmgPalette::GREYS);
+            gameboy.memory.ppu.set_colour_correction(ColourCorrection::Raw);
+            for _ in 0..frames {
+                gameboy.run_frame();
+            }
+
+            let diff = std::env::temp_dir().join(reference.file_stem().unwrap()).with_extension("diff.png");
+            if let Err(err) = check_reference(&gameboy.memory.ppu, &reference, &diff) {
+                failed.push(err);
+            }
+        }
+        assert!(failed.is_empty(), "{}", failed.join("\n"));
+    }
+}
@@ -0,0 +1,88 @@
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+const SC_TRANSFER_START: u8 = 1 << 7;
+const SC_INTERNAL_CLOCK: u8 = 1 << 0;
+
+// 8 bits shifted out at 8192 Hz.
+const TRANSFER_CYCLES: u32 = 8 * 512;
+
+// Anything plugged into the link port. The Game Boy is always the clock master
+// here, so a device only ever answers a byte with the byte it shifts back.
+pub trait SerialDevice {
+    fn exchange(&mut self, byte: u8) -> u8;
+}
+
+pub struct Serial {
+    data: u8,
+    control: u8,
+    cycles_remaining: u32,
+    device: Option<Box<dyn SerialDevice>>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Serial {
+            data: 0,
+            control: 0,
+            cycles_remaining: 0,
+            device: None,
+        }
+    }
+
+    pub fn attach(&mut self, device: Box<dyn SerialDevice>) {
+        self.device = Some(device);
+    }
+
+    pub fn detach(&mut self) -> Option<Box<dyn SerialDevice>> {
+        self.device.take()
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            SB => self.data,
+            SC => self.control | 0x7E,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            SB => self.data = value,
+            SC => {
+                self.control = value & (SC_TRANSFER_START | SC_INTERNAL_CLOCK);
+                if self.control == SC_TRANSFER_START | SC_INTERNAL_CLOCK {
+                    self.cycles_remaining = TRANSFER_CYCLES;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Returns true when a transfer finished and the serial interrupt should fire.
+    // Transfers on the external clock never complete since nothing drives it.
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        if self.cycles_remaining == 0 {
+            return false;
+        }
+
+        self.cycles_remaining = self.cycles_remaining.saturating_sub(cycles);
+        if self.cycles_remaining > 0 {
+            return false;
+        }
+
+        // With nothing connected the input line floats high.
+        self.data = match self.device.as_mut() {
+            Some(device) => device.exchange(self.data),
+            None => 0xFF,
+        };
+        self.control &= !SC_TRANSFER_START;
+        true
+    }
+}
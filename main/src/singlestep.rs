@@ -0,0 +1,202 @@
+use crate::gameboy::Gameboy;
+use crate::memory::BusAccess;
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+// Runs the per-opcode JSON vectors from the SingleStepTests sm83 suite
+// (github.com/SingleStepTests/sm83). A file such as `v1/3e.json` or
+// `v1/cb 11.json` holds an array of cases; each gives the CPU and RAM before
+// and after one instruction, and what happened on the bus in every M-cycle.
+//
+// The vectors model the SM83 overlapping fetch with execution: the opcode at
+// PC-1 has already been read when a case starts, and the last cycle reads
+// the next opcode. The emulator fetches its own opcode, so a case is started
+// at PC-1 and ends with the next opcode being read.
+
+#[derive(Debug, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    pub initial: State,
+    #[serde(rename = "final")]
+    pub expected: State,
+    // [address, data, "r-m"/"-wm"/"---"] per M-cycle. Idle cycles may be null.
+    pub cycles: Vec<Option<(u16, Option<u8>, String)>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct State {
+    pub a: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub f: u8,
+    pub h: u8,
+    pub l: u8,
+    pub pc: u16,
+    pub sp: u16,
+    pub ie: Option<u8>,
+    // Not modelled by the CPU yet, so never compared.
+    pub ime: Option<u8>,
+    pub ram: Vec<(u16, u8)>,
+}
+
+// How one file of cases went.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OpcodeReport {
+    // The file name without its extension, e.g. "3e" or "cb 11".
+    pub opcode: String,
+    pub passed: usize,
+    pub failed: usize,
+    // The name of the first failing case and what was wrong.
+    pub first_failure: Option<String>,
+}
+
+impl OpcodeReport {
+    pub fn all_passed(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl fmt::Display for OpcodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}/{} passed", self.opcode, self.passed, self.passed + self.failed)?;
+        if let Some(failure) = &self.first_failure {
+            write!(f, ", first failure {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+// Runs one case on a fresh Gameboy with a flat bus, and says what differed
+// first if it fails.
+pub fn run_case(case: &TestCase) -> Result<(), String> {
+    let mut gameboy = Gameboy::new();
+    gameboy.memory.set_flat_bus(true);
+
+    let initial = &case.initial;
+    let cpu = &mut gameboy.cpu;
+    (cpu.a, cpu.f, cpu.b, cpu.c) = (initial.a, initial.f, initial.b, initial.c);
+    (cpu.d, cpu.e, cpu.h, cpu.l) = (initial.d, initial.e, initial.h, initial.l);
+    cpu.stack_pointer = initial.sp;
+    cpu.program_counter = initial.pc.wrapping_sub(1);
+    for &(address, value) in &initial.ram {
+        gameboy.memory.write_u8(address, value);
+    }
+    if let Some(ie) = initial.ie {
+        gameboy.memory.write_u8(0xFFFF, ie);
+    }
+    gameboy.memory.take_bus_log();
+
+    let cycles = gameboy.execute_next();
+    gameboy.read_u8_increment_pc();
+    // The opcode fetch isn't part of the case; the read of the next one is.
+    let accesses: Vec<BusAccess> = gameboy.memory.take_bus_log().into_iter().skip(1).collect();
+
+    let expected = &case.expected;
+    let cpu = &gameboy.cpu;
+    let registers = [
+        ("a", cpu.a, expected.a),
+        ("f", cpu.f, expected.f),
+        ("b", cpu.b, expected.b),
+        ("c", cpu.c, expected.c),
+        ("d", cpu.d, expected.d),
+        ("e", cpu.e, expected.e),
+        ("h", cpu.h, expected.h),
+        ("l", cpu.l, expected.l),
+    ];
+    for (name, actual, expected) in registers {
+        if actual != expected {
+            return Err(format!("{}: expected {:02X}, got {:02X}", name, expected, actual));
+        }
+    }
+    if cpu.stack_pointer != expected.sp {
+        return Err(format!("sp: expected {:04X}, got {:04X}", expected.sp, cpu.stack_pointer));
+    }
+    if cpu.program_counter != expected.pc {
+        return Err(format!("pc: expected {:04X}, got {:04X}", expected.pc, cpu.program_counter));
+    }
+
+    let mut ram = expected.ram.clone();
+    if let Some(ie) = expected.ie {
+        ram.push((0xFFFF, ie));
+    }
+    for (address, value) in ram {
+        let actual = gameboy.memory.read_u8(address);
+        if actual != value {
+            return Err(format!("[{:04X}]: expected {:02X}, got {:02X}", address, value, actual));
+        }
+    }
+
+    let expected_cycles = case.cycles.len() as u32 * 4;
+    if cycles != expected_cycles {
+        return Err(format!("expected {} T-cycles, took {}", expected_cycles, cycles));
+    }
+
+    let expected_accesses: Vec<BusAccess> = case
+        .cycles
+        .iter()
+        .flatten()
+        .filter_map(|(address, data, kind)| match (data, kind.as_bytes()) {
+            (Some(data), [b'r', ..]) => Some(BusAccess::Read(*address, *data)),
+            (Some(data), [_, b'w', ..]) => Some(BusAccess::Write(*address, *data)),
+            _ => None,
+        })
+        .collect();
+    if accesses != expected_accesses {
+        return Err(format!("bus: expected {:?}, got {:?}", expected_accesses, accesses));
+    }
+    Ok(())
+}
+
+pub fn run_cases(opcode: &str, cases: &[TestCase]) -> OpcodeReport {
+    let mut report = OpcodeReport {
+        opcode: opcode.to_string(),
+        passed: 0,
+        failed: 0,
+        first_failure: None,
+    };
+    for case in cases {
+        match run_case(case) {
+            Ok(()) => report.passed += 1,
+            Err(err) => {
+                report.failed += 1;
+                report.first_failure.get_or_insert_with(|| format!("{}: {}", case.name, err));
+            }
+        }
+    }
+    report
+}
+
+pub fn run_file(path: &Path) -> Result<OpcodeReport, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let cases: Vec<TestCase> = serde_json::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let opcode = path.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(run_cases(&opcode, &cases))
+}
+
+// Every .json file in `dir`, in name order.
+pub fn run_dir(dir: &Path) -> Result<Vec<OpcodeReport>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|err| format!("{}: {}", dir.display(), err))?.path();
+        if path.extension().is_some_and(|extension| extension == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths.iter().map(|path| run_file(path)).collect()
+}
+
+// One line per opcode, then how many passed every case.
+pub fn table(reports: &[OpcodeReport]) -> String {
+    let mut text = String::new();
+    for report in reports {
+        text += &format!("{}\n", report);
+    }
+    let passed = reports.iter().filter(|report| report.all_passed()).count();
+    text += &format!("{}/{} opcodes passed\n", passed, reports.len());
+    text
+}
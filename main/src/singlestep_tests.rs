@@ -0,0 +1,164 @@
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::Gameboy;
+    use crate::singlestep::*;
+    use std::path::PathBuf;
+
+    // Files of the suite that are known to fail, and why. "cb" stands for
+    // every CB-prefixed file. Everything else has to pass, and these have to
+    // keep failing until they're taken off the list.
+    const KNOWN_FAILING: &[(&str, &str)] = &[
+        ("03", "16-bit INC/DEC isn't implemented"),
+        ("07", "rotates aren't implemented"),
+        ("08", "LD (u16),SP isn't implemented"),
+        ("09", "16-bit ADD isn't implemented"),
+        ("0b", "16-bit INC/DEC isn't implemented"),
+        ("0f", "rotates aren't implemented"),
+        ("13", "16-bit INC/DEC isn't implemented"),
+        ("17", "rotates aren't implemented"),
+        ("18", "jumps aren't implemented"),
+        ("19", "16-bit ADD isn't implemented"),
+        ("1b", "16-bit INC/DEC isn't implemented"),
+        ("1f", "rotates aren't implemented"),
+        ("20", "jumps aren't implemented"),
+        ("23", "16-bit INC/DEC isn't implemented"),
+        ("28", "jumps aren't implemented"),
+        ("29", "16-bit ADD isn't implemented"),
+        ("2b", "16-bit INC/DEC isn't implemented"),
+        ("30", "jumps aren't implemented"),
+        ("33", "16-bit INC/DEC isn't implemented"),
+        ("38", "jumps aren't implemented"),
+        ("39", "16-bit ADD isn't implemented"),
+        ("3b", "16-bit INC/DEC isn't implemented"),
+        ("76", "HALT isn't implemented"),
+        ("c0", "calls and returns aren't implemented"),
+        ("c1", "PUSH and POP aren't implemented"),
+        ("c2", "jumps aren't implemented"),
+        ("c3", "jumps aren't implemented"),
+        ("c4", "calls and returns aren't implemented"),
+        ("c5", "PUSH and POP aren't implemented"),
+        ("c7", "RST isn't implemented"),
+        ("c8", "calls and returns aren't implemented"),
+        ("c9", "calls and returns aren't implemented"),
+        ("ca", "jumps aren't implemented"),
+        ("cc", "calls and returns aren't implemented"),
+        ("cd", "calls and returns aren't implemented"),
+        ("cf", "RST isn't implemented"),
+        ("d0", "calls and returns aren't implemented"),
+        ("d1", "PUSH and POP aren't implemented"),
+        ("d2", "jumps aren't implemented"),
+        ("d4", "calls and returns aren't implemented"),
+        ("d5", "PUSH and POP aren't implemented"),
+        ("d7", "RST isn't implemented"),
+        ("d8", "calls and returns aren't implemented"),
+        ("d9", "calls and returns aren't implemented"),
+        ("da", "jumps aren't implemented"),
+        ("dc", "calls and returns aren't implemented"),
+        ("df", "RST isn't implemented"),
+        ("e1", "PUSH and POP aren't implemented"),
+        ("e5", "PUSH and POP aren't implemented"),
+        ("e7", "RST isn't implemented"),
+        ("e8", "16-bit ADD isn't implemented"),
+        ("e9", "jumps aren't implemented"),
+        ("ef", "RST isn't implemented"),
+        ("f1", "PUSH and POP aren't implemented"),
+        ("f3", "DI and EI aren't implemented"),
+        ("f5", "PUSH and POP aren't implemented"),
+        ("f7", "RST isn't implemented"),
+        ("fb", "DI and EI aren't implemented"),
+        ("ff", "RST isn't implemented"),
+        ("10", "STOP doesn't skip its second byte, and resets DIV on the bus"),
+        ("cb", "CB-prefixed opcodes aren't implemented"),
+    ];
+
+    // Opcodes that don't exist, which the suite has no files for.
+    const ILLEGAL: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+
+    fn known_failure(opcode: &str) -> Option<&'static str> {
+        let opcode = if opcode.starts_with("cb ") { "cb" } else { opcode };
+        KNOWN_FAILING.iter().find(|(known, _)| *known == opcode).map(|(_, why)| *why)
+    }
+
+    fn vectors() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/sm83/v1")
+    }
+
+    fn case(json: &str) -> TestCase {
+        serde_json::from_str(json).unwrap()
+    }
+
+    // LD B,A at C000 with A = 12.
+    const LD_B_A: &str = r#"{
+        "name": "47 0000",
+        "initial": {"a": 18, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0, "pc": 49153, "sp": 65534,
+                    "ram": [[49152, 71], [49153, 0]]},
+        "final": {"a": 18, "b": 18, "c": 0, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0, "pc": 49154, "sp": 65534,
+                  "ram": [[49152, 71], [49153, 0]]},
+        "cycles": [[49153, 0, "r-m"]]
+    }"#;
+
+    #[test]
+    fn test_runs_a_case() {
+        assert_eq!(run_case(&case(LD_B_A)), Ok(()));
+
+        let wrong = LD_B_A.replace(r#""b": 18"#, r#""b": 19"#);
+        assert_eq!(run_case(&case(&wrong)), Err("b: expected 13, got 12".to_string()));
+
+        let wrong = LD_B_A.replace(r#"[[49153, 0, "r-m"]]"#, r#"[[49153, 0, "r-m"], [49153, null, "---"]]"#);
+        assert_eq!(run_case(&case(&wrong)), Err("expected 8 T-cycles, took 4".to_string()));
+    }
+
+    // Every opcode without a handler is expected to fail, so one that gets a
+    // handler has to come off the list.
+    #[test]
+    fn test_known_failures_are_unhandled_opcodes() {
+        for opcode in (0..=0xFF).filter(|opcode| !ILLEGAL.contains(opcode)) {
+            let name = format!("{:02x}", opcode);
+            let handled = Gameboy::has_handler(opcode);
+            let known = known_failure(&name).is_some();
+            assert!(handled != known || opcode == 0x10, "{}: handled {}, in KNOWN_FAILING {}", name, handled, known);
+        }
+    }
+
+    // Needs the suite's v1/*.json files in testdata/sm83/v1; run it with
+    // `cargo test -- --ignored --nocapture` to see the table as well.
+    // `main singlestep <dir>` prints the table on its own.
+    #[test]
+    #[ignore = "needs the SingleStepTests vectors in testdata/sm83/v1"]
+    fn test_sm83_suite() {
+        let dir = vectors();
+        assert!(dir.exists(), "{} isn't there", dir.display());
+        let reports = run_dir(&dir).unwrap();
+        assert!(!reports.is_empty(), "no vectors in {}", dir.display());
+        print!("{}", table(&reports));
+
+        let wrong: Vec<String> = reports
+            .iter()
+            .filter_map(|report| match known_failure(&report.opcode) {
+                None if !report.all_passed() => Some(report.to_string()),
+                Some(why) if report.all_passed() => {
+                    Some(format!("{} passes now, take it off KNOWN_FAILING ({})", report.opcode, why))
+                }
+                _ => None,
+            })
+            .collect();
+        assert!(wrong.is_empty(), "{}", wrong.join("\n"));
+    }
+
+    #[test]
+    fn test_table() {
+        let reports = [
+            OpcodeReport { opcode: "00".to_string(), passed: 2, failed: 0, first_failure: None },
+            OpcodeReport {
+                opcode: "cb 11".to_string(),
+                passed: 1,
+                failed: 1,
+                first_failure: Some("cb 11 0001: a: expected 01, got 00".to_string()),
+            },
+        ];
+        assert_eq!(
+            table(&reports),
+            "00: 2/2 passed\ncb 11: 1/2 passed, first failure cb 11 0001: a: expected 01, got 00\n1/2 opcodes passed\n"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use crate::gameboy::Gameboy;
+use crate::joypad::Button;
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{BufWriter, Stdout, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 70224 cycles of a 4194304 Hz clock, about 59.73 frames a second.
+pub const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706);
+
+// Most terminals only report key presses. Without release events a button is
+// let go this many frames after the last press or key repeat.
+const HOLD_FRAMES: u32 = 12;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Hotkey {
+    Quit,
+    Screenshot,
+}
+
+pub fn button_for_key(code: KeyCode) -> Option<Button> {
+    match code {
+        KeyCode::Right => Some(Button::Right),
+        KeyCode::Left => Some(Button::Left),
+        KeyCode::Up => Some(Button::Up),
+        KeyCode::Down => Some(Button::Down),
+        KeyCode::Char('x') | KeyCode::Char('X') => Some(Button::A),
+        KeyCode::Char('z') | KeyCode::Char('Z') => Some(Button::B),
+        KeyCode::Backspace => Some(Button::Select),
+        KeyCode::Enter => Some(Button::Start),
+        _ => None,
+    }
+}
+
+pub fn hotkey_for_key(key: &KeyEvent) -> Option<Hotkey> {
+    match key.code {
+        KeyCode::Esc => Some(Hotkey::Quit),
+        KeyCode::F(12) | KeyCode::Char('p') => Some(Hotkey::Screenshot),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Hotkey::Quit),
+        _ => None,
+    }
+}
+
+// Draws packed RGB pixels two rows to a character: the top pixel is the
+// foreground of a "▀" and the bottom one its background.
+pub fn render_frame<W: Write>(out: &mut W, rgb: &[u8]) -> std::io::Result<()> {
+    let pixel = |x: usize, y: usize| {
+        let i = (y * SCREEN_WIDTH + x) * 3;
+        Color::Rgb {
+            r: rgb[i],
+            g: rgb[i + 1],
+            b: rgb[i + 2],
+        }
+    };
+
+    for row in 0..SCREEN_HEIGHT / 2 {
+        queue!(out, MoveTo(0, row as u16))?;
+        let mut colours = None;
+        for x in 0..SCREEN_WIDTH {
+            let (top, bottom) = (pixel(x, row * 2), pixel(x, row * 2 + 1));
+            if colours != Some((top, bottom)) {
+                queue!(out, SetForegroundColor(top), SetBackgroundColor(bottom))?;
+                colours = Some((top, bottom));
+            }
+            queue!(out, Print('▀'))?;
+        }
+        queue!(out, ResetColor)?;
+    }
+    Ok(())
+}
+
+// Interactive frontend on the controlling terminal. Puts it in raw mode on the
+// alternate screen and restores it when dropped.
+pub struct Terminal {
+    out: BufWriter<Stdout>,
+    release_events: bool,
+    held: Vec<(Button, u32)>,
+    next_frame: Instant,
+}
+
+impl Terminal {
+    pub fn new() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = BufWriter::new(std::io::stdout());
+        execute!(out, EnterAlternateScreen, Hide)?;
+
+        let release_events = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if release_events {
+            execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Terminal {
+            out,
+            release_events,
+            held: Vec::new(),
+            next_frame: Instant::now(),
+        })
+    }
+
+    // Passes pending key presses on to the joypad and returns any hotkeys.
+    pub fn poll_input(&mut self, gameboy: &mut Gameboy) -> std::io::Result<Vec<Hotkey>> {
+        let mut hotkeys = Vec::new();
+
+        if !self.release_events {
+            self.held.retain_mut(|(button, frames)| {
+                *frames -= 1;
+                if *frames == 0 {
+                    gameboy.release_button(*button);
+                }
+                *frames > 0
+            });
+        }
+
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+
+            if let Some(hotkey) = hotkey_for_key(&key) {
+                if key.kind == KeyEventKind::Press {
+                    hotkeys.push(hotkey);
+                }
+                continue;
+            }
+
+            let Some(button) = button_for_key(key.code) else {
+                continue;
+            };
+            match key.kind {
+                KeyEventKind::Release => gameboy.release_button(button),
+                _ => {
+                    gameboy.press_button(button);
+                    if !self.release_events {
+                        self.held.retain(|(held, _)| *held != button);
+                        self.held.push((button, HOLD_FRAMES));
+                    }
+                }
+            }
+        }
+        Ok(hotkeys)
+    }
+
+    pub fn draw(&mut self, gameboy: &Gameboy) -> std::io::Result<()> {
+        render_frame(&mut self.out, &gameboy.memory.ppu.framebuffer_rgb())?;
+        self.out.flush()
+    }
+
+    // Sleeps until the next frame is due. A run that falls behind starts
+    // again from now rather than rushing to catch up.
+    pub fn wait_for_next_frame(&mut self) {
+        self.next_frame += FRAME_DURATION;
+        let now = Instant::now();
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        } else {
+            self.next_frame = now;
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.release_events {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
@@ -0,0 +1,46 @@
+#[cfg(test)]
+mod tests {
+    use crate::joypad::Button;
+    use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+    use crate::terminal::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn render(rgb: &[u8]) -> String {
+        let mut out = Vec::new();
+        render_frame(&mut out, rgb).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_half_blocks_cover_the_screen() {
+        let output = render(&vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 3]);
+
+        assert_eq!(output.matches('▀').count(), SCREEN_WIDTH * SCREEN_HEIGHT / 2);
+        // One colour change per row when the screen is flat
+        assert_eq!(output.matches("\x1b[38;2;0;0;0m").count(), SCREEN_HEIGHT / 2);
+    }
+
+    #[test]
+    fn test_top_pixel_is_foreground() {
+        let mut rgb = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 3];
+        rgb[..3].copy_from_slice(&[0x12, 0x34, 0x56]);
+        rgb[SCREEN_WIDTH * 3..SCREEN_WIDTH * 3 + 3].copy_from_slice(&[0xAB, 0xCD, 0xEF]);
+
+        let output = render(&rgb);
+
+        assert!(output.starts_with("\x1b[1;1H\x1b[38;2;18;52;86m\x1b[48;2;171;205;239m▀\x1b[38;2;0;0;0m"));
+    }
+
+    #[test]
+    fn test_key_mapping() {
+        assert_eq!(button_for_key(KeyCode::Up), Some(Button::Up));
+        assert_eq!(button_for_key(KeyCode::Char('x')), Some(Button::A));
+        assert_eq!(button_for_key(KeyCode::Enter), Some(Button::Start));
+        assert_eq!(button_for_key(KeyCode::Char('q')), None);
+
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(hotkey_for_key(&ctrl_c), Some(Hotkey::Quit));
+        assert_eq!(hotkey_for_key(&KeyEvent::from(KeyCode::Char('c'))), None);
+        assert_eq!(hotkey_for_key(&KeyEvent::from(KeyCode::F(12))), Some(Hotkey::Screenshot));
+    }
+}
@@ -0,0 +1,76 @@
+// Fixtures shared between the test modules.
+use crate::asm;
+use crate::cartridge::Cartridge;
+use crate::gameboy::Gameboy;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Where `gameboy_with_program` puts its code.
+pub const PROGRAM_START: u16 = 0xC000;
+
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+// A Gameboy about to run `source`, assembled into work RAM at PROGRAM_START, with
+// SP at the top of high RAM.
+pub fn gameboy_with_program(source: &str) -> Gameboy {
+    let mut gameboy = Gameboy::new();
+    asm::patch(&mut gameboy, PROGRAM_START, source).unwrap();
+    gameboy.cpu.program_counter = PROGRAM_START;
+    gameboy.cpu.stack_pointer = 0xFFFE;
+    gameboy
+}
+
+// The same, but from cartridge ROM at 0150, just after the header, the way
+// a test ROM starts once the boot ROM is done. `cartridge_type` and
+// `ram_size` go in the header as 0147 and 0149.
+pub fn gameboy_with_rom_program(source: &str, cartridge_type: u8, ram_size: u8) -> Gameboy {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0147] = cartridge_type;
+    rom[0x0149] = ram_size;
+    let code = asm::assemble_at(source, 0x0150).unwrap();
+    rom[0x0150..0x0150 + code.len()].copy_from_slice(&code);
+
+    let mut gameboy = Gameboy::new();
+    gameboy.insert_cartridge(Cartridge::from_bytes(rom).unwrap(), None);
+    gameboy.skip_boot_rom();
+    gameboy.cpu.program_counter = 0x0150;
+    gameboy
+}
+
+// A path in the temp directory that no other test, or other run of the
+// suite, uses. Whatever ends up there is removed when it's dropped, even if
+// the test panics.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    // `name` is kept at the end, so its extension still counts.
+    pub fn new(name: &str) -> Self {
+        let unique = format!("gameboy_{}_{}_{}", std::process::id(), NEXT_TEMP.fetch_add(1, Ordering::Relaxed), name);
+        TempPath(std::env::temp_dir().join(unique))
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        } else {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+pub const DIV: u16 = 0xFF04;
+pub const TIMA: u16 = 0xFF05;
+pub const TMA: u16 = 0xFF06;
+pub const TAC: u16 = 0xFF07;
+
+const TAC_ENABLE: u8 = 1 << 2;
+
+// The APU frame sequencer steps on the falling edge of DIV bit 4, or bit 5
+// in CGB double speed so that it stays at 512 Hz.
+const FRAME_SEQUENCER_BIT: u16 = 1 << 12;
+const FRAME_SEQUENCER_BIT_DOUBLE_SPEED: u16 = 1 << 13;
+
+// DIV is the upper byte of a 16-bit counter running at the CPU clock. TIMA
+// counts falling edges of one of its bits, chosen by TAC.
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    frame_sequencer_bit: u16,
+    frame_sequencer_steps: u32,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Timer {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            frame_sequencer_bit: FRAME_SEQUENCER_BIT,
+            frame_sequencer_steps: 0,
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            DIV => (self.counter >> 8) as u8,
+            TIMA => self.tima,
+            TMA => self.tma,
+            TAC => self.tac | 0xF8,
+            _ => 0xFF,
+        }
+    }
+
+    // Returns true when the write made TIMA overflow.
+    pub fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            DIV => self.set_counter(0),
+            TIMA => {
+                self.tima = value;
+                false
+            }
+            TMA => {
+                self.tma = value;
+                false
+            }
+            TAC => {
+                let before = self.timer_signal();
+                self.tac = value & 0x07;
+                before && !self.timer_signal() && self.increment_tima()
+            }
+            _ => false,
+        }
+    }
+
+    // Returns true when TIMA overflowed and the timer interrupt should fire.
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        let mut overflow = false;
+        for _ in 0..cycles / 4 {
+            overflow |= self.set_counter(self.counter.wrapping_add(4));
+        }
+        overflow
+    }
+
+    pub fn set_double_speed(&mut self, double_speed: bool) {
+        self.frame_sequencer_bit = if double_speed {
+            FRAME_SEQUENCER_BIT_DOUBLE_SPEED
+        } else {
+            FRAME_SEQUENCER_BIT
+        };
+    }
+
+    // Frame sequencer clocks seen since the last call.
+    pub fn take_frame_sequencer_steps(&mut self) -> u32 {
+        std::mem::take(&mut self.frame_sequencer_steps)
+    }
+
+    fn set_counter(&mut self, value: u16) -> bool {
+        let before = self.timer_signal();
+        let sequencer_before = self.counter & self.frame_sequencer_bit != 0;
+
+        self.counter = value;
+
+        if sequencer_before && self.counter & self.frame_sequencer_bit == 0 {
+            self.frame_sequencer_steps += 1;
+        }
+        before && !self.timer_signal() && self.increment_tima()
+    }
+
+    fn timer_signal(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0 => 9,
+            1 => 3,
+            2 => 5,
+            _ => 7,
+        };
+        self.tac & TAC_ENABLE != 0 && self.counter & (1 << bit) != 0
+    }
+
+    fn increment_tima(&mut self) -> bool {
+        let (value, overflow) = self.tima.overflowing_add(1);
+        self.tima = if overflow { self.tma } else { value };
+        overflow
+    }
+}
@@ -0,0 +1,196 @@
+use crate::cpu::Gbz80;
+use crate::debugger;
+use crate::gameboy::Gameboy;
+use crate::memory::GbMemory;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+// Which instructions get a line. Everything passes by default.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct TraceFilter {
+    // Only PCs in start..=end.
+    pub pc: Option<(u16, u16)>,
+    // Only code running from this ROM bank.
+    pub bank: Option<usize>,
+    // Instructions executed before the first line.
+    pub skip: u64,
+    // Lines to write at most.
+    pub limit: Option<u64>,
+}
+
+// Logs the CPU state before each instruction in the format Gameboy Doctor
+// and several reference emulators use, so traces can be diffed line by line:
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+pub struct Tracer {
+    out: Box<dyn Write>,
+    filter: TraceFilter,
+    instructions: u64,
+    lines: u64,
+    // The first write that failed. Tracing stops there and `finish` says why.
+    error: Option<std::io::Error>,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>, filter: TraceFilter) -> Self {
+        Tracer {
+            out,
+            filter,
+            instructions: 0,
+            lines: 0,
+            error: None,
+        }
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P, filter: TraceFilter) -> std::io::Result<Self> {
+        Ok(Self::new(Box::new(BufWriter::new(File::create(path)?)), filter))
+    }
+
+    pub fn lines_written(&self) -> u64 {
+        self.lines
+    }
+
+    // Called by `Gameboy::execute_next` before each instruction.
+    pub fn log(&mut self, cpu: &Gbz80, memory: &GbMemory) {
+        let index = self.instructions;
+        self.instructions += 1;
+        if self.error.is_some() || !self.wants(index, cpu.program_counter, memory) {
+            return;
+        }
+
+        let result = writeln!(self.out, "{}", state_line(cpu, memory));
+        match result {
+            Ok(()) => self.lines += 1,
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    // Writes out anything buffered, or returns the error that stopped the trace.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.out.flush(),
+        }
+    }
+
+    fn wants(&self, index: u64, pc: u16, memory: &GbMemory) -> bool {
+        let filter = &self.filter;
+        index >= filter.skip
+            && filter.limit.is_none_or(|limit| self.lines < limit)
+            && filter.pc.is_none_or(|(start, end)| (start..=end).contains(&pc))
+            && filter.bank.is_none_or(|bank| memory.rom_bank(pc) == Some(bank))
+    }
+}
+
+// One line of the trace for the state before the instruction at PC.
+pub fn state_line(cpu: &Gbz80, memory: &GbMemory) -> String {
+    let pc = cpu.program_counter;
+    let pcmem = |i: u16| memory.read_u8(pc.wrapping_add(i));
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        cpu.a,
+        cpu.f,
+        cpu.b,
+        cpu.c,
+        cpu.d,
+        cpu.e,
+        cpu.h,
+        cpu.l,
+        cpu.stack_pointer,
+        pc,
+        pcmem(0),
+        pcmem(1),
+        pcmem(2),
+        pcmem(3)
+    )
+}
+
+// Where a run first stopped matching a reference trace.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // 1-based line in the reference.
+    pub line: u64,
+    pub expected: String,
+    // None when the emulator stopped before the reference ended.
+    pub actual: Option<String>,
+    // Lines that matched just before, oldest first.
+    pub context: Vec<String>,
+    pub registers: String,
+    // The last instruction that ran, whose result is what differs, then the
+    // one at PC. Only the second when the first line already differs.
+    pub disassembly: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "trace diverges at line {}", self.line)?;
+        for line in &self.context {
+            writeln!(f, "  {}", line)?;
+        }
+        writeln!(f, "- {}", self.expected)?;
+        match &self.actual {
+            Some(actual) => {
+                writeln!(f, "+ {}", actual)?;
+                let fields: Vec<&str> = self
+                    .expected
+                    .split(' ')
+                    .zip(actual.split(' '))
+                    .filter(|(expected, actual)| expected != actual)
+                    .map(|(expected, _)| expected.split(':').next().unwrap_or(expected))
+                    .collect();
+                writeln!(f, "differs in {}", fields.join(", "))?;
+            }
+            None => writeln!(f, "+ (emulation stopped)")?,
+        }
+        write!(f, "\n{}\n{}", self.registers, self.disassembly)
+    }
+}
+
+// Runs `gameboy` one instruction per line of `reference`, comparing the
+// state before each, and stops at the first line that differs. Blank lines
+// in the reference are skipped. Returns how many lines matched when all do.
+pub fn diff<R: BufRead>(
+    gameboy: &mut Gameboy,
+    reference: R,
+    context: usize,
+) -> std::io::Result<Result<u64, Divergence>> {
+    let mut recent = VecDeque::with_capacity(context + 1);
+    let mut matched = 0;
+    let mut last_pc = None;
+
+    for (i, expected) in reference.lines().enumerate() {
+        let expected = expected?;
+        let expected = expected.trim();
+        if expected.is_empty() {
+            continue;
+        }
+
+        let actual = gameboy.running.then(|| state_line(&gameboy.cpu, &gameboy.memory));
+        if actual.as_deref() != Some(expected) {
+            let mut disassembly = String::new();
+            if let Some(last_pc) = last_pc {
+                disassembly += &debugger::disassemble(gameboy, last_pc, 1);
+            }
+            disassembly += &debugger::disassemble(gameboy, gameboy.cpu.program_counter, 1);
+            return Ok(Err(Divergence {
+                line: i as u64 + 1,
+                expected: expected.to_string(),
+                actual,
+                context: recent.into(),
+                registers: debugger::registers(&gameboy.cpu),
+                disassembly,
+            }));
+        }
+
+        recent.push_back(expected.to_string());
+        if recent.len() > context {
+            recent.pop_front();
+        }
+        matched += 1;
+        last_pc = Some(gameboy.cpu.program_counter);
+        gameboy.execute_next();
+    }
+    Ok(Ok(matched))
+}
@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm;
+    use crate::cartridge::Cartridge;
+    use crate::gameboy::Gameboy;
+    use crate::testing::{TempPath, gameboy_with_program};
+    use crate::trace::*;
+
+    // The program starts with the registers the DMG boot ROM leaves.
+    fn traced_gameboy() -> Gameboy {
+        let mut gameboy = gameboy_with_program("ld a, $12\nld b, $34\nld c, b\nld d, a");
+        gameboy.cpu.set_af(0x01B0);
+        gameboy.cpu.set_bc(0x0013);
+        gameboy.cpu.set_de(0x00D8);
+        gameboy.cpu.set_hl(0x014D);
+        gameboy
+    }
+
+    // Runs `instructions` with a tracer and returns what it wrote.
+    fn trace(gameboy: &mut Gameboy, filter: TraceFilter, instructions: usize) -> Vec<String> {
+        let path = TempPath::new("trace.log");
+        gameboy.tracer = Some(Tracer::create(&path, filter).unwrap());
+        for _ in 0..instructions {
+            gameboy.execute_next();
+        }
+        gameboy.tracer.take().unwrap().finish().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn pc(line: &str) -> &str {
+        line.split(' ').find(|field| field.starts_with("PC:")).unwrap()
+    }
+
+    #[test]
+    fn test_lines_match_gameboy_doctor() {
+        let mut gameboy = traced_gameboy();
+
+        let lines = trace(&mut gameboy, TraceFilter::default(), 2);
+
+        assert_eq!(
+            lines,
+            [
+                "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C000 PCMEM:3E,12,06,34",
+                "A:12 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C002 PCMEM:06,34,48,57",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filters_by_pc_and_count() {
+        let mut gameboy = traced_gameboy();
+        let filter = TraceFilter {
+            pc: Some((0xC002, 0xC005)),
+            ..TraceFilter::default()
+        };
+        let lines = trace(&mut gameboy, filter, 4);
+        let pcs: Vec<&str> = lines.iter().map(|line| pc(line)).collect();
+        assert_eq!(pcs, ["PC:C002", "PC:C004", "PC:C005"]);
+
+        let mut gameboy = traced_gameboy();
+        let filter = TraceFilter {
+            skip: 1,
+            limit: Some(2),
+            ..TraceFilter::default()
+        };
+        let lines = trace(&mut gameboy, filter, 4);
+        let pcs: Vec<&str> = lines.iter().map(|line| pc(line)).collect();
+        assert_eq!(pcs, ["PC:C002", "PC:C004"]);
+    }
+
+    #[test]
+    fn test_filters_by_bank() {
+        let mut gameboy = Gameboy::new();
+        gameboy.insert_cartridge(Cartridge::from_bytes(vec![0; 0x8000]).unwrap(), None);
+        gameboy.skip_boot_rom();
+        // Runs off the end of bank 0 into bank 1.
+        asm::patch(&mut gameboy, 0x3FFE, "ld a, $12\nld b, a\nld c, a").unwrap();
+        gameboy.cpu.program_counter = 0x3FFE;
+
+        let filter = TraceFilter {
+            bank: Some(1),
+            ..TraceFilter::default()
+        };
+        let lines = trace(&mut gameboy, filter, 3);
+        let pcs: Vec<&str> = lines.iter().map(|line| pc(line)).collect();
+        assert_eq!(pcs, ["PC:4000", "PC:4001"]);
+    }
+
+    fn reference(lines: &[&str]) -> std::io::Cursor<String> {
+        std::io::Cursor::new(lines.join("\n"))
+    }
+
+    const LINES: [&str; 3] = [
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C000 PCMEM:3E,12,06,34",
+        "A:12 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C002 PCMEM:06,34,48,57",
+        "A:12 F:B0 B:34 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C004 PCMEM:48,57,00,00",
+    ];
+
+    #[test]
+    fn test_diff_matches_reference() {
+        let mut gameboy = traced_gameboy();
+        let result = diff(&mut gameboy, reference(&LINES), 5).unwrap();
+        assert_eq!(result, Ok(3));
+        assert_eq!(gameboy.cpu.program_counter, 0xC005);
+    }
+
+    #[test]
+    fn test_diff_stops_at_divergence() {
+        let mut gameboy = traced_gameboy();
+        let mut lines = LINES;
+        lines[2] = "A:12 F:B0 B:35 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C004 PCMEM:48,57,00,00";
+
+        let divergence = diff(&mut gameboy, reference(&lines), 1).unwrap().unwrap_err();
+
+        assert_eq!(divergence.line, 3);
+        assert_eq!(divergence.context, [LINES[1]]);
+        assert_eq!(divergence.actual.as_deref(), Some(LINES[2]));
+        assert_eq!(gameboy.cpu.program_counter, 0xC004);
+
+        let report = divergence.to_string();
+        assert!(report.starts_with("trace diverges at line 3\n  A:12 F:B0 B:00"));
+        assert!(report.contains(&format!("- {}\n+ {}\ndiffers in B\n", lines[2], LINES[2])));
+        assert!(report.contains("BC=3413"));
+        // LD B,$34 set B, and LD C,B was about to run.
+        let disassembly: Vec<&str> = divergence.disassembly.lines().collect();
+        assert_eq!(disassembly.len(), 2);
+        assert!(disassembly[0].contains("C002") && disassembly[0].contains("LD B,"), "{}", disassembly[0]);
+        assert!(disassembly[1].contains("C004") && disassembly[1].contains("LD C,B"), "{}", disassembly[1]);
+        assert!(report.ends_with(&divergence.disassembly));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::parse::{parse_address, parse_hex};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WatchKind {
+    Read,
+    Write,
+    // A write that leaves a different value behind.
+    Change,
+    // A write that changes the location to this value.
+    Value(u8),
+}
+
+// Watches every address in start..=end.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WatchHit {
+    pub kind: WatchKind,
+    // Start of the instruction that made the access.
+    pub pc: u16,
+    pub address: u16,
+    // Before and after a write; both the value read for a read.
+    pub old: u8,
+    pub new: u8,
+}
+
+impl Watchpoint {
+    pub fn new(start: u16, end: u16, kind: WatchKind) -> Self {
+        Watchpoint { start, end, kind }
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        (self.start..=self.end).contains(&address)
+    }
+
+    pub fn on_read(&self) -> bool {
+        self.kind == WatchKind::Read
+    }
+
+    pub fn triggers_on_write(&self, old: u8, new: u8) -> bool {
+        match self.kind {
+            WatchKind::Read => false,
+            WatchKind::Write => true,
+            WatchKind::Change => old != new,
+            WatchKind::Value(value) => new == value && old != value,
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write | WatchKind::Value(_) => "write",
+            WatchKind::Change => "change",
+        };
+        write!(f, "{}:{:04X}", kind, self.start)?;
+        if self.end != self.start {
+            write!(f, "-{:04X}", self.end)?;
+        }
+        if let WatchKind::Value(value) = self.kind {
+            write!(f, "={:02X}", value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    // KIND:ADDR[-END][=XX], where KIND is read, write or change and a value
+    // turns a write watch into one for that value. Numbers are hex.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, rest) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected KIND:ADDR, got '{}'", s))?;
+        let (range, value) = match rest.split_once('=') {
+            Some((range, value)) => {
+                let value = parse_hex::<u16>(value)?;
+                let value = u8::try_from(value).map_err(|_| format!("value '{:X}' is over FF", value))?;
+                (range, Some(value))
+            }
+            None => (rest, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+            None => (parse_address(range)?, parse_address(range)?),
+        };
+        if end < start {
+            return Err(format!("range '{}' ends before it starts", range));
+        }
+
+        let kind = match (kind, value) {
+            ("r" | "read", None) => WatchKind::Read,
+            ("w" | "write", None) => WatchKind::Write,
+            ("w" | "write", Some(value)) => WatchKind::Value(value),
+            ("c" | "change", None) => WatchKind::Change,
+            _ => return Err(format!("invalid watchpoint '{}'", s)),
+        };
+        Ok(Watchpoint::new(start, end, kind))
+    }
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            WatchKind::Read => write!(f, "read {:04X} at PC {:04X}: {:02X}", self.address, self.pc, self.new),
+            _ => write!(
+                f,
+                "write {:04X} at PC {:04X}: {:02X} -> {:02X}",
+                self.address, self.pc, self.old, self.new
+            ),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+    use crate::debugger::{Debugger, Stop};
+    use crate::gameboy::Gameboy;
+    use crate::ppu::LCDC;
+    use crate::testing::gameboy_with_program;
+    use crate::watch::*;
+
+    // HL points at `target`.
+    fn gameboy_writing(target: u16) -> Gameboy {
+        let mut gameboy = gameboy_with_program("ld a, $11\nld [hl], a\nld b, [hl]\nld [hl], a");
+        gameboy.cpu.set_hl(target);
+        gameboy
+    }
+
+    fn run(gameboy: &mut Gameboy, instructions: usize) -> Vec<WatchHit> {
+        let mut hits = Vec::new();
+        for _ in 0..instructions {
+            gameboy.execute_next();
+            hits.extend(gameboy.memory.take_watch_hits());
+        }
+        hits
+    }
+
+    #[test]
+    fn test_parse_watchpoints() {
+        assert_eq!("read:FF40".parse(), Ok(Watchpoint::new(0xFF40, 0xFF40, WatchKind::Read)));
+        assert_eq!("w:8000-9FFF".parse(), Ok(Watchpoint::new(0x8000, 0x9FFF, WatchKind::Write)));
+        assert_eq!("change:$C000".parse(), Ok(Watchpoint::new(0xC000, 0xC000, WatchKind::Change)));
+        assert_eq!("change:0XC000".parse(), Ok(Watchpoint::new(0xC000, 0xC000, WatchKind::Change)));
+        assert_eq!("write:FF40=91".parse(), Ok(Watchpoint::new(0xFF40, 0xFF40, WatchKind::Value(0x91))));
+
+        assert!("read:FF40=91".parse::<Watchpoint>().is_err());
+        assert!("write:FF40=100".parse::<Watchpoint>().is_err());
+        assert!("write:9FFF-8000".parse::<Watchpoint>().is_err());
+        assert!("FF40".parse::<Watchpoint>().is_err());
+
+        assert_eq!(Watchpoint::new(0x8000, 0x9FFF, WatchKind::Value(3)).to_string(), "write:8000-9FFF=03");
+    }
+
+    #[test]
+    fn test_write_watch_on_io_register() {
+        let mut gameboy = gameboy_writing(LCDC);
+        gameboy.memory.add_watchpoint("write:FF40".parse().unwrap());
+
+        let hits = run(&mut gameboy, 2);
+
+        assert_eq!(
+            hits,
+            [WatchHit {
+                kind: WatchKind::Write,
+                pc: 0xC002,
+                address: LCDC,
+                old: 0x00,
+                new: 0x11,
+            }]
+        );
+        assert_eq!(hits[0].to_string(), "write FF40 at PC C002: 00 -> 11");
+    }
+
+    #[test]
+    fn test_read_watch_over_range() {
+        let mut gameboy = gameboy_writing(0xD123);
+        gameboy.memory.add_watchpoint("read:D000-DFFF".parse().unwrap());
+
+        let hits = run(&mut gameboy, 3);
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].pc, hits[0].address, hits[0].new), (0xC003, 0xD123, 0x11));
+    }
+
+    #[test]
+    fn test_change_and_value_watches_ignore_same_value() {
+        let mut gameboy = gameboy_writing(0xD000);
+        gameboy.memory.add_watchpoint("change:D000".parse().unwrap());
+        gameboy.memory.add_watchpoint("write:D000=11".parse().unwrap());
+
+        // Both fire on the first store, neither on the second
+        let hits = run(&mut gameboy, 5);
+
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| hit.pc == 0xC002));
+    }
+
+    #[test]
+    fn test_accesses_outside_instructions_are_not_watched() {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.add_watchpoint("read:C000".parse().unwrap());
+        gameboy.memory.add_watchpoint("write:C000".parse().unwrap());
+
+        gameboy.memory.write_u8(0xC000, 1);
+        gameboy.memory.read_u8(0xC000);
+
+        assert!(!gameboy.memory.has_watch_hits());
+    }
+
+    #[test]
+    fn test_debugger_stops_on_watchpoint() {
+        let mut gameboy = gameboy_writing(LCDC);
+        let mut debugger = Debugger::new();
+
+        debugger.execute(&mut gameboy, "watch write:FF40=11").unwrap();
+        assert_eq!(debugger.execute(&mut gameboy, "watch").unwrap(), "0: write:FF40=11\n");
+
+        let output = debugger.execute(&mut gameboy, "continue").unwrap();
+        assert!(output.starts_with("watchpoint: write FF40 at PC C002: 00 -> 11\n"));
+        assert_eq!(gameboy.cpu.program_counter, 0xC003);
+
+        debugger.execute(&mut gameboy, "unwatch 0").unwrap();
+        assert!(debugger.execute(&mut gameboy, "unwatch 0").is_err());
+        assert!(matches!(debugger.run_until(&mut gameboy, Some(2), false), Stop::Steps));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::gameboy::Gameboy;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER_BYTES: u32 = 44;
+
+// 16-bit PCM WAV file. The header is rewritten on every flush so the file
+// stays playable even if the emulator is killed half way through.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32, channels: u16) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let block_align = channels * 2;
+
+        file.write_all(b"RIFF")?;
+        file.write_u32::<LittleEndian>(HEADER_BYTES - 8)?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_u32::<LittleEndian>(16)?;
+        file.write_u16::<LittleEndian>(1)?; // PCM
+        file.write_u16::<LittleEndian>(channels)?;
+        file.write_u32::<LittleEndian>(sample_rate)?;
+        file.write_u32::<LittleEndian>(sample_rate * block_align as u32)?;
+        file.write_u16::<LittleEndian>(block_align)?;
+        file.write_u16::<LittleEndian>(16)?;
+        file.write_all(b"data")?;
+        file.write_u32::<LittleEndian>(0)?;
+
+        Ok(WavWriter {
+            file,
+            data_bytes: 0,
+        })
+    }
+
+    // Samples are interleaved and in -1.0..=1.0; anything outside is clipped.
+    pub fn write_samples(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        for &sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_i16::<LittleEndian>(value)?;
+        }
+        self.data_bytes += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_u32::<LittleEndian>(HEADER_BYTES - 8 + self.data_bytes)?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_u32::<LittleEndian>(self.data_bytes)?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+// Records the APU output frame by frame. With per-channel capture on, each
+// channel also gets its own file next to the mix: out.wav, out.ch1.wav, ...
+pub struct AudioCapture {
+    mix: WavWriter,
+    channels: Option<Vec<WavWriter>>,
+}
+
+impl AudioCapture {
+    pub fn create<P: AsRef<Path>>(gameboy: &mut Gameboy, path: P, per_channel: bool) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let sample_rate = gameboy.memory.apu.sample_rate();
+        gameboy.memory.apu.set_channel_capture(per_channel);
+
+        let channels = if per_channel {
+            let writers = (1..=4)
+                .map(|channel| WavWriter::create(channel_path(path, channel), sample_rate, 2))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            Some(writers)
+        } else {
+            None
+        };
+
+        Ok(AudioCapture {
+            mix: WavWriter::create(path, sample_rate, 2)?,
+            channels,
+        })
+    }
+
+    // Appends the samples produced by the last `Gameboy::run_frame`.
+    pub fn write_frame(&mut self, gameboy: &Gameboy) -> std::io::Result<()> {
+        self.mix.write_samples(gameboy.audio_frame())?;
+        self.mix.flush()?;
+
+        if let Some(writers) = self.channels.as_mut() {
+            for (channel, writer) in writers.iter_mut().enumerate() {
+                writer.write_samples(gameboy.channel_audio_frame(channel))?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn channel_path(path: &Path, channel: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.ch{}.wav", stem, channel))
+}
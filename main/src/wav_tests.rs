@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use crate::apu::{NR22, NR24, NR50, NR51, NR52};
+    use crate::gameboy::Gameboy;
+    use crate::wav::AudioCapture;
+
+    fn beeping_gameboy() -> Gameboy {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.write_u8(NR52, 0x80);
+        gameboy.memory.write_u8(NR50, 0x77);
+        gameboy.memory.write_u8(NR51, 0x22);
+        gameboy.memory.write_u8(NR22, 0xF0);
+        gameboy.memory.write_u8(NR24, 0x87);
+        gameboy
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_frame_sample_buffer() {
+        let mut gameboy = beeping_gameboy();
+
+        gameboy.run_frame();
+
+        // 70224 cycles at 44.1kHz is just under 739 stereo samples.
+        let frames = gameboy.audio_frame().len() / 2;
+        assert!((738..=739).contains(&frames), "{} samples", frames);
+        assert!(gameboy.channel_audio_frame(1).is_empty());
+    }
+
+    #[test]
+    fn test_capture_writes_wav_files() {
+        let dir = std::env::temp_dir().join(format!("gb_wav_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.wav");
+
+        let mut gameboy = beeping_gameboy();
+        let mut capture = AudioCapture::create(&mut gameboy, &path, true).unwrap();
+        let mut samples = 0;
+        for _ in 0..3 {
+            // Memory is all NOPs; keep PC away from the I/O registers.
+            gameboy.cpu.program_counter = 0;
+            gameboy.run_frame();
+            samples += gameboy.audio_frame().len();
+            capture.write_frame(&gameboy).unwrap();
+        }
+
+        let mix = std::fs::read(&path).unwrap();
+        assert_eq!(&mix[0..4], b"RIFF");
+        assert_eq!(&mix[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&mix, 24), 44_100);
+        assert_eq!(u32_at(&mix, 40) as usize, samples * 2);
+        assert_eq!(mix.len(), 44 + samples * 2);
+
+        // Only channel 2 is playing, so its file matches the mix exactly.
+        let square2 = std::fs::read(dir.join("out.ch2.wav")).unwrap();
+        let noise = std::fs::read(dir.join("out.ch4.wav")).unwrap();
+        assert_eq!(square2, mix);
+        assert_eq!(noise.len(), mix.len());
+        assert!(noise[44..].iter().all(|&b| b == 0));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}